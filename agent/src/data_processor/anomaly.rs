@@ -0,0 +1,376 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use serde::Serialize;
+
+use crate::{
+    data_processor::{
+        events::{publish, StoreEvent},
+        sharded::ShardedMap,
+    },
+    helper::{
+        config::AppConfig,
+        metrics::{RingBuffer, SeriesKey},
+        time::now_secs,
+    },
+    log_print,
+};
+
+// 参与异常检测的序列，Node 上报与 agent 采样的 CPU、RSS、事件循环延迟
+pub const ANOMALY_SERIES: [&str; 5] = [
+    "cpu.load",
+    "memory.rss",
+    "event_loop.lag_p99",
+    "os.cpu.usage",
+    "os.memory.rss",
+];
+// EWMA 平滑系数，越大基线跟随越快
+const EWMA_ALPHA: f64 = 0.1;
+// 基线建立前至少需要的样本数，样本不足时只更新基线不做判断
+const WARMUP_SAMPLES: u64 = 30;
+// 标准差下限占均值的比例，避免平稳序列上的微小波动被放大成异常
+const MIN_STDDEV_RATIO: f64 = 0.01;
+// 每个进程最多保留的异常记录
+const MAX_ANOMALIES: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    // 高于基线
+    High,
+    // 低于基线
+    Low,
+}
+
+/// 一次异常及其持续的窗口，结束前 end 为 None
+#[derive(Debug, Clone, Serialize)]
+pub struct Anomaly {
+    pub series: String,
+    pub thread_id: Option<u32>,
+    pub direction: Direction,
+    // 窗口起止时间 timestamp millisecond
+    pub start: u64,
+    pub end: Option<u64>,
+    // 进入异常时的基线
+    pub baseline_mean: f64,
+    pub baseline_stddev: f64,
+    // 窗口内偏离最大的值及其 z-score
+    pub peak_value: f64,
+    pub peak_z_score: f64,
+    pub samples: u64,
+}
+
+/// 单条序列的 EWMA 基线
+#[derive(Debug, Clone, Serialize)]
+pub struct Baseline {
+    pub mean: f64,
+    // EWMA 方差
+    #[serde(skip)]
+    variance: f64,
+    pub stddev: f64,
+    pub samples: u64,
+    // 最近一个样本的 z-score，基线未建立时为 None
+    pub z_score: Option<f64>,
+    // 进行中的异常，结束后移入 ProcessBaselines::anomalies
+    #[serde(skip)]
+    active: Option<Anomaly>,
+}
+
+impl Baseline {
+    fn new(value: f64) -> Self {
+        Self {
+            mean: value,
+            variance: 0.0,
+            stddev: 0.0,
+            samples: 1,
+            z_score: None,
+            active: None,
+        }
+    }
+
+    fn effective_stddev(&self) -> f64 {
+        self.variance
+            .sqrt()
+            .max(self.mean.abs() * MIN_STDDEV_RATIO)
+            .max(f64::EPSILON)
+    }
+
+    /// 并入新样本，异常期间只更新均值，避免离群值把方差撑大后异常窗口提前结束
+    fn update(&mut self, value: f64) {
+        let diff = value - self.mean;
+        let increment = EWMA_ALPHA * diff;
+        self.mean += increment;
+        if self.active.is_none() {
+            self.variance = (1.0 - EWMA_ALPHA) * (self.variance + diff * increment);
+            self.stddev = self.variance.sqrt();
+        }
+        self.samples += 1;
+    }
+}
+
+/// 单个进程所有序列的基线和最近的异常记录
+#[derive(Debug)]
+pub struct ProcessBaselines {
+    baselines: HashMap<SeriesKey, Baseline>,
+    anomalies: RingBuffer<Anomaly>,
+}
+
+impl Default for ProcessBaselines {
+    fn default() -> Self {
+        Self {
+            baselines: HashMap::new(),
+            anomalies: RingBuffer::new(MAX_ANOMALIES),
+        }
+    }
+}
+
+impl ProcessBaselines {
+    /// 用新样本判断异常并更新基线，返回新开始的异常
+    ///
+    /// 先用更新前的基线计算 z-score，再把样本并入基线，持续偏离时均值会逐渐跟上新的水平
+    fn observe(
+        &mut self,
+        key: SeriesKey,
+        timestamp: u64,
+        value: f64,
+        threshold: f64,
+    ) -> Option<Anomaly> {
+        let Some(baseline) = self.baselines.get_mut(&key) else {
+            self.baselines.insert(key, Baseline::new(value));
+            return None;
+        };
+        if baseline.samples < WARMUP_SAMPLES {
+            baseline.update(value);
+            return None;
+        }
+
+        let stddev = baseline.effective_stddev();
+        let z_score = (value - baseline.mean) / stddev;
+        baseline.z_score = Some(z_score);
+        let anomalous = z_score.abs() >= threshold;
+        let direction = if z_score >= 0.0 {
+            Direction::High
+        } else {
+            Direction::Low
+        };
+
+        let mut started = None;
+        match baseline.active.as_mut() {
+            Some(active) if anomalous && active.direction == direction => {
+                active.samples += 1;
+                if z_score.abs() > active.peak_z_score.abs() {
+                    active.peak_value = value;
+                    active.peak_z_score = z_score;
+                }
+            }
+            _ => {
+                // 恢复正常或者反向偏离时结束当前窗口
+                if let Some(mut finished) = baseline.active.take() {
+                    finished.end = Some(timestamp);
+                    self.anomalies.push(finished);
+                }
+                if anomalous {
+                    let anomaly = Anomaly {
+                        series: key.name.clone(),
+                        thread_id: key.thread_id,
+                        direction,
+                        start: timestamp,
+                        end: None,
+                        baseline_mean: baseline.mean,
+                        baseline_stddev: stddev,
+                        peak_value: value,
+                        peak_z_score: z_score,
+                        samples: 1,
+                    };
+                    started = Some(anomaly.clone());
+                    baseline.active = Some(anomaly);
+                }
+            }
+        }
+        baseline.update(value);
+        started
+    }
+
+    /// 基线与异常记录占用的内存估算，单位 byte
+    pub fn estimated_bytes(&self) -> usize {
+        let baselines: usize = self
+            .baselines
+            .keys()
+            .map(|key| {
+                std::mem::size_of::<(SeriesKey, Baseline)>()
+                    + key.name.len()
+                    + std::mem::size_of::<Anomaly>()
+            })
+            .sum();
+        baselines + self.anomalies.allocated_bytes()
+    }
+
+    /// 各序列当前的基线
+    pub fn baselines(&self) -> Vec<(SeriesKey, Baseline)> {
+        let mut baselines: Vec<(SeriesKey, Baseline)> = self
+            .baselines
+            .iter()
+            .map(|(key, baseline)| (key.clone(), baseline.clone()))
+            .collect();
+        baselines.sort_by(|a, b| a.0.cmp(&b.0));
+        baselines
+    }
+
+    /// 进行中和已结束的异常，按开始时间从新到旧
+    pub fn anomalies(&self) -> Vec<Anomaly> {
+        let mut anomalies: Vec<Anomaly> = self
+            .anomalies
+            .iter()
+            .cloned()
+            .chain(
+                self.baselines
+                    .values()
+                    .filter_map(|baseline| baseline.active.clone()),
+            )
+            .collect();
+        anomalies.sort_by_key(|anomaly| std::cmp::Reverse(anomaly.start));
+        anomalies
+    }
+}
+
+/// 以 pid 为 key 的序列基线
+pub static ANOMALY_DETECTOR: LazyLock<ShardedMap<ProcessBaselines>> =
+    LazyLock::new(ShardedMap::new);
+
+/// 把刚写入的样本交给异常检测，只处理 ANOMALY_SERIES 中的序列
+pub fn observe_anomalies(pid: u32, thread_id: Option<u32>, timestamp: u64, values: &[(&str, f64)]) {
+    let values: Vec<&(&str, f64)> = values
+        .iter()
+        .filter(|(name, value)| ANOMALY_SERIES.contains(name) && value.is_finite())
+        .collect();
+    if values.is_empty() {
+        return;
+    }
+    let threshold = AppConfig::global().anomaly_z_threshold;
+
+    let started = ANOMALY_DETECTOR.upsert(pid, ProcessBaselines::default, |baselines| {
+        values
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let key = SeriesKey {
+                    name: name.to_string(),
+                    thread_id,
+                };
+                baselines.observe(key, timestamp, *value, threshold)
+            })
+            .collect::<Vec<_>>()
+    });
+
+    for anomaly in started {
+        log_print!(
+            "⚠️ 进程 {} {} 偏离基线，值 {} 基线 {:.2} z-score {:.2}",
+            pid,
+            anomaly.series,
+            anomaly.peak_value,
+            anomaly.baseline_mean,
+            anomaly.peak_z_score
+        );
+        publish(StoreEvent::AnomalyDetected {
+            process_id: pid,
+            thread_id: anomaly.thread_id,
+            series: anomaly.series,
+            value: anomaly.peak_value,
+            baseline: anomaly.baseline_mean,
+            z_score: anomaly.peak_z_score,
+            time: now_secs(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> SeriesKey {
+        SeriesKey {
+            name: "cpu.load".to_string(),
+            thread_id: None,
+        }
+    }
+
+    /// 用在 100 上下小幅波动的样本建立基线
+    fn warmed_up() -> ProcessBaselines {
+        let mut baselines = ProcessBaselines::default();
+        for i in 0..WARMUP_SAMPLES {
+            let value = if i % 2 == 0 { 98.0 } else { 102.0 };
+            assert!(baselines.observe(key(), i, value, 3.0).is_none());
+        }
+        baselines
+    }
+
+    #[test]
+    fn no_anomaly_during_warmup() {
+        let mut baselines = ProcessBaselines::default();
+        for i in 0..WARMUP_SAMPLES {
+            // 预热期间即使剧烈波动也不判断
+            let value = if i % 2 == 0 { 1.0 } else { 1_000.0 };
+            assert!(baselines.observe(key(), i, value, 3.0).is_none());
+        }
+        let (_, baseline) = &baselines.baselines()[0];
+        assert_eq!(baseline.samples, WARMUP_SAMPLES);
+        assert!(baseline.z_score.is_none());
+    }
+
+    #[test]
+    fn spike_opens_window_and_recovery_closes_it() {
+        let mut baselines = warmed_up();
+        let started = baselines.observe(key(), 100, 200.0, 3.0).unwrap();
+        assert_eq!(started.direction, Direction::High);
+        assert!(started.peak_z_score >= 3.0, "{}", started.peak_z_score);
+        // 持续偏离只更新峰值，不重复开始
+        assert!(baselines.observe(key(), 101, 250.0, 3.0).is_none());
+
+        let active = baselines.anomalies();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].end, None);
+        assert_eq!(active[0].samples, 2);
+        assert_eq!(active[0].peak_value, 250.0);
+
+        // 异常期间方差不变，回到基线附近即结束窗口
+        let mean = baselines.baselines()[0].1.mean;
+        assert!(baselines.observe(key(), 102, mean, 3.0).is_none());
+        let finished = baselines.anomalies();
+        assert_eq!(finished.len(), 1);
+        assert_eq!((finished[0].start, finished[0].end), (100, Some(102)));
+    }
+
+    #[test]
+    fn reversed_deviation_starts_a_new_window() {
+        let mut baselines = warmed_up();
+        baselines.observe(key(), 100, 200.0, 3.0).unwrap();
+        let low = baselines.observe(key(), 101, 0.0, 3.0).unwrap();
+        assert_eq!(low.direction, Direction::Low);
+        let anomalies = baselines.anomalies();
+        assert_eq!(anomalies.len(), 2);
+        // 从新到旧
+        assert_eq!(anomalies[0].direction, Direction::Low);
+        assert_eq!(anomalies[1].end, Some(101));
+    }
+
+    #[test]
+    fn flat_series_uses_stddev_floor() {
+        let mut baselines = ProcessBaselines::default();
+        for i in 0..WARMUP_SAMPLES {
+            baselines.observe(key(), i, 100.0, 3.0);
+        }
+        // 方差为 0 时下限为均值的 1%，2% 的波动不算异常
+        assert!(baselines.observe(key(), 100, 102.0, 3.0).is_none());
+        let (_, baseline) = &baselines.baselines()[0];
+        assert!((baseline.z_score.unwrap() - 2.0).abs() < 1e-9);
+        assert!(baselines.observe(key(), 101, 110.0, 3.0).is_some());
+    }
+
+    #[test]
+    fn ewma_mean_follows_level_shift() {
+        let mut baselines = warmed_up();
+        for i in 0..200 {
+            baselines.observe(key(), 100 + i, 150.0, 3.0);
+        }
+        let (_, baseline) = &baselines.baselines()[0];
+        assert!((baseline.mean - 150.0).abs() < 1.0, "{}", baseline.mean);
+    }
+}
@@ -0,0 +1,220 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::{task, time::interval};
+
+use crate::{
+    data_processor::{
+        anomaly::ANOMALY_DETECTOR,
+        events::RemoveReason,
+        histograms::HISTOGRAM_STORE,
+        leaks::HANDLE_TRACKER,
+        sampler::THREAD_SNAPSHOTS,
+        store::{ProcessStore, PROCESS_DATA, PROCESS_MAP_STORE},
+    },
+    exporter,
+    helper::{
+        config::AppConfig,
+        metrics::{remove_process_data, METRICS_STORE},
+        time::now_millis,
+    },
+    log_print,
+};
+
+// 检查内存预算的间隔
+const ENFORCE_INTERVAL: Duration = Duration::from_secs(10);
+// 按时间清理时依次尝试的保留时长，单位毫秒
+const RETENTION_STEPS: [u64; 7] = [
+    43_200_000, 21_600_000, 10_800_000, 3_600_000, 1_800_000, 900_000, 600_000,
+];
+
+/// 各模块占用的内存估算，单位 byte
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryUsage {
+    pub budget: usize,
+    pub total: usize,
+    pub process_store: usize,
+    pub metrics: usize,
+    pub histograms: usize,
+    pub leaks: usize,
+    pub anomalies: usize,
+    pub threads: usize,
+    pub exports: usize,
+}
+
+pub fn memory_usage() -> MemoryUsage {
+    let mut usage = MemoryUsage {
+        budget: AppConfig::global().memory_budget,
+        total: 0,
+        process_store: 0,
+        metrics: METRICS_STORE.estimated_bytes(),
+        histograms: 0,
+        leaks: 0,
+        anomalies: 0,
+        threads: 0,
+        exports: exporter::buffered_bytes(),
+    };
+    PROCESS_DATA.for_each(|_, process| usage.process_store += process.estimated_bytes());
+    HISTOGRAM_STORE.for_each(|_, histograms| usage.histograms += histograms.estimated_bytes());
+    HANDLE_TRACKER.for_each(|_, history| usage.leaks += history.estimated_bytes());
+    ANOMALY_DETECTOR.for_each(|_, baselines| usage.anomalies += baselines.estimated_bytes());
+    THREAD_SNAPSHOTS.for_each(|_, snapshot| usage.threads += snapshot.estimated_bytes());
+    usage.total = usage.process_store
+        + usage.metrics
+        + usage.histograms
+        + usage.leaks
+        + usage.anomalies
+        + usage.threads
+        + usage.exports;
+    usage
+}
+
+/// 优先级低的排在前面，同优先级时最久没有心跳的排在前面
+fn eviction_order() -> Vec<ProcessStore> {
+    let mut processes = PROCESS_DATA.values();
+    processes.sort_by_key(|process| {
+        (
+            process.priority,
+            process.latest_heartbeat_time,
+            process.process_id,
+        )
+    });
+    processes
+}
+
+/// 除待导出数据以外的占用是否超出预算
+///
+/// 待导出数据在导出任务发送完当前批次后释放，不能靠清理其他数据降下来
+fn over_budget(budget: usize) -> bool {
+    let usage = memory_usage();
+    usage.total - usage.exports > budget
+}
+
+/// 超出预算时依次清理，直到回到预算以内：
+/// 0. 要求导出任务释放待导出数据，并缩小之后每轮导出的数据量
+/// 1. 所有进程中最旧的数据，保留时长逐级缩短到 10 分钟，尚未导出的原始样本除外
+/// 2. 优先级最低的进程的指标数据
+/// 3. 优先级最低的进程本身
+fn enforce_budget() {
+    let budget = AppConfig::global().memory_budget;
+    let usage = memory_usage();
+    if usage.total <= budget {
+        exporter::relax_buffers();
+        return;
+    }
+    log_print!(
+        "⚠️ 内存占用 {}KB 超出预算 {}KB，开始清理",
+        usage.total / 1024,
+        budget / 1024
+    );
+
+    if usage.exports > 0 {
+        exporter::shrink_buffers();
+        log_print!("🧹 已要求释放 {}KB 待导出数据", usage.exports / 1024);
+        if !over_budget(budget) {
+            return;
+        }
+    }
+
+    let now = now_millis();
+    for retention in RETENTION_STEPS {
+        let cutoff = now.saturating_sub(retention);
+        METRICS_STORE.evict_before(cutoff);
+        HISTOGRAM_STORE.retain(|_, histograms| {
+            histograms.evict_before(cutoff);
+            true
+        });
+        HANDLE_TRACKER.retain(|_, history| {
+            history.evict_before(cutoff);
+            true
+        });
+        if !over_budget(budget) {
+            log_print!("🧹 已清理 {} 分钟之前的数据", retention / 60_000);
+            return;
+        }
+    }
+
+    for process in eviction_order() {
+        // 保留注册信息，只清理指标数据
+        remove_process_data(process.process_id);
+        log_print!(
+            "🧹 已清理进程 {} 的指标数据（优先级 {}）",
+            process.process_id,
+            process.priority
+        );
+        if !over_budget(budget) {
+            return;
+        }
+    }
+
+    for process in eviction_order() {
+        if PROCESS_MAP_STORE
+            .remove(&process.process_id, RemoveReason::OverBudget)
+            .is_some()
+        {
+            log_print!(
+                "🧹 内存预算不足，移除进程 {}（优先级 {}）",
+                process.process_id,
+                process.priority
+            );
+        }
+        if !over_budget(budget) {
+            return;
+        }
+    }
+}
+
+/// 在后台定时检查内存预算
+pub fn start_budget_enforcer() {
+    task::spawn(async {
+        let mut ticker = interval(ENFORCE_INTERVAL);
+        loop {
+            ticker.tick().await;
+            enforce_budget();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_usage_total_sums_subsystems() {
+        let usage = memory_usage();
+        assert_eq!(
+            usage.total,
+            usage.process_store
+                + usage.metrics
+                + usage.histograms
+                + usage.leaks
+                + usage.anomalies
+                + usage.threads
+                + usage.exports
+        );
+        assert_eq!(usage.budget, AppConfig::global().memory_budget);
+    }
+
+    #[test]
+    fn lower_priority_and_stale_processes_are_evicted_first() {
+        let process = |pid: u32, priority: i32, heartbeat: u64| ProcessStore {
+            priority,
+            latest_heartbeat_time: heartbeat,
+            ..ProcessStore::for_test(pid, "budget-test")
+        };
+        let pids = [4_102_401, 4_102_402, 4_102_403];
+        PROCESS_MAP_STORE.register(process(pids[0], 1, 100));
+        PROCESS_MAP_STORE.register(process(pids[1], 0, 200));
+        PROCESS_MAP_STORE.register(process(pids[2], 0, 100));
+
+        let order: Vec<u32> = eviction_order()
+            .into_iter()
+            .map(|process| process.process_id)
+            .filter(|pid| pids.contains(pid))
+            .collect();
+        assert_eq!(order, [pids[2], pids[1], pids[0]]);
+        for pid in pids {
+            PROCESS_MAP_STORE.remove(&pid, RemoveReason::OverBudget);
+        }
+    }
+}
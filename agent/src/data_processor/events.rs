@@ -0,0 +1,161 @@
+use std::sync::LazyLock;
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::data_processor::{rules::Alert, store::MetricType};
+
+// 广播通道容量，订阅者处理过慢时会丢弃最旧的事件（recv 返回 Lagged）
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// 进程被移除的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoveReason {
+    // 心跳超时被清理
+    Evicted,
+    // 超出内存预算被清理
+    OverBudget,
+}
+
+/// store 数据变化事件，时间均为 timestamp second
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StoreEvent {
+    ProcessRegistered {
+        process_id: u32,
+        session_id: String,
+        app_name: String,
+        time: u64,
+    },
+    HeartbeatReceived {
+        process_id: u32,
+        time: u64,
+    },
+    MetricIngested {
+        process_id: u32,
+        thread_id: Option<u32>,
+        metric_type: MetricType,
+        time: u64,
+    },
+    // 事件循环延迟超过阈值，只在状态变化时发布
+    EventLoopBlocked {
+        process_id: u32,
+        thread_id: Option<u32>,
+        // 单位毫秒
+        lag_max: f64,
+        threshold: f64,
+        time: u64,
+    },
+    EventLoopRecovered {
+        process_id: u32,
+        thread_id: Option<u32>,
+        lag_max: f64,
+        time: u64,
+    },
+    // 创建栈的句柄数量在窗口内持续增长，只在首次判定时发布
+    LeakSuspected {
+        process_id: u32,
+        kind: String,
+        stack: String,
+        count: u64,
+        growth: u64,
+        time: u64,
+    },
+    // 序列偏离自身的 EWMA 基线，每个异常窗口只在开始时发布一次
+    AnomalyDetected {
+        process_id: u32,
+        thread_id: Option<u32>,
+        series: String,
+        value: f64,
+        baseline: f64,
+        z_score: f64,
+        time: u64,
+    },
+    // 告警触发或恢复，同一告警只在状态变化时发布
+    Alert {
+        alert: Alert,
+    },
+    ProcessStale {
+        process_id: u32,
+        // 最近一次心跳时间
+        latest_heartbeat_time: u64,
+        time: u64,
+    },
+    ProcessExited {
+        process_id: u32,
+        time: u64,
+    },
+    ProcessRemoved {
+        process_id: u32,
+        reason: RemoveReason,
+        time: u64,
+    },
+}
+
+static EVENT_BUS: LazyLock<broadcast::Sender<StoreEvent>> =
+    LazyLock::new(|| broadcast::channel(EVENT_CHANNEL_CAPACITY).0);
+
+/// 发布事件，没有订阅者时直接丢弃
+pub fn publish(event: StoreEvent) {
+    let _ = EVENT_BUS.send(event);
+}
+
+/// 订阅 store 事件，只能收到订阅之后发布的事件
+pub fn subscribe() -> broadcast::Receiver<StoreEvent> {
+    EVENT_BUS.subscribe()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_processor::store::{ProcessStore, PROCESS_MAP_STORE};
+
+    #[tokio::test]
+    async fn subscribers_receive_store_events() {
+        let pid = 4_100_801;
+        let mut receiver = subscribe();
+        PROCESS_MAP_STORE.register(ProcessStore::for_test(pid, "demo"));
+
+        // 其他测试可能并行发布事件，只关心本测试的进程
+        loop {
+            match receiver.recv().await.unwrap() {
+                StoreEvent::ProcessRegistered {
+                    process_id,
+                    app_name,
+                    ..
+                } if process_id == pid => {
+                    assert_eq!(app_name, "demo");
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn events_serialize_with_snake_case_type_tag() {
+        let event = StoreEvent::ProcessRemoved {
+            process_id: 1,
+            reason: RemoveReason::Evicted,
+            time: 2,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "type": "process_removed",
+                "process_id": 1,
+                "reason": "evicted",
+                "time": 2,
+            })
+        );
+    }
+
+    #[test]
+    fn publish_without_subscribers_does_not_fail() {
+        publish(StoreEvent::ProcessExited {
+            process_id: 1,
+            time: 1,
+        });
+    }
+}
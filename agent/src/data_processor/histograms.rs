@@ -0,0 +1,403 @@
+use std::{collections::BTreeMap, sync::LazyLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data_processor::{query::GroupBy, sharded::ShardedMap, store::PROCESS_MAP_STORE},
+    helper::{
+        histogram::Histogram,
+        metrics::{Resolution, RingBuffer, SeriesKey},
+        time::{now_millis, parse_duration_ms},
+    },
+};
+
+// 未指定 from 时默认查询最近 5 分钟
+const DEFAULT_RANGE: u64 = 300_000;
+// 单次查询最多返回的 step 数量
+const MAX_STEPS: u64 = 1_000;
+// 未指定 quantiles 时返回的分位数
+const DEFAULT_QUANTILES: [f64; 3] = [0.5, 0.9, 0.99];
+
+/// 对齐到桶宽度的一段时间内合并后的直方图
+#[derive(Debug, Clone)]
+struct TimedHistogram {
+    // 桶起点 timestamp millisecond
+    timestamp: u64,
+    histogram: Histogram,
+}
+
+/// 固定宽度的直方图时间桶，新数据合并进当前桶
+#[derive(Debug)]
+struct HistogramTier {
+    resolution: Resolution,
+    buckets: RingBuffer<TimedHistogram>,
+}
+
+impl HistogramTier {
+    fn new(resolution: Resolution) -> Self {
+        Self {
+            resolution,
+            buckets: RingBuffer::new((resolution.retention() / resolution.width()) as usize),
+        }
+    }
+
+    fn push(&mut self, timestamp: u64, histogram: &Histogram) {
+        let width = self.resolution.width();
+        let start = timestamp - timestamp % width;
+        if let Some(latest) = self.buckets.iter_mut().next_back() {
+            if latest.timestamp == start {
+                latest.histogram.merge(histogram);
+                return;
+            }
+            // 乱序上报的旧数据直接丢弃
+            if latest.timestamp > start {
+                return;
+            }
+        }
+        self.buckets.push(TimedHistogram {
+            timestamp: start,
+            histogram: histogram.clone(),
+        });
+    }
+
+    fn estimated_bytes(&self) -> usize {
+        self.buckets.allocated_bytes()
+            + self
+                .buckets
+                .iter()
+                .map(|bucket| bucket.histogram.estimated_bytes())
+                .sum::<usize>()
+    }
+
+    fn evict_before(&mut self, cutoff: u64) {
+        self.buckets.evict_while(|bucket| bucket.timestamp < cutoff);
+        self.buckets.shrink_to_fit();
+    }
+}
+
+/// 单个直方图的 10s 与 1m 两层聚合，没有原始精度
+#[derive(Debug)]
+pub struct HistogramSeries {
+    ten_seconds: HistogramTier,
+    one_minute: HistogramTier,
+}
+
+impl Default for HistogramSeries {
+    fn default() -> Self {
+        Self {
+            ten_seconds: HistogramTier::new(Resolution::TenSeconds),
+            one_minute: HistogramTier::new(Resolution::OneMinute),
+        }
+    }
+}
+
+impl HistogramSeries {
+    pub fn push(&mut self, timestamp: u64, histogram: &Histogram) {
+        self.ten_seconds.push(timestamp, histogram);
+        self.one_minute.push(timestamp, histogram);
+    }
+
+    fn tier(&self, resolution: Resolution) -> &HistogramTier {
+        match resolution {
+            Resolution::OneMinute => &self.one_minute,
+            _ => &self.ten_seconds,
+        }
+    }
+}
+
+/// 单个进程的所有直方图，name 为上报的直方图名称
+#[derive(Debug, Default)]
+pub struct ProcessHistograms {
+    series: BTreeMap<SeriesKey, HistogramSeries>,
+}
+
+impl ProcessHistograms {
+    pub fn estimated_bytes(&self) -> usize {
+        self.series
+            .iter()
+            .map(|(key, series)| {
+                std::mem::size_of::<SeriesKey>()
+                    + key.name.len()
+                    + series.ten_seconds.estimated_bytes()
+                    + series.one_minute.estimated_bytes()
+            })
+            .sum()
+    }
+
+    /// 移除早于 cutoff 的时间桶，没有数据的直方图一并移除
+    pub fn evict_before(&mut self, cutoff: u64) {
+        for series in self.series.values_mut() {
+            series.ten_seconds.evict_before(cutoff);
+            series.one_minute.evict_before(cutoff);
+        }
+        self.series
+            .retain(|_, series| !series.one_minute.buckets.is_empty());
+    }
+}
+
+/// 以 pid 为 key 的直方图存储
+pub static HISTOGRAM_STORE: LazyLock<ShardedMap<ProcessHistograms>> =
+    LazyLock::new(ShardedMap::new);
+
+pub fn record_histogram(
+    pid: u32,
+    thread_id: Option<u32>,
+    timestamp: u64,
+    name: &str,
+    histogram: &Histogram,
+) {
+    if histogram.is_empty() {
+        return;
+    }
+    let key = SeriesKey {
+        name: name.to_string(),
+        thread_id,
+    };
+    HISTOGRAM_STORE.upsert(pid, ProcessHistograms::default, |histograms| {
+        histograms
+            .series
+            .entry(key)
+            .or_default()
+            .push(timestamp, histogram);
+    });
+}
+
+/// 查询参数，时间均为 timestamp millisecond
+#[derive(Debug, Default, Deserialize)]
+pub struct HistogramQuery {
+    // 直方图名称，不指定时返回所有直方图
+    pub name: Option<String>,
+    // 只查询指定进程
+    pub pid: Option<u32>,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    // 指定时额外返回每个 step 的分位数，例如 1m
+    pub step: Option<String>,
+    // 逗号分隔的分位数，例如 0.5,0.9,0.99
+    pub quantiles: Option<String>,
+    #[serde(default)]
+    pub group_by: GroupBy,
+}
+
+/// 合并后的直方图摘要，quantiles 的 key 形如 p50、p99、p99.9
+#[derive(Debug, Serialize)]
+pub struct HistogramSummary {
+    pub count: u64,
+    pub sum: f64,
+    pub avg: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub quantiles: BTreeMap<String, Option<f64>>,
+}
+
+impl HistogramSummary {
+    fn new(histogram: &Histogram, quantiles: &[f64]) -> Self {
+        let non_empty = !histogram.is_empty();
+        Self {
+            count: histogram.count,
+            sum: histogram.sum,
+            avg: histogram.avg(),
+            min: non_empty.then_some(histogram.min),
+            max: non_empty.then_some(histogram.max),
+            quantiles: quantiles
+                .iter()
+                .map(|q| (quantile_label(*q), histogram.quantile(*q)))
+                .collect(),
+        }
+    }
+}
+
+fn quantile_label(q: f64) -> String {
+    format!("p{}", (q * 1000.0).round() / 10.0)
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistogramResult {
+    pub name: String,
+    pub thread_id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process_id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_name: Option<String>,
+    // 整个查询范围合并后的摘要
+    pub summary: HistogramSummary,
+    // 指定 step 时每个 step 的摘要，没有数据的 step 为 null
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub points: Option<Vec<(u64, Option<HistogramSummary>)>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistogramQueryResult {
+    pub from: u64,
+    pub to: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub step: Option<u64>,
+    pub resolution: Resolution,
+    pub histograms: Vec<HistogramResult>,
+}
+
+/// 校验后的查询范围，start 按 step 或桶宽度对齐
+struct HistogramRange {
+    start: u64,
+    to: u64,
+    step: Option<u64>,
+    resolution: Resolution,
+    quantiles: Vec<f64>,
+}
+
+fn resolve_range(query: &HistogramQuery) -> Result<HistogramRange, String> {
+    let now = now_millis();
+    let to = query.to.unwrap_or(now);
+    let from = query.from.unwrap_or(to.saturating_sub(DEFAULT_RANGE));
+    if from >= to {
+        return Err(format!("from {} 必须小于 to {}", from, to));
+    }
+    // 直方图没有原始精度，最近 1 小时内使用 10s 聚合
+    let resolution = match Resolution::for_range(from, now) {
+        Resolution::Raw => Resolution::TenSeconds,
+        resolution => resolution,
+    };
+    let step = match &query.step {
+        Some(step) => Some(
+            parse_duration_ms(step)
+                .filter(|step| *step > 0)
+                .ok_or_else(|| format!("step {} 无效，示例: 10s、1m", step))?
+                .max(resolution.width()),
+        ),
+        None => None,
+    };
+    let align = step.unwrap_or(resolution.width());
+    let start = from - from % align;
+    if step.is_some_and(|step| (to - start) / step >= MAX_STEPS) {
+        return Err(format!(
+            "查询范围内的 step 数量超过 {}，请增大 step",
+            MAX_STEPS
+        ));
+    }
+    let quantiles = match &query.quantiles {
+        Some(quantiles) => quantiles
+            .split(',')
+            .map(|q| {
+                q.trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|q| (0.0..=1.0).contains(q))
+                    .ok_or_else(|| format!("分位数 {} 无效，范围为 0~1", q.trim()))
+            })
+            .collect::<Result<Vec<f64>, String>>()?,
+        None => DEFAULT_QUANTILES.to_vec(),
+    };
+    Ok(HistogramRange {
+        start,
+        to,
+        step,
+        resolution,
+        quantiles,
+    })
+}
+
+/// 整个范围以及每个 step 合并后的直方图
+#[derive(Default)]
+struct MergedHistogram {
+    total: Histogram,
+    steps: Vec<Histogram>,
+}
+
+impl MergedHistogram {
+    fn add(&mut self, range: &HistogramRange, timestamp: u64, histogram: &Histogram) {
+        self.total.merge(histogram);
+        if let Some(step) = range.step {
+            let steps = ((range.to - range.start) / step + 1) as usize;
+            self.steps.resize_with(steps, Histogram::default);
+            let index = ((timestamp - range.start) / step) as usize;
+            if let Some(slot) = self.steps.get_mut(index) {
+                slot.merge(histogram);
+            }
+        }
+    }
+}
+
+/// 跨进程合并直方图，按 group_by 分组后计算分位数
+pub fn query_histograms(query: &HistogramQuery) -> Result<HistogramQueryResult, String> {
+    let range = resolve_range(query)?;
+    let mut processes = PROCESS_MAP_STORE.list();
+    processes.retain(|process| query.pid.is_none_or(|pid| pid == process.process_id));
+    processes.sort_by_key(|process| process.process_id);
+
+    // (分组标识, 序列) -> (pid, app_name, 合并结果)
+    type Group = (Option<u32>, Option<String>, MergedHistogram);
+    let mut groups: BTreeMap<(String, SeriesKey), Group> = BTreeMap::new();
+    for process in &processes {
+        let (group, process_id, app_name) = match query.group_by {
+            GroupBy::Pid => (
+                format!("{:010}", process.process_id),
+                Some(process.process_id),
+                Some(process.app_name.clone()),
+            ),
+            GroupBy::App => (
+                process.app_name.clone(),
+                None,
+                Some(process.app_name.clone()),
+            ),
+            GroupBy::All => (String::new(), None, None),
+        };
+        HISTOGRAM_STORE.read(process.process_id, |histograms| {
+            let matching = histograms
+                .series
+                .iter()
+                .filter(|(key, _)| query.name.as_ref().is_none_or(|name| key.name == *name));
+            for (key, series) in matching {
+                let buckets = series
+                    .tier(range.resolution)
+                    .buckets
+                    .iter()
+                    .filter(|bucket| {
+                        bucket.timestamp >= range.start && bucket.timestamp <= range.to
+                    });
+                for bucket in buckets {
+                    let (_, _, merged) =
+                        groups
+                            .entry((group.clone(), key.clone()))
+                            .or_insert_with(|| {
+                                (process_id, app_name.clone(), MergedHistogram::default())
+                            });
+                    merged.add(&range, bucket.timestamp, &bucket.histogram);
+                }
+            }
+        });
+    }
+
+    let histograms = groups
+        .into_iter()
+        .map(
+            |((_, key), (process_id, app_name, merged))| HistogramResult {
+                name: key.name,
+                thread_id: key.thread_id,
+                process_id,
+                app_name,
+                summary: HistogramSummary::new(&merged.total, &range.quantiles),
+                points: range.step.map(|step| {
+                    merged
+                        .steps
+                        .iter()
+                        .enumerate()
+                        .map(|(index, histogram)| {
+                            let timestamp = range.start + index as u64 * step;
+                            let summary = (!histogram.is_empty())
+                                .then(|| HistogramSummary::new(histogram, &range.quantiles));
+                            (timestamp, summary)
+                        })
+                        .collect()
+                }),
+            },
+        )
+        .collect();
+
+    Ok(HistogramQueryResult {
+        from: range.start,
+        to: range.to,
+        step: range.step,
+        resolution: range.resolution,
+        histograms,
+    })
+}
@@ -0,0 +1,347 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use serde::Serialize;
+
+use crate::{
+    data_processor::{
+        events::{publish, StoreEvent},
+        sharded::ShardedMap,
+        store::HandleGroup,
+    },
+    helper::{metrics::RingBuffer, time::now_secs},
+    log_print,
+};
+
+// 每个进程最多跟踪的创建栈数量，超出时只保留数量最多的
+const MAX_TRACKED_STACKS: usize = 200;
+// 同一创建栈两条记录的最小间隔，更密集的上报只更新最新一条记录
+const MIN_POINT_INTERVAL_MS: u64 = 1_000;
+// 检测窗口上限，单位秒，限制每个创建栈保留的历史记录数量
+pub const MAX_LEAK_WINDOW: u64 = 3_600;
+// 判定为持续增长至少需要的记录数
+const MIN_SAMPLES: usize = 5;
+// 每个进程返回的疑似泄漏数量
+pub const TOP_SUSPECTS: usize = 10;
+
+/// 句柄、定时器、请求按 (类型, 创建栈) 聚合
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct StackKey {
+    kind: String,
+    stack: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CountPoint {
+    // timestamp millisecond
+    timestamp: u64,
+    count: u64,
+}
+
+#[derive(Debug)]
+struct StackHistory {
+    points: RingBuffer<CountPoint>,
+    // 已发布过疑似泄漏事件，恢复正常前不重复发布
+    flagged: bool,
+}
+
+impl StackHistory {
+    /// 容量按窗口与最小记录间隔计算，任意上报频率下都能覆盖整个窗口
+    fn new(window: u64) -> Self {
+        Self {
+            points: RingBuffer::new((window / MIN_POINT_INTERVAL_MS) as usize + 1),
+            flagged: false,
+        }
+    }
+
+    /// 写入一条记录，距上一条不足 MIN_POINT_INTERVAL_MS 时只更新上一条的数量
+    fn record(&mut self, point: CountPoint, window: u64) {
+        let merged = self
+            .points
+            .iter_mut()
+            .next_back()
+            .filter(|latest| point.timestamp < latest.timestamp + MIN_POINT_INTERVAL_MS)
+            .map(|latest| latest.count = point.count)
+            .is_some();
+        if !merged {
+            self.points.push(point);
+        }
+        // 窗口之外的记录不再参与判断
+        let expire_before = point.timestamp.saturating_sub(window);
+        self.points
+            .evict_while(|point| point.timestamp < expire_before);
+    }
+}
+
+/// 疑似泄漏的创建栈
+#[derive(Debug, Clone, Serialize)]
+pub struct LeakSuspect {
+    pub kind: String,
+    pub stack: String,
+    // 窗口内第一条记录的数量
+    pub first_count: u64,
+    pub count: u64,
+    pub growth: u64,
+    // 每分钟增长数量
+    pub growth_per_minute: f64,
+    // 窗口内第一条记录的时间 timestamp millisecond
+    pub since: u64,
+    pub samples: usize,
+}
+
+/// 单个进程各创建栈的数量历史
+#[derive(Debug, Default)]
+pub struct HandleHistory {
+    stacks: HashMap<StackKey, StackHistory>,
+}
+
+impl HandleHistory {
+    /// 窗口内数量单调不减且有增长时视为疑似泄漏
+    fn suspect(
+        key: &StackKey,
+        history: &StackHistory,
+        now: u64,
+        window: u64,
+    ) -> Option<LeakSuspect> {
+        let start = now.saturating_sub(window);
+        let points: Vec<&CountPoint> = history
+            .points
+            .iter()
+            .filter(|point| point.timestamp >= start)
+            .collect();
+        if points.len() < MIN_SAMPLES {
+            return None;
+        }
+        let monotonic = points.windows(2).all(|pair| pair[1].count >= pair[0].count);
+        let (first, last) = (points.first()?, points.last()?);
+        if !monotonic || last.count <= first.count {
+            return None;
+        }
+        let growth = last.count - first.count;
+        let minutes = (last.timestamp.saturating_sub(first.timestamp)) as f64 / 60_000.0;
+        Some(LeakSuspect {
+            kind: key.kind.clone(),
+            stack: key.stack.clone(),
+            first_count: first.count,
+            count: last.count,
+            growth,
+            growth_per_minute: if minutes > 0.0 {
+                growth as f64 / minutes
+            } else {
+                0.0
+            },
+            since: first.timestamp,
+            samples: points.len(),
+        })
+    }
+
+    /// 创建栈与数量历史占用的内存估算，单位 byte
+    pub fn estimated_bytes(&self) -> usize {
+        self.stacks
+            .iter()
+            .map(|(key, history)| {
+                std::mem::size_of::<(StackKey, StackHistory)>()
+                    + key.kind.len()
+                    + key.stack.len()
+                    + history.points.allocated_bytes()
+            })
+            .sum()
+    }
+
+    /// 移除早于 cutoff 的数量记录，没有记录的创建栈一并移除
+    pub fn evict_before(&mut self, cutoff: u64) {
+        for history in self.stacks.values_mut() {
+            history.points.evict_while(|point| point.timestamp < cutoff);
+            history.points.shrink_to_fit();
+        }
+        self.stacks.retain(|_, history| !history.points.is_empty());
+    }
+
+    /// 按增长数量排序的疑似泄漏
+    pub fn suspects(&self, now: u64, window: u64) -> Vec<LeakSuspect> {
+        let mut suspects: Vec<LeakSuspect> = self
+            .stacks
+            .iter()
+            .filter_map(|(key, history)| Self::suspect(key, history, now, window))
+            .collect();
+        suspects.sort_by(|a, b| b.growth.cmp(&a.growth).then(a.stack.cmp(&b.stack)));
+        suspects
+    }
+}
+
+/// 以 pid 为 key 的句柄数量历史
+pub static HANDLE_TRACKER: LazyLock<ShardedMap<HandleHistory>> = LazyLock::new(ShardedMap::new);
+
+/// 记录一次上报的分组数量，上报中不存在的创建栈视为数量归零，直接丢弃其历史
+pub fn record_handles(pid: u32, timestamp: u64, groups: &[HandleGroup], window: u64) {
+    // 同一 (类型, 创建栈) 可能被拆成多条上报，先合并
+    let mut counts: HashMap<StackKey, u64> = HashMap::new();
+    for group in groups {
+        *counts
+            .entry(StackKey {
+                kind: group.kind.clone(),
+                stack: group.stack.clone(),
+            })
+            .or_default() += group.count;
+    }
+    let mut counts: Vec<(StackKey, u64)> = counts.into_iter().collect();
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    counts.truncate(MAX_TRACKED_STACKS);
+
+    let newly_flagged = HANDLE_TRACKER.upsert(pid, HandleHistory::default, |history| {
+        history
+            .stacks
+            .retain(|key, _| counts.iter().any(|(reported, _)| reported == key));
+        for (key, count) in counts {
+            history
+                .stacks
+                .entry(key)
+                .or_insert_with(|| StackHistory::new(window))
+                .record(CountPoint { timestamp, count }, window);
+        }
+
+        let suspects = history.suspects(timestamp, window);
+        let mut newly_flagged = Vec::new();
+        for (key, stack_history) in history.stacks.iter_mut() {
+            let suspect = suspects
+                .iter()
+                .take(TOP_SUSPECTS)
+                .find(|suspect| suspect.kind == key.kind && suspect.stack == key.stack);
+            match suspect {
+                Some(suspect) if !stack_history.flagged => {
+                    stack_history.flagged = true;
+                    newly_flagged.push(suspect.clone());
+                }
+                Some(_) => {}
+                None => stack_history.flagged = false,
+            }
+        }
+        newly_flagged
+    });
+
+    for suspect in newly_flagged {
+        log_print!(
+            "⚠️ 进程 {} 疑似 {} 泄漏，数量 {} -> {}: {}",
+            pid,
+            suspect.kind,
+            suspect.first_count,
+            suspect.count,
+            suspect.stack
+        );
+        publish(StoreEvent::LeakSuspected {
+            process_id: pid,
+            kind: suspect.kind,
+            stack: suspect.stack,
+            count: suspect.count,
+            growth: suspect.growth,
+            time: now_secs(),
+        });
+    }
+}
+
+/// 进程的 top 疑似泄漏创建栈
+pub fn top_suspects(pid: u32, now: u64, window: u64) -> Vec<LeakSuspect> {
+    HANDLE_TRACKER
+        .read(pid, |history| {
+            let mut suspects = history.suspects(now, window);
+            suspects.truncate(TOP_SUSPECTS);
+            suspects
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: u64 = 600_000;
+
+    fn group(kind: &str, stack: &str, count: u64) -> HandleGroup {
+        HandleGroup {
+            kind: kind.to_string(),
+            stack: stack.to_string(),
+            count,
+        }
+    }
+
+    #[test]
+    fn history_covers_window_for_fast_reporters() {
+        let mut history = StackHistory::new(WINDOW);
+        // 每 100ms 上报一次，持续两个窗口
+        for index in 0..12_000u64 {
+            history.record(
+                CountPoint {
+                    timestamp: index * 100,
+                    count: index,
+                },
+                WINDOW,
+            );
+        }
+        let first = history.points.iter().next().unwrap();
+        let last = history.points.latest().unwrap();
+        assert!(last.timestamp - first.timestamp >= WINDOW - MIN_POINT_INTERVAL_MS);
+        // 合并到上一条记录时保留最新的数量
+        assert_eq!(last.count, 11_999);
+    }
+
+    #[test]
+    fn history_drops_points_outside_window() {
+        let mut history = StackHistory::new(WINDOW);
+        for timestamp in [0, 60_000, WINDOW + 120_000] {
+            history.record(
+                CountPoint {
+                    timestamp,
+                    count: 1,
+                },
+                WINDOW,
+            );
+        }
+        let timestamps: Vec<u64> = history.points.iter().map(|point| point.timestamp).collect();
+        assert_eq!(timestamps, [WINDOW + 120_000]);
+    }
+
+    #[test]
+    fn steadily_growing_stack_is_suspected() {
+        let pid = 4_101_901;
+        for index in 0..10u64 {
+            record_handles(
+                pid,
+                index * 10_000,
+                &[
+                    group("timer", "at leak (a.js:1)", 10 + index * 5),
+                    group("timer", "at stable (b.js:2)", 10),
+                ],
+                WINDOW,
+            );
+        }
+        let suspects = top_suspects(pid, 90_000, WINDOW);
+        assert_eq!(suspects.len(), 1);
+        assert_eq!(suspects[0].stack, "at leak (a.js:1)");
+        assert_eq!((suspects[0].first_count, suspects[0].count), (10, 55));
+        assert_eq!(suspects[0].growth_per_minute, 30.0);
+
+        // 数量回落后不再视为泄漏
+        record_handles(
+            pid,
+            100_000,
+            &[group("timer", "at leak (a.js:1)", 1)],
+            WINDOW,
+        );
+        assert!(top_suspects(pid, 100_000, WINDOW).is_empty());
+        HANDLE_TRACKER.remove(pid);
+    }
+
+    #[test]
+    fn too_few_samples_are_not_suspected() {
+        let pid = 4_101_902;
+        for index in 0..(MIN_SAMPLES as u64 - 1) {
+            record_handles(
+                pid,
+                index * 10_000,
+                &[group("request", "at f", index + 1)],
+                WINDOW,
+            );
+        }
+        assert!(top_suspects(pid, 40_000, WINDOW).is_empty());
+        HANDLE_TRACKER.remove(pid);
+    }
+}
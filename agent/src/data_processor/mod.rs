@@ -1,2 +1,14 @@
+pub mod anomaly;
+pub mod budget;
+pub mod events;
+pub mod histograms;
+pub mod leaks;
+pub mod persist;
+pub mod query;
+pub mod reaper;
+pub mod rules;
+pub mod sampler;
+pub mod segments;
+pub mod sharded;
 pub mod store;
 pub mod subscribe;
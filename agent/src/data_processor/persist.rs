@@ -0,0 +1,221 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{task, time::interval};
+
+use crate::{
+    data_processor::store::{ProcessStatus, ProcessStore, PROCESS_MAP_STORE},
+    error_print,
+    helper::{
+        error::{AppError, AppResult},
+        metrics::{ProcessMetrics, METRICS_STORE},
+        time::now_secs,
+    },
+    log_print,
+};
+
+const SNAPSHOT_FILE_NAME: &str = "store.json";
+// 快照格式变化时递增，加载时忽略不兼容的版本
+const SNAPSHOT_VERSION: u32 = 2;
+
+/// 落盘到 agent_dir 的 store 快照
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoreSnapshot {
+    pub version: u32,
+    // timestamp second
+    pub saved_time: u64,
+    pub processes: Vec<ProcessStore>,
+    // 各进程最近的指标序列
+    #[serde(default)]
+    pub metrics: HashMap<u32, ProcessMetrics>,
+}
+
+fn snapshot_path(agent_dir: &str) -> PathBuf {
+    Path::new(agent_dir).join(SNAPSHOT_FILE_NAME)
+}
+
+/// 将当前 store 写入 agent_dir，先写临时文件再重命名，避免写到一半时留下损坏的快照
+pub fn save_snapshot(agent_dir: &str) -> AppResult<()> {
+    fs::create_dir_all(agent_dir)?;
+    let snapshot = StoreSnapshot {
+        version: SNAPSHOT_VERSION,
+        saved_time: now_secs(),
+        processes: PROCESS_MAP_STORE.list(),
+        metrics: METRICS_STORE.snapshot(),
+    };
+
+    let path = snapshot_path(agent_dir);
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, serde_json::to_vec(&snapshot)?)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+/// 从 agent_dir 加载快照并恢复到 store，返回恢复的进程数量
+pub fn load_snapshot(agent_dir: &str) -> AppResult<usize> {
+    let path = snapshot_path(agent_dir);
+    if !path.exists() {
+        return Ok(0);
+    }
+
+    let snapshot: StoreSnapshot = serde_json::from_slice(&fs::read(&path)?)?;
+    if snapshot.version != SNAPSHOT_VERSION {
+        return Err(AppError::DataProcessing(format!(
+            "快照版本 {} 与当前版本 {} 不兼容",
+            snapshot.version, SNAPSHOT_VERSION
+        )));
+    }
+
+    let now = now_secs();
+    let count = snapshot.processes.len();
+    for mut process in snapshot.processes {
+        // agent 重启期间收不到心跳，以加载时间作为最近心跳，避免恢复后立即被清理
+        if process.status != ProcessStatus::Exited {
+            process.latest_heartbeat_time = process.latest_heartbeat_time.max(now);
+        }
+        let pid = process.process_id;
+        PROCESS_MAP_STORE.set(&pid, process);
+    }
+    // 只恢复仍在 store 中的进程的指标
+    for (pid, metrics) in snapshot.metrics {
+        if PROCESS_MAP_STORE.contains(&pid) {
+            METRICS_STORE.restore(pid, metrics);
+        }
+    }
+    Ok(count)
+}
+
+/// 在后台定时保存快照
+pub fn start_persist(agent_dir: String, interval_secs: u64) {
+    task::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(interval_secs));
+        // 第一次 tick 会立即触发，跳过以免覆盖刚加载的快照
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if let Err(e) = save_snapshot(&agent_dir) {
+                error_print!("保存 store 快照失败: {}", e);
+            }
+        }
+    });
+}
+
+/// 启动时恢复快照，失败时只记录日志不影响启动
+pub fn restore_on_startup(agent_dir: &str) {
+    match load_snapshot(agent_dir) {
+        Ok(0) => {}
+        Ok(count) => log_print!("♻️ 已从 {} 恢复 {} 个进程", agent_dir, count),
+        Err(e) => error_print!("加载 store 快照失败: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::metrics::SeriesKey;
+
+    fn test_dir(name: &str) -> String {
+        let dir =
+            std::env::temp_dir().join(format!("mito-persist-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.to_string_lossy().to_string()
+    }
+
+    fn write_snapshot(agent_dir: &str, snapshot: &StoreSnapshot) {
+        fs::create_dir_all(agent_dir).unwrap();
+        fs::write(
+            snapshot_path(agent_dir),
+            serde_json::to_vec(snapshot).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn save_snapshot_writes_store_atomically() {
+        let agent_dir = test_dir("save");
+        let pid = 4_100_501;
+        PROCESS_MAP_STORE.register(ProcessStore::for_test(pid, "demo"));
+
+        save_snapshot(&agent_dir).unwrap();
+        let snapshot: StoreSnapshot =
+            serde_json::from_slice(&fs::read(snapshot_path(&agent_dir)).unwrap()).unwrap();
+        assert_eq!(snapshot.version, SNAPSHOT_VERSION);
+        assert!(snapshot.processes.iter().any(|p| p.process_id == pid));
+        assert!(!snapshot_path(&agent_dir)
+            .with_extension("json.tmp")
+            .exists());
+        fs::remove_dir_all(&agent_dir).unwrap();
+    }
+
+    #[test]
+    fn load_snapshot_restores_processes_and_their_metrics() {
+        let agent_dir = test_dir("load");
+        let (pid, exited_pid, orphan_pid) = (4_100_511, 4_100_512, 4_100_513);
+        let mut process = ProcessStore::for_test(pid, "demo");
+        process.latest_heartbeat_time = 1;
+        let mut exited = ProcessStore::for_test(exited_pid, "demo");
+        exited.latest_heartbeat_time = 1;
+        exited.status = ProcessStatus::Exited;
+
+        let mut metrics = HashMap::new();
+        for id in [pid, orphan_pid] {
+            METRICS_STORE.record(id, None, 1_000, &[("cpu.load", 0.5)]);
+            metrics.insert(id, METRICS_STORE.remove(id).unwrap());
+        }
+        write_snapshot(
+            &agent_dir,
+            &StoreSnapshot {
+                version: SNAPSHOT_VERSION,
+                saved_time: 1,
+                processes: vec![process, exited],
+                metrics,
+            },
+        );
+
+        assert_eq!(load_snapshot(&agent_dir).unwrap(), 2);
+        // 重启期间收不到心跳，未退出的进程以加载时间作为最近心跳
+        assert!(PROCESS_MAP_STORE.get(&pid).unwrap().latest_heartbeat_time > 1);
+        assert_eq!(
+            PROCESS_MAP_STORE
+                .get(&exited_pid)
+                .unwrap()
+                .latest_heartbeat_time,
+            1
+        );
+        let key = SeriesKey {
+            name: "cpu.load".to_string(),
+            thread_id: None,
+        };
+        assert!(METRICS_STORE.latest_sample(pid, &key).is_some());
+        // 不在快照进程列表中的指标不会恢复
+        assert!(METRICS_STORE.latest_sample(orphan_pid, &key).is_none());
+        fs::remove_dir_all(&agent_dir).unwrap();
+    }
+
+    #[test]
+    fn load_snapshot_rejects_other_versions() {
+        let agent_dir = test_dir("version");
+        write_snapshot(
+            &agent_dir,
+            &StoreSnapshot {
+                version: SNAPSHOT_VERSION - 1,
+                saved_time: 1,
+                processes: vec![ProcessStore::for_test(4_100_521, "demo")],
+                metrics: HashMap::new(),
+            },
+        );
+        assert!(load_snapshot(&agent_dir).is_err());
+        assert!(!PROCESS_MAP_STORE.contains(&4_100_521));
+        fs::remove_dir_all(&agent_dir).unwrap();
+    }
+
+    #[test]
+    fn missing_snapshot_loads_nothing() {
+        assert_eq!(load_snapshot(&test_dir("missing")).unwrap(), 0);
+    }
+}
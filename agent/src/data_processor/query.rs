@@ -0,0 +1,613 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+
+use crate::{
+    data_processor::{
+        segments,
+        store::{ProcessStore, PROCESS_MAP_STORE},
+    },
+    helper::{
+        metrics::{Bucket, Resolution, SeriesKey, METRICS_STORE},
+        time::{now_millis, parse_duration_ms},
+    },
+};
+
+// 未指定 from 时默认查询最近 5 分钟
+const DEFAULT_RANGE: u64 = 300_000;
+// 未指定 step 时的目标点数
+const DEFAULT_POINTS: u64 = 300;
+// 原始样本的最小对齐间隔
+const MIN_STEP: u64 = 1_000;
+// 单条序列最多返回的点数
+const MAX_POINTS: u64 = 10_000;
+
+/// 每个 step 内的聚合方式
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Display, EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Aggregation {
+    #[default]
+    Avg,
+    Max,
+    Min,
+    // 聚合层只保留 min/avg/max，使用聚合层时 last 取桶的平均值
+    Last,
+    // 相邻两个有数据的 step 最后一个值的每秒变化量，用于累计值，第一个 step 没有值
+    // 按两个值的实际时间间隔计算，中间有空的 step 时不会放大；计数器归零时按归零后的值计算
+    Rate,
+}
+
+/// 跨进程查询时的分组方式
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Display, EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum GroupBy {
+    // 每个进程单独一条序列
+    #[default]
+    Pid,
+    // 同一 app_name 的进程合并
+    App,
+    // 所有进程合并
+    All,
+}
+
+/// 数据来源
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Display, EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Source {
+    // 内存中的分层数据，最多覆盖 1 天
+    #[default]
+    Memory,
+    // 落盘的 segment，覆盖范围取决于保留时间
+    Disk,
+}
+
+/// 查询参数，时间均为 timestamp millisecond
+#[derive(Debug, Default, Deserialize)]
+pub struct MetricsQuery {
+    // 序列名前缀，例如 cpu、memory、event_loop、os
+    #[serde(rename = "type")]
+    pub metric_type: Option<String>,
+    // 完整的序列名，例如 cpu.load
+    pub name: Option<String>,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    // 对齐间隔，例如 10s、1m，不带单位时为毫秒
+    pub step: Option<String>,
+    #[serde(default)]
+    pub agg: Aggregation,
+    // 不指定时根据 from 自动选择
+    pub resolution: Option<Resolution>,
+    #[serde(default)]
+    pub group_by: GroupBy,
+    // 不指定时 from 超出内存覆盖范围且启用了落盘则查询落盘数据
+    pub source: Option<Source>,
+}
+
+/// 对齐后的一条序列，points 为 [timestamp, value]，没有数据的 step 为 null
+#[derive(Debug, Serialize)]
+pub struct SeriesResult {
+    pub name: String,
+    pub thread_id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process_id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_name: Option<String>,
+    pub points: Vec<(u64, Option<f64>)>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetricsQueryResult {
+    pub from: u64,
+    pub to: u64,
+    pub step: u64,
+    pub agg: Aggregation,
+    pub resolution: Resolution,
+    pub source: Source,
+    pub series: Vec<SeriesResult>,
+}
+
+/// 校验后的查询范围
+struct QueryRange {
+    // 第一个 step 的起点，按 step 对齐
+    start: u64,
+    to: u64,
+    step: u64,
+    resolution: Resolution,
+    source: Source,
+}
+
+impl QueryRange {
+    fn steps(&self) -> usize {
+        ((self.to - self.start) / self.step + 1) as usize
+    }
+
+    fn index(&self, timestamp: u64) -> usize {
+        ((timestamp.max(self.start) - self.start) / self.step) as usize
+    }
+
+    fn timestamps(&self) -> impl Iterator<Item = u64> + '_ {
+        (0..self.steps() as u64).map(|index| self.start + index * self.step)
+    }
+}
+
+fn resolve_range(query: &MetricsQuery) -> Result<QueryRange, String> {
+    let now = now_millis();
+    let to = query.to.unwrap_or(now);
+    let from = query.from.unwrap_or(to.saturating_sub(DEFAULT_RANGE));
+    if from >= to {
+        return Err(format!("from {} 必须小于 to {}", from, to));
+    }
+    let resolution = query
+        .resolution
+        .unwrap_or_else(|| Resolution::for_range(from, now));
+    let source = match query.source {
+        Some(Source::Disk) if !segments::enabled() => {
+            return Err("未启用指标落盘，无法查询 source=disk".to_string());
+        }
+        Some(source) => source,
+        None if segments::enabled()
+            && from < now.saturating_sub(Resolution::OneMinute.retention()) =>
+        {
+            Source::Disk
+        }
+        None => Source::Memory,
+    };
+    let step = match &query.step {
+        Some(step) => parse_duration_ms(step)
+            .filter(|step| *step > 0)
+            .ok_or_else(|| format!("step {} 无效，示例: 10s、1m、500", step))?,
+        None => ((to - from) / DEFAULT_POINTS).max(MIN_STEP),
+    };
+    // step 小于聚合桶宽度时没有意义
+    let step = step.max(resolution.width());
+    let start = from - from % step;
+    if (to - start) / step >= MAX_POINTS {
+        return Err(format!("查询范围内的点数超过 {}，请增大 step", MAX_POINTS));
+    }
+    Ok(QueryRange {
+        start,
+        to,
+        step,
+        resolution,
+        source,
+    })
+}
+
+/// 单个 step 内的数据汇总
+#[derive(Debug, Clone, Copy)]
+struct StepStats {
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+    // (timestamp, value)
+    last: (u64, f64),
+}
+
+impl StepStats {
+    fn from_bucket(bucket: &Bucket) -> Self {
+        Self {
+            count: bucket.count as u64,
+            sum: bucket.sum,
+            min: bucket.min,
+            max: bucket.max,
+            last: (bucket.timestamp, bucket.avg()),
+        }
+    }
+
+    fn merge(&mut self, other: &StepStats) {
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        if other.last.0 >= self.last.0 {
+            self.last = other.last;
+        }
+    }
+}
+
+type Steps = Vec<Option<StepStats>>;
+
+fn align(range: &QueryRange, buckets: &[Bucket]) -> Steps {
+    let mut steps: Steps = vec![None; range.steps()];
+    for bucket in buckets {
+        let stats = StepStats::from_bucket(bucket);
+        let Some(slot) = steps.get_mut(range.index(bucket.timestamp)) else {
+            continue;
+        };
+        match slot {
+            Some(existing) => existing.merge(&stats),
+            None => *slot = Some(stats),
+        }
+    }
+    steps
+}
+
+fn merge_steps(target: &mut Steps, other: &Steps) {
+    for (slot, other) in target.iter_mut().zip(other) {
+        match (slot.as_mut(), other) {
+            (Some(existing), Some(other)) => existing.merge(other),
+            (None, Some(other)) => *slot = Some(*other),
+            _ => {}
+        }
+    }
+}
+
+fn evaluate(steps: &Steps, agg: Aggregation) -> Vec<Option<f64>> {
+    // (timestamp, value)
+    let mut previous_last: Option<(u64, f64)> = None;
+    steps
+        .iter()
+        .map(|stats| {
+            let stats = stats.as_ref()?;
+            Some(match agg {
+                Aggregation::Avg => stats.sum / stats.count as f64,
+                Aggregation::Max => stats.max,
+                Aggregation::Min => stats.min,
+                Aggregation::Last => stats.last.1,
+                Aggregation::Rate => {
+                    let (timestamp, current) = stats.last;
+                    let (previous_timestamp, previous) = previous_last.replace(stats.last)?;
+                    let elapsed = timestamp.checked_sub(previous_timestamp)?;
+                    if elapsed == 0 {
+                        return None;
+                    }
+                    let delta = if current >= previous {
+                        current - previous
+                    } else {
+                        current
+                    };
+                    delta / (elapsed as f64 / 1000.0)
+                }
+            })
+        })
+        .collect()
+}
+
+/// 序列名过滤条件，type 按前缀匹配，name 按全名匹配
+fn series_filter(query: &MetricsQuery) -> impl Fn(&SeriesKey) -> bool + '_ {
+    move |key: &SeriesKey| {
+        let type_matches = query.metric_type.as_ref().is_none_or(|metric_type| {
+            key.name
+                .strip_prefix(metric_type.as_str())
+                .is_some_and(|rest| rest.starts_with('.'))
+        });
+        let name_matches = query.name.as_ref().is_none_or(|name| key.name == *name);
+        type_matches && name_matches
+    }
+}
+
+fn query_process(
+    process_id: u32,
+    query: &MetricsQuery,
+    range: &QueryRange,
+) -> Result<Vec<(SeriesKey, Steps)>, String> {
+    let series = match range.source {
+        Source::Memory => METRICS_STORE.query_matching(
+            process_id,
+            series_filter(query),
+            range.resolution,
+            range.start,
+            range.to,
+        ),
+        // 落盘数据直接按 step 合并
+        Source::Disk => segments::query_matching(
+            process_id,
+            series_filter(query),
+            range.start,
+            range.to,
+            range.step,
+        )
+        .map_err(|e| format!("读取落盘指标失败: {}", e))?,
+    };
+    Ok(series
+        .into_iter()
+        .map(|(key, buckets)| (key, align(range, &buckets)))
+        .collect())
+}
+
+fn to_points(range: &QueryRange, values: Vec<Option<f64>>) -> Vec<(u64, Option<f64>)> {
+    range.timestamps().zip(values).collect()
+}
+
+fn result(
+    query: &MetricsQuery,
+    range: &QueryRange,
+    series: Vec<SeriesResult>,
+) -> MetricsQueryResult {
+    MetricsQueryResult {
+        from: range.start,
+        to: range.to,
+        step: range.step,
+        agg: query.agg,
+        resolution: range.resolution,
+        source: range.source,
+        series,
+    }
+}
+
+/// 查询单个进程的对齐序列
+pub fn query_process_metrics(
+    process: &ProcessStore,
+    query: &MetricsQuery,
+) -> Result<MetricsQueryResult, String> {
+    let range = resolve_range(query)?;
+    let series = query_process(process.process_id, query, &range)?
+        .into_iter()
+        .map(|(key, steps)| SeriesResult {
+            name: key.name,
+            thread_id: key.thread_id,
+            process_id: Some(process.process_id),
+            app_name: Some(process.app_name.clone()),
+            points: to_points(&range, evaluate(&steps, query.agg)),
+        })
+        .collect();
+    Ok(result(query, &range, series))
+}
+
+/// 分组内 avg/max/min 合并所有样本后计算，last 与 rate 对每个进程计算后求和
+#[derive(Default)]
+struct GroupAccumulator {
+    process_id: Option<u32>,
+    app_name: Option<String>,
+    merged: Option<Steps>,
+    summed: Option<Vec<Option<f64>>>,
+}
+
+impl GroupAccumulator {
+    fn add(&mut self, steps: Steps, agg: Aggregation) {
+        match agg {
+            Aggregation::Last | Aggregation::Rate => {
+                let values = evaluate(&steps, agg);
+                match &mut self.summed {
+                    Some(summed) => {
+                        for (total, value) in summed.iter_mut().zip(values) {
+                            if let Some(value) = value {
+                                *total = Some(total.unwrap_or(0.0) + value);
+                            }
+                        }
+                    }
+                    None => self.summed = Some(values),
+                }
+            }
+            _ => match &mut self.merged {
+                Some(merged) => merge_steps(merged, &steps),
+                None => self.merged = Some(steps),
+            },
+        }
+    }
+
+    fn values(self, agg: Aggregation) -> Vec<Option<f64>> {
+        match (self.summed, self.merged) {
+            (Some(summed), _) => summed,
+            (None, Some(merged)) => evaluate(&merged, agg),
+            (None, None) => Vec::new(),
+        }
+    }
+}
+
+/// 跨进程查询，按 group_by 合并序列
+pub fn query_all_metrics(query: &MetricsQuery) -> Result<MetricsQueryResult, String> {
+    let range = resolve_range(query)?;
+    // pid -> app_name，查询落盘数据时包含已经不在内存中的进程
+    let mut processes: BTreeMap<u32, String> = PROCESS_MAP_STORE
+        .list()
+        .into_iter()
+        .map(|process| (process.process_id, process.app_name))
+        .collect();
+    if range.source == Source::Disk {
+        for (process_id, app_name) in segments::processes(range.start, range.to) {
+            processes.entry(process_id).or_insert(app_name);
+        }
+    }
+
+    // (分组标识, 序列) -> 累加器
+    let mut groups: BTreeMap<(String, SeriesKey), GroupAccumulator> = BTreeMap::new();
+    for (process_id, app_name) in processes {
+        let (group, group_process_id, group_app_name) = match query.group_by {
+            GroupBy::Pid => (
+                format!("{:010}", process_id),
+                Some(process_id),
+                Some(app_name.clone()),
+            ),
+            GroupBy::App => (app_name.clone(), None, Some(app_name.clone())),
+            GroupBy::All => (String::new(), None, None),
+        };
+        for (key, steps) in query_process(process_id, query, &range)? {
+            let accumulator =
+                groups
+                    .entry((group.clone(), key))
+                    .or_insert_with(|| GroupAccumulator {
+                        process_id: group_process_id,
+                        app_name: group_app_name.clone(),
+                        ..Default::default()
+                    });
+            accumulator.add(steps, query.agg);
+        }
+    }
+
+    let series = groups
+        .into_iter()
+        .map(|((_, key), accumulator)| SeriesResult {
+            name: key.name,
+            thread_id: key.thread_id,
+            process_id: accumulator.process_id,
+            app_name: accumulator.app_name.clone(),
+            points: to_points(&range, accumulator.values(query.agg)),
+        })
+        .collect();
+    Ok(result(query, &range, series))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: u64, to: u64, step: u64) -> QueryRange {
+        QueryRange {
+            start,
+            to,
+            step,
+            resolution: Resolution::Raw,
+            source: Source::Memory,
+        }
+    }
+
+    fn bucket(timestamp: u64, value: f64) -> Bucket {
+        Bucket {
+            timestamp,
+            count: 1,
+            sum: value,
+            min: value,
+            max: value,
+        }
+    }
+
+    #[test]
+    fn align_merges_buckets_into_steps() {
+        let range = range(0, 29_999, 10_000);
+        let steps = align(
+            &range,
+            &[
+                bucket(1_000, 1.0),
+                bucket(9_000, 3.0),
+                bucket(25_000, 5.0),
+                // 超出范围的桶被忽略
+                bucket(60_000, 9.0),
+            ],
+        );
+        assert_eq!(steps.len(), 3);
+        let first = steps[0].unwrap();
+        assert_eq!(
+            (first.count, first.sum, first.min, first.max),
+            (2, 4.0, 1.0, 3.0)
+        );
+        assert_eq!(first.last, (9_000, 3.0));
+        assert!(steps[1].is_none());
+
+        assert_eq!(
+            evaluate(&steps, Aggregation::Avg),
+            [Some(2.0), None, Some(5.0)]
+        );
+        assert_eq!(
+            evaluate(&steps, Aggregation::Max),
+            [Some(3.0), None, Some(5.0)]
+        );
+        assert_eq!(
+            evaluate(&steps, Aggregation::Last),
+            [Some(3.0), None, Some(5.0)]
+        );
+    }
+
+    #[test]
+    fn rate_divides_by_elapsed_time_across_gaps() {
+        let range = range(0, 49_999, 10_000);
+        let steps = align(
+            &range,
+            &[
+                bucket(0, 100.0),
+                bucket(10_000, 200.0),
+                // 20s、30s 两个 step 没有数据
+                bucket(40_000, 500.0),
+            ],
+        );
+        assert_eq!(
+            evaluate(&steps, Aggregation::Rate),
+            [None, Some(10.0), None, None, Some(10.0)]
+        );
+    }
+
+    #[test]
+    fn rate_uses_value_after_counter_reset() {
+        let range = range(0, 19_999, 10_000);
+        let steps = align(&range, &[bucket(0, 100.0), bucket(10_000, 30.0)]);
+        assert_eq!(evaluate(&steps, Aggregation::Rate), [None, Some(3.0)]);
+    }
+
+    #[test]
+    fn resolve_range_aligns_start_and_limits_points() {
+        let query = MetricsQuery {
+            from: Some(12_345),
+            to: Some(60_000),
+            step: Some("10s".to_string()),
+            resolution: Some(Resolution::Raw),
+            source: Some(Source::Memory),
+            ..Default::default()
+        };
+        let range = resolve_range(&query).unwrap();
+        assert_eq!(
+            (range.start, range.step, range.steps()),
+            (10_000, 10_000, 6)
+        );
+
+        // step 不能小于聚合桶宽度
+        let query = MetricsQuery {
+            resolution: Some(Resolution::OneMinute),
+            ..query
+        };
+        assert_eq!(resolve_range(&query).unwrap().step, 60_000);
+
+        let too_many = MetricsQuery {
+            from: Some(0),
+            to: Some(MAX_POINTS * 1_000),
+            step: Some("1s".to_string()),
+            resolution: Some(Resolution::Raw),
+            source: Some(Source::Memory),
+            ..Default::default()
+        };
+        assert!(resolve_range(&too_many).is_err());
+        let reversed = MetricsQuery {
+            from: Some(2),
+            to: Some(1),
+            ..Default::default()
+        };
+        assert!(resolve_range(&reversed).is_err());
+    }
+
+    #[test]
+    fn series_filter_matches_type_prefix_and_name() {
+        let key = |name: &str| SeriesKey {
+            name: name.to_string(),
+            thread_id: None,
+        };
+        let by_type = MetricsQuery {
+            metric_type: Some("cpu".to_string()),
+            ..Default::default()
+        };
+        let filter = series_filter(&by_type);
+        assert!(filter(&key("cpu.load")));
+        assert!(!filter(&key("cpuset.load")));
+        let by_name = MetricsQuery {
+            name: Some("cpu.load".to_string()),
+            ..Default::default()
+        };
+        let filter = series_filter(&by_name);
+        assert!(filter(&key("cpu.load")));
+        assert!(!filter(&key("cpu.user_load")));
+    }
+
+    #[test]
+    fn group_rate_sums_per_process_rates() {
+        let range = range(0, 19_999, 10_000);
+        let mut group = GroupAccumulator::default();
+        group.add(
+            align(&range, &[bucket(0, 0.0), bucket(10_000, 100.0)]),
+            Aggregation::Rate,
+        );
+        group.add(
+            align(&range, &[bucket(0, 0.0), bucket(10_000, 50.0)]),
+            Aggregation::Rate,
+        );
+        assert_eq!(group.values(Aggregation::Rate), [None, Some(15.0)]);
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use serde::Serialize;
+use tokio::{task, time::interval};
+
+use crate::{
+    data_processor::{
+        events::{publish, RemoveReason, StoreEvent},
+        store::{ProcessStatus, PROCESS_MAP_STORE},
+    },
+    helper::{
+        config::ReaperConfig,
+        metrics::remove_process_data,
+        procfs::{check_liveness, Liveness},
+        time::now_secs,
+    },
+    log_print,
+};
+
+// 最多保留最近被清理的进程数量
+const MAX_EVICTED_HISTORY: usize = 100;
+
+/// 因心跳超时被移除的进程记录
+#[derive(Debug, Clone, Serialize)]
+pub struct EvictedProcess {
+    pub process_id: u32,
+    pub session_id: String,
+    pub app_name: String,
+    // timestamp second
+    pub latest_heartbeat_time: u64,
+    // timestamp second
+    pub evicted_time: u64,
+}
+
+pub static EVICTED_PROCESSES: LazyLock<Mutex<VecDeque<EvictedProcess>>> =
+    LazyLock::new(|| Mutex::new(VecDeque::with_capacity(MAX_EVICTED_HISTORY)));
+
+/// 返回最近被清理的进程，按清理时间从新到旧排列
+pub fn recent_evictions() -> Vec<EvictedProcess> {
+    EVICTED_PROCESSES
+        .lock()
+        .unwrap()
+        .iter()
+        .rev()
+        .cloned()
+        .collect()
+}
+
+fn record_eviction(evicted: EvictedProcess) {
+    let mut history = EVICTED_PROCESSES.lock().unwrap();
+    if history.len() >= MAX_EVICTED_HISTORY {
+        history.pop_front();
+    }
+    history.push_back(evicted);
+}
+
+/// 通过 /proc 检测尚未退出的进程，返回已退出或 pid 被复用的进程及其会话 id
+///
+/// 在加锁前完成文件读取，避免 IO 阻塞其他请求
+fn detect_exited() -> HashMap<u32, (String, Liveness)> {
+    PROCESS_MAP_STORE
+        .list()
+        .into_iter()
+        .filter(|process| process.status != ProcessStatus::Exited)
+        .filter_map(
+            |process| match check_liveness(process.process_id, process.proc_start_time) {
+                liveness @ (Liveness::Exited | Liveness::PidReused) => {
+                    Some((process.process_id, (process.session_id, liveness)))
+                }
+                Liveness::Alive | Liveness::Unknown => None,
+            },
+        )
+        .collect()
+}
+
+/// 扫描一次 store：
+/// - 通过 /proc 检测到退出的进程标记为 exited（区分 "挂起" 与 "已退出"）
+/// - 心跳超时的进程标记为 stale
+/// - 超过清理时间的直接移除
+pub fn reap_once(config: &ReaperConfig, now: u64) {
+    apply_reap(config, now, &detect_exited());
+}
+
+fn apply_reap(config: &ReaperConfig, now: u64, exited: &HashMap<u32, (String, Liveness)>) {
+    let mut evicted = Vec::new();
+    // 持有分片锁期间只收集事件，遍历结束后再统一发布
+    let mut events = Vec::new();
+    PROCESS_MAP_STORE.retain(|pid, process| {
+        if let Some((session_id, liveness)) = exited.get(pid) {
+            // 检测期间进程可能已重新注册，只处理同一会话的记录
+            if *session_id == process.session_id {
+                let reason = match liveness {
+                    Liveness::PidReused => "pid 已被复用",
+                    _ => "进程已退出",
+                };
+                log_print!("💀 进程 {} {}: {} -> exited", pid, reason, process.status);
+                process.status = ProcessStatus::Exited;
+                process.status_update_time = now;
+                process.exited_time = Some(now);
+                events.push(StoreEvent::ProcessExited {
+                    process_id: *pid,
+                    time: now,
+                });
+            }
+        }
+
+        let elapsed = now.saturating_sub(process.latest_heartbeat_time);
+        if elapsed >= config.evict_timeout {
+            log_print!(
+                "🗑️ 进程 {} 超过 {}s 无心跳: {} -> evicted",
+                pid,
+                elapsed,
+                process.status
+            );
+            evicted.push(EvictedProcess {
+                process_id: *pid,
+                session_id: process.session_id.clone(),
+                app_name: process.app_name.clone(),
+                latest_heartbeat_time: process.latest_heartbeat_time,
+                evicted_time: now,
+            });
+            events.push(StoreEvent::ProcessRemoved {
+                process_id: *pid,
+                reason: RemoveReason::Evicted,
+                time: now,
+            });
+            return false;
+        }
+
+        if elapsed >= config.stale_timeout && process.status == ProcessStatus::Active {
+            log_print!("⚠️ 进程 {} 超过 {}s 无心跳: active -> stale", pid, elapsed);
+            process.status = ProcessStatus::Stale;
+            process.status_update_time = now;
+            events.push(StoreEvent::ProcessStale {
+                process_id: *pid,
+                latest_heartbeat_time: process.latest_heartbeat_time,
+                time: now,
+            });
+        }
+        true
+    });
+
+    for item in evicted {
+        remove_process_data(item.process_id);
+        record_eviction(item);
+    }
+    for event in events {
+        publish(event);
+    }
+}
+
+/// 在后台定时扫描进程心跳
+pub fn start_reaper(config: ReaperConfig) {
+    task::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(config.interval));
+        loop {
+            ticker.tick().await;
+            reap_once(&config, now_secs());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data_processor::store::ProcessStore, helper::metrics::METRICS_STORE};
+
+    fn config() -> ReaperConfig {
+        ReaperConfig {
+            interval: 5,
+            stale_timeout: 30,
+            evict_timeout: 300,
+        }
+    }
+
+    fn register(pid: u32, heartbeat_age: u64, now: u64) {
+        let mut process = ProcessStore::for_test(pid, "demo");
+        process.latest_heartbeat_time = now - heartbeat_age;
+        PROCESS_MAP_STORE.register(process);
+    }
+
+    #[test]
+    fn silent_process_becomes_stale_then_evicted() {
+        let now = now_secs();
+        let (fresh, stale, evicted) = (4_100_301, 4_100_302, 4_100_303);
+        register(fresh, 0, now);
+        register(stale, 60, now);
+        register(evicted, 600, now);
+        METRICS_STORE.record(evicted, None, now * 1000, &[("cpu.load", 1.0)]);
+
+        apply_reap(&config(), now, &HashMap::new());
+        assert_eq!(
+            PROCESS_MAP_STORE.get(&fresh).unwrap().status,
+            ProcessStatus::Active
+        );
+        assert_eq!(
+            PROCESS_MAP_STORE.get(&stale).unwrap().status,
+            ProcessStatus::Stale
+        );
+        assert!(!PROCESS_MAP_STORE.contains(&evicted));
+        // 移除进程时同步清理其指标
+        assert!(METRICS_STORE.series_keys(evicted).is_empty());
+        assert!(recent_evictions()
+            .iter()
+            .any(|process| process.process_id == evicted));
+    }
+
+    #[test]
+    fn exited_process_is_marked_only_for_same_session() {
+        let now = now_secs();
+        let (exited, reregistered) = (4_100_311, 4_100_312);
+        register(exited, 0, now);
+        register(reregistered, 0, now);
+        let session_id = PROCESS_MAP_STORE.get(&exited).unwrap().session_id;
+
+        let detected = HashMap::from([
+            (exited, (session_id, Liveness::Exited)),
+            // 检测期间重新注册，会话 id 已经变化
+            (reregistered, ("old-session".to_string(), Liveness::Exited)),
+        ]);
+        apply_reap(&config(), now, &detected);
+
+        let process = PROCESS_MAP_STORE.get(&exited).unwrap();
+        assert_eq!(process.status, ProcessStatus::Exited);
+        assert_eq!(process.exited_time, Some(now));
+        assert_eq!(
+            PROCESS_MAP_STORE.get(&reregistered).unwrap().status,
+            ProcessStatus::Active
+        );
+    }
+}
@@ -0,0 +1,746 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{sync::broadcast::error::RecvError, task, time::interval};
+
+use crate::{
+    data_processor::{
+        events::{publish, subscribe, StoreEvent},
+        store::PROCESS_MAP_STORE,
+    },
+    error_print,
+    helper::{
+        error::{AppError, AppResult},
+        metrics::{SeriesKey, METRICS_STORE},
+        time::{now_millis, now_secs, parse_duration_ms},
+    },
+    log_print,
+};
+
+pub const RULES_FILE_NAME: &str = "rules.json";
+// 最新样本超过该时间未更新时不参与判断，单位毫秒
+const MAX_SAMPLE_AGE: u64 = 300_000;
+// 最多保留最近恢复的告警数量
+const MAX_RESOLVED_HISTORY: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Comparison {
+    #[serde(rename = ">")]
+    Gt,
+    #[serde(rename = ">=")]
+    Ge,
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Le,
+}
+
+impl Comparison {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            ">" => Some(Comparison::Gt),
+            ">=" => Some(Comparison::Ge),
+            "<" => Some(Comparison::Lt),
+            "<=" => Some(Comparison::Le),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::Gt => value > threshold,
+            Comparison::Ge => value >= threshold,
+            Comparison::Lt => value < threshold,
+            Comparison::Le => value <= threshold,
+        }
+    }
+
+    /// 告警恢复需要越过阈值 hysteresis 的距离，避免在阈值附近反复触发
+    fn resolved(&self, value: f64, threshold: f64, hysteresis: f64) -> bool {
+        match self {
+            Comparison::Gt | Comparison::Ge => value < threshold - hysteresis,
+            Comparison::Lt | Comparison::Le => value > threshold + hysteresis,
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+/// 规则判断的值
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Condition {
+    // 最新样本的值
+    Value,
+    // 与 window 之前相比的增长百分比
+    Growth { window: u64 },
+}
+
+/// 解析后的规则表达式，例如 `cpu.load > 90 for 60s`、`memory.heap_used growth > 20%/10m`
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleExpr {
+    pub series: String,
+    pub condition: Condition,
+    pub comparison: Comparison,
+    pub threshold: f64,
+    // 条件需要持续满足的时间，单位毫秒
+    pub duration: u64,
+}
+
+/// 序列名支持 Node 侧的驼峰写法，例如 heapUsed 转换为 heap_used
+fn normalize_series_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len() + 4);
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            normalized.push('_');
+            normalized.push(c.to_ascii_lowercase());
+        } else {
+            normalized.push(c);
+        }
+    }
+    normalized
+}
+
+impl RuleExpr {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let (condition_part, duration) = match expr.split_once(" for ") {
+            Some((condition, duration)) => (
+                condition,
+                parse_duration_ms(duration)
+                    .ok_or_else(|| format!("持续时间 {} 无效", duration.trim()))?,
+            ),
+            None => (expr, 0),
+        };
+        let mut tokens = condition_part.split_whitespace();
+        let series = tokens.next().ok_or("缺少序列名")?;
+        let mut next = tokens.next().ok_or("缺少比较运算符")?;
+        let growth = next == "growth";
+        if growth {
+            next = tokens.next().ok_or("缺少比较运算符")?;
+        }
+        let comparison =
+            Comparison::parse(next).ok_or_else(|| format!("不支持的比较运算符 {}", next))?;
+        let threshold_part: String = tokens.collect();
+        if threshold_part.is_empty() {
+            return Err("缺少阈值".to_string());
+        }
+
+        let (threshold, condition) = if growth {
+            // 形如 20%/10m
+            let (percent, window) = threshold_part.split_once("%/").ok_or_else(|| {
+                format!("增长阈值 {} 应为 <百分比>%/<时长> 的形式", threshold_part)
+            })?;
+            let window = parse_duration_ms(window)
+                .filter(|window| *window > 0)
+                .ok_or_else(|| format!("增长窗口 {} 无效", window))?;
+            (percent, Condition::Growth { window })
+        } else {
+            (threshold_part.as_str(), Condition::Value)
+        };
+        let threshold: f64 = threshold
+            .parse()
+            .map_err(|_| format!("阈值 {} 不是数字", threshold))?;
+
+        Ok(Self {
+            series: normalize_series_name(series),
+            condition,
+            comparison,
+            threshold,
+            duration,
+        })
+    }
+}
+
+/// rules.json 中的一条规则
+#[derive(Debug, Clone, Deserialize)]
+struct RuleConfig {
+    name: String,
+    expr: String,
+    #[serde(default = "default_severity")]
+    severity: String,
+    // 恢复时需要越过阈值的距离，单位与阈值相同
+    #[serde(default)]
+    hysteresis: f64,
+}
+
+fn default_severity() -> String {
+    "warning".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+struct RulesFile {
+    rules: Vec<RuleConfig>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Rule {
+    pub name: String,
+    pub expr: String,
+    pub severity: String,
+    pub hysteresis: f64,
+    #[serde(skip)]
+    parsed: RuleExpr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertState {
+    Firing,
+    Resolved,
+}
+
+/// 规则在某个进程的某条序列上触发的告警
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub rule: String,
+    pub expr: String,
+    pub severity: String,
+    pub process_id: u32,
+    pub app_name: String,
+    pub series: String,
+    pub thread_id: Option<u32>,
+    pub state: AlertState,
+    // 触发或恢复时的值，进程被移除时为 None
+    pub value: Option<f64>,
+    pub threshold: f64,
+    // timestamp second
+    pub started_at: u64,
+    pub resolved_at: Option<u64>,
+}
+
+/// 告警的唯一标识：(规则名, pid, 序列)
+type AlertKey = (String, u32, SeriesKey);
+
+#[derive(Default)]
+struct AlertStore {
+    firing: HashMap<AlertKey, Alert>,
+    resolved: VecDeque<Alert>,
+}
+
+static ALERTS: LazyLock<Mutex<AlertStore>> = LazyLock::new(|| Mutex::new(AlertStore::default()));
+
+/// 当前触发中的告警，按开始时间排序
+pub fn firing_alerts() -> Vec<Alert> {
+    let mut alerts: Vec<Alert> = ALERTS.lock().unwrap().firing.values().cloned().collect();
+    alerts.sort_by_key(|alert| (alert.started_at, alert.process_id));
+    alerts
+}
+
+/// 最近恢复的告警，从新到旧
+pub fn resolved_alerts() -> Vec<Alert> {
+    ALERTS
+        .lock()
+        .unwrap()
+        .resolved
+        .iter()
+        .rev()
+        .cloned()
+        .collect()
+}
+
+/// 当前生效的规则以及解析失败的原因
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LoadedRules {
+    pub rules: Vec<Rule>,
+    pub errors: Vec<String>,
+}
+
+static LOADED_RULES: LazyLock<Mutex<LoadedRules>> =
+    LazyLock::new(|| Mutex::new(LoadedRules::default()));
+
+pub fn loaded_rules() -> LoadedRules {
+    LOADED_RULES.lock().unwrap().clone()
+}
+
+fn rules_path(agent_dir: &str) -> PathBuf {
+    Path::new(agent_dir).join(RULES_FILE_NAME)
+}
+
+/// 读取规则文件，单条规则解析失败时跳过并记录原因
+fn load_rules(path: &Path) -> AppResult<LoadedRules> {
+    let content = fs::read_to_string(path)?;
+    let file: RulesFile = serde_json::from_str(&content)
+        .map_err(|e| AppError::Config(format!("{} 格式错误: {}", path.display(), e)))?;
+    let mut loaded = LoadedRules::default();
+    for config in file.rules {
+        match RuleExpr::parse(&config.expr) {
+            Ok(parsed) => loaded.rules.push(Rule {
+                name: config.name,
+                expr: config.expr,
+                severity: config.severity,
+                hysteresis: config.hysteresis,
+                parsed,
+            }),
+            Err(e) => loaded.errors.push(format!(
+                "规则 {} 的表达式 `{}` 无效: {}",
+                config.name, config.expr, e
+            )),
+        }
+    }
+    Ok(loaded)
+}
+
+/// 条件开始满足的时间，用于 for 持续时间的判断
+#[derive(Default)]
+struct PendingState {
+    // timestamp millisecond
+    since: HashMap<AlertKey, u64>,
+}
+
+/// 计算规则在序列上的当前值，数据不足时返回 None
+fn evaluate_value(pid: u32, key: &SeriesKey, expr: &RuleExpr, now: u64) -> Option<f64> {
+    let latest = METRICS_STORE.latest_sample(pid, key)?;
+    if now.saturating_sub(latest.timestamp) > MAX_SAMPLE_AGE {
+        return None;
+    }
+    match expr.condition {
+        Condition::Value => Some(latest.value),
+        Condition::Growth { window } => {
+            let from = now.saturating_sub(window);
+            // 取窗口起点附近的第一个点作为基准
+            let base = METRICS_STORE
+                .query(pid, key, from, now)
+                .points
+                .first()?
+                .avg();
+            if base == 0.0 {
+                return None;
+            }
+            Some((latest.value - base) / base.abs() * 100.0)
+        }
+    }
+}
+
+struct RulesEngine {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    rules: Vec<Rule>,
+    pending: PendingState,
+}
+
+impl RulesEngine {
+    /// 规则文件变化时重新加载，文件被删除时清空规则
+    fn reload_if_changed(&mut self) {
+        let modified = fs::metadata(&self.path)
+            .and_then(|meta| meta.modified())
+            .ok();
+        if modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+        let loaded = if modified.is_none() {
+            LoadedRules::default()
+        } else {
+            match load_rules(&self.path) {
+                Ok(loaded) => loaded,
+                Err(e) => {
+                    error_print!("加载告警规则失败: {}", e);
+                    LoadedRules {
+                        rules: Vec::new(),
+                        errors: vec![e.to_string()],
+                    }
+                }
+            }
+        };
+        for error in &loaded.errors {
+            error_print!("{}", error);
+        }
+        log_print!("📏 已加载 {} 条告警规则", loaded.rules.len());
+        self.rules = loaded.rules.clone();
+        *LOADED_RULES.lock().unwrap() = loaded;
+    }
+
+    fn evaluate_once(&mut self) {
+        self.reload_if_changed();
+        let now = now_millis();
+        let processes = PROCESS_MAP_STORE.list();
+        let mut seen: HashSet<AlertKey> = HashSet::new();
+        let mut events = Vec::new();
+
+        {
+            let mut alerts = ALERTS.lock().unwrap();
+            for rule in &self.rules {
+                for process in &processes {
+                    let pid = process.process_id;
+                    let keys = METRICS_STORE
+                        .series_keys(pid)
+                        .into_iter()
+                        .filter(|key| key.name == rule.parsed.series);
+                    for key in keys {
+                        let alert_key: AlertKey = (rule.name.clone(), pid, key.clone());
+                        seen.insert(alert_key.clone());
+                        let Some(value) = evaluate_value(pid, &key, &rule.parsed, now) else {
+                            // 数据中断后重新计算持续时间，避免跨越空档触发 for 规则
+                            self.pending.since.remove(&alert_key);
+                            continue;
+                        };
+                        let expr = &rule.parsed;
+
+                        if let Some(alert) = alerts.firing.get_mut(&alert_key) {
+                            alert.value = Some(value);
+                            if expr
+                                .comparison
+                                .resolved(value, expr.threshold, rule.hysteresis)
+                            {
+                                let mut alert = alerts.firing.remove(&alert_key).unwrap();
+                                alert.state = AlertState::Resolved;
+                                alert.resolved_at = Some(now_secs());
+                                events.push(alert.clone());
+                                push_resolved(&mut alerts.resolved, alert);
+                            }
+                            continue;
+                        }
+
+                        if !expr.comparison.matches(value, expr.threshold) {
+                            self.pending.since.remove(&alert_key);
+                            continue;
+                        }
+                        let since = *self.pending.since.entry(alert_key.clone()).or_insert(now);
+                        if now.saturating_sub(since) < expr.duration {
+                            continue;
+                        }
+                        self.pending.since.remove(&alert_key);
+                        let alert = Alert {
+                            rule: rule.name.clone(),
+                            expr: rule.expr.clone(),
+                            severity: rule.severity.clone(),
+                            process_id: pid,
+                            app_name: process.app_name.clone(),
+                            series: key.name.clone(),
+                            thread_id: key.thread_id,
+                            state: AlertState::Firing,
+                            value: Some(value),
+                            threshold: expr.threshold,
+                            started_at: now_secs(),
+                            resolved_at: None,
+                        };
+                        events.push(alert.clone());
+                        alerts.firing.insert(alert_key, alert);
+                    }
+                }
+            }
+
+            // 规则被删除或进程被移除的告警直接恢复
+            let stale: Vec<AlertKey> = alerts
+                .firing
+                .keys()
+                .filter(|key| !seen.contains(key))
+                .cloned()
+                .collect();
+            events.extend(
+                stale
+                    .iter()
+                    .filter_map(|key| resolve_without_value(&mut alerts, key)),
+            );
+        }
+        self.pending.since.retain(|key, _| seen.contains(key));
+
+        for alert in events {
+            publish_alert(alert);
+        }
+    }
+}
+
+/// 规则被删除或进程被移除时恢复告警，此时没有当前值
+fn resolve_without_value(alerts: &mut AlertStore, key: &AlertKey) -> Option<Alert> {
+    let mut alert = alerts.firing.remove(key)?;
+    alert.state = AlertState::Resolved;
+    alert.value = None;
+    alert.resolved_at = Some(now_secs());
+    push_resolved(&mut alerts.resolved, alert.clone());
+    Some(alert)
+}
+
+/// 进程被移除时立即恢复其所有告警，不等下一轮执行
+fn resolve_process_alerts(pid: u32) {
+    let resolved: Vec<Alert> = {
+        let mut alerts = ALERTS.lock().unwrap();
+        let keys: Vec<AlertKey> = alerts
+            .firing
+            .keys()
+            .filter(|(_, alert_pid, _)| *alert_pid == pid)
+            .cloned()
+            .collect();
+        keys.iter()
+            .filter_map(|key| resolve_without_value(&mut alerts, key))
+            .collect()
+    };
+    for alert in resolved {
+        publish_alert(alert);
+    }
+}
+
+/// 订阅 store 事件，进程被移除时恢复其告警
+fn start_removal_listener() {
+    // 在启动任务前订阅，避免漏掉启动期间的事件
+    let mut events = subscribe();
+    task::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(StoreEvent::ProcessRemoved { process_id, .. }) => {
+                    resolve_process_alerts(process_id)
+                }
+                Ok(_) => {}
+                // 漏掉的移除事件由下一轮执行时的对账兜底
+                Err(RecvError::Lagged(skipped)) => {
+                    error_print!("告警规则跳过了 {} 个 store 事件", skipped)
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+fn push_resolved(history: &mut VecDeque<Alert>, alert: Alert) {
+    if history.len() >= MAX_RESOLVED_HISTORY {
+        history.pop_front();
+    }
+    history.push_back(alert);
+}
+
+fn publish_alert(alert: Alert) {
+    match alert.state {
+        AlertState::Firing => log_print!(
+            "🚨 告警 {} 触发: 进程 {} {} = {:?}",
+            alert.rule,
+            alert.process_id,
+            alert.series,
+            alert.value
+        ),
+        AlertState::Resolved => log_print!(
+            "✅ 告警 {} 恢复: 进程 {} {} = {:?}",
+            alert.rule,
+            alert.process_id,
+            alert.series,
+            alert.value
+        ),
+    }
+    publish(StoreEvent::Alert { alert });
+}
+
+/// 在后台定时执行告警规则，规则文件修改后自动重新加载
+pub fn start_rules(agent_dir: &str, interval_secs: u64) {
+    let mut engine = RulesEngine {
+        path: rules_path(agent_dir),
+        modified: None,
+        rules: Vec::new(),
+        pending: PendingState::default(),
+    };
+    start_removal_listener();
+    task::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(interval_secs));
+        loop {
+            ticker.tick().await;
+            engine.evaluate_once();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_processor::{events::RemoveReason, store::ProcessStore};
+
+    #[test]
+    fn parse_value_rule_with_duration() {
+        let expr = RuleExpr::parse("cpu.load > 90 for 60s").unwrap();
+        assert_eq!(expr.series, "cpu.load");
+        assert_eq!(expr.condition, Condition::Value);
+        assert_eq!(expr.comparison, Comparison::Gt);
+        assert_eq!(expr.threshold, 90.0);
+        assert_eq!(expr.duration, 60_000);
+    }
+
+    #[test]
+    fn parse_growth_rule_and_camel_case_series() {
+        let expr = RuleExpr::parse("memory.heapUsed growth >= 20%/10m").unwrap();
+        assert_eq!(expr.series, "memory.heap_used");
+        assert_eq!(expr.condition, Condition::Growth { window: 600_000 });
+        assert_eq!(expr.comparison, Comparison::Ge);
+        assert_eq!(expr.threshold, 20.0);
+        assert_eq!(expr.duration, 0);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_rules() {
+        for expr in [
+            "",
+            "cpu.load",
+            "cpu.load == 90",
+            "cpu.load >",
+            "cpu.load > high",
+            "cpu.load > 90 for soon",
+            "memory.heap_used growth > 20",
+            "memory.heap_used growth > 20%/0s",
+        ] {
+            assert!(RuleExpr::parse(expr).is_err(), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn resolution_needs_to_cross_hysteresis() {
+        assert!(!Comparison::Gt.resolved(88.0, 90.0, 5.0));
+        assert!(Comparison::Gt.resolved(84.0, 90.0, 5.0));
+        assert!(!Comparison::Lt.resolved(12.0, 10.0, 5.0));
+        assert!(Comparison::Lt.resolved(16.0, 10.0, 5.0));
+    }
+
+    fn rule(name: &str, expr: &str, hysteresis: f64) -> Rule {
+        Rule {
+            name: name.to_string(),
+            expr: expr.to_string(),
+            severity: default_severity(),
+            hysteresis,
+            parsed: RuleExpr::parse(expr).unwrap(),
+        }
+    }
+
+    fn firing_for(pid: u32) -> Vec<String> {
+        firing_alerts()
+            .into_iter()
+            .filter(|alert| alert.process_id == pid)
+            .map(|alert| alert.rule)
+            .collect()
+    }
+
+    #[test]
+    fn alert_fires_and_resolves_past_hysteresis() {
+        let pid = 4_102_101;
+        PROCESS_MAP_STORE.register(ProcessStore::for_test(pid, "rules-test"));
+        let mut engine = RulesEngine {
+            path: std::env::temp_dir()
+                .join("mito-rules-missing")
+                .join(RULES_FILE_NAME),
+            modified: None,
+            rules: vec![
+                rule("hot", "cpu.load > 90", 5.0),
+                rule("hot_for_a_while", "cpu.load > 90 for 60s", 0.0),
+            ],
+            pending: PendingState::default(),
+        };
+        let now = now_millis();
+        let mut evaluate = |offset: u64, value: f64| {
+            METRICS_STORE.record(pid, None, now + offset, &[("cpu.load", value)]);
+            engine.evaluate_once();
+            firing_for(pid)
+        };
+
+        // 持续时间未满足的规则不会触发
+        assert_eq!(evaluate(0, 95.0), ["hot"]);
+        // 回落到阈值以下但未越过 hysteresis 时继续触发
+        assert_eq!(evaluate(1, 88.0), ["hot"]);
+        assert_eq!(evaluate(2, 84.0), Vec::<String>::new());
+        let resolved = resolved_alerts()
+            .into_iter()
+            .find(|alert| alert.process_id == pid)
+            .unwrap();
+        assert_eq!(resolved.state, AlertState::Resolved);
+        assert_eq!(resolved.value, Some(84.0));
+
+        // 进程被移除后告警直接恢复
+        assert_eq!(evaluate(3, 99.0), ["hot"]);
+        PROCESS_MAP_STORE.remove(&pid, RemoveReason::OverBudget);
+        engine.evaluate_once();
+        assert!(firing_for(pid).is_empty());
+    }
+
+    #[test]
+    fn removed_process_alerts_resolve_without_waiting_for_a_tick() {
+        let pid = 4_102_103;
+        PROCESS_MAP_STORE.register(ProcessStore::for_test(pid, "rules-test"));
+        let mut engine = RulesEngine {
+            path: std::env::temp_dir()
+                .join("mito-rules-missing")
+                .join(RULES_FILE_NAME),
+            modified: None,
+            rules: vec![rule("busy", "cpu.load > 90", 0.0)],
+            pending: PendingState::default(),
+        };
+        METRICS_STORE.record(pid, None, now_millis(), &[("cpu.load", 95.0)]);
+        engine.evaluate_once();
+        assert_eq!(firing_for(pid), ["busy"]);
+
+        PROCESS_MAP_STORE.remove(&pid, RemoveReason::OverBudget);
+        resolve_process_alerts(pid);
+        assert!(firing_for(pid).is_empty());
+        let resolved = resolved_alerts()
+            .into_iter()
+            .find(|alert| alert.process_id == pid)
+            .unwrap();
+        assert_eq!(resolved.value, None);
+    }
+
+    #[test]
+    fn pending_duration_restarts_after_data_gap() {
+        let pid = 4_102_104;
+        PROCESS_MAP_STORE.register(ProcessStore::for_test(pid, "rules-test"));
+        let mut engine = RulesEngine {
+            path: std::env::temp_dir()
+                .join("mito-rules-missing")
+                .join(RULES_FILE_NAME),
+            modified: None,
+            rules: vec![rule("busy_for_a_while", "cpu.load > 90 for 60s", 0.0)],
+            pending: PendingState::default(),
+        };
+        let key = SeriesKey {
+            name: "cpu.load".to_string(),
+            thread_id: None,
+        };
+        let alert_key: AlertKey = ("busy_for_a_while".to_string(), pid, key);
+        // 条件很早之前就开始满足，但之后的数据已经过期
+        let now = now_millis();
+        engine
+            .pending
+            .since
+            .insert(alert_key.clone(), now - 10 * 60_000);
+        METRICS_STORE.record(
+            pid,
+            None,
+            now - MAX_SAMPLE_AGE - 60_000,
+            &[("cpu.load", 95.0)],
+        );
+        engine.evaluate_once();
+        assert!(!engine.pending.since.contains_key(&alert_key));
+
+        // 数据恢复后重新开始计算持续时间
+        METRICS_STORE.record(pid, None, now_millis(), &[("cpu.load", 95.0)]);
+        engine.evaluate_once();
+        assert!(firing_for(pid).is_empty());
+        assert!(engine.pending.since.contains_key(&alert_key));
+
+        PROCESS_MAP_STORE.remove(&pid, RemoveReason::OverBudget);
+        METRICS_STORE.remove(pid);
+    }
+
+    #[test]
+    fn growth_is_measured_against_start_of_window() {
+        let pid = 4_102_102;
+        let now = now_millis();
+        METRICS_STORE.record(pid, None, now - 9 * 60_000, &[("memory.heap_used", 100.0)]);
+        METRICS_STORE.record(pid, None, now, &[("memory.heap_used", 130.0)]);
+        let expr = RuleExpr::parse("memory.heap_used growth > 20%/10m").unwrap();
+        let key = SeriesKey {
+            name: "memory.heap_used".to_string(),
+            thread_id: None,
+        };
+        let growth = evaluate_value(pid, &key, &expr, now).unwrap();
+        assert!((growth - 30.0).abs() < 1e-9, "{}", growth);
+        METRICS_STORE.remove(pid);
+    }
+}
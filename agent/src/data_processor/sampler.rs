@@ -0,0 +1,442 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::LazyLock,
+    time::Duration,
+};
+
+use serde::Serialize;
+use strum::Display;
+use tokio::{task, time::interval};
+
+use crate::{
+    data_processor::{
+        anomaly::observe_anomalies,
+        sharded::ShardedMap,
+        store::{ProcessStatus, PROCESS_MAP_STORE},
+    },
+    helper::{
+        metrics::METRICS_STORE,
+        procfs::{
+            read_proc_stat, read_proc_status, read_thread_stats, ProcStat, CLOCK_TICKS_PER_SEC,
+        },
+        time::now_millis,
+    },
+    log_print,
+};
+
+/// Node 进程中线程的用途，根据线程名判断
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ThreadKind {
+    // 执行 JS 的主线程
+    Main,
+    // libuv 线程池，fs、dns、crypto、zlib 等任务
+    LibuvThreadpool,
+    // V8 platform 的后台线程，主要是并发标记、并发编译等 GC 与 JIT 任务
+    V8Platform,
+    // worker_threads 创建的 JS 线程
+    WorkerThread,
+    Other,
+}
+
+// Node 启动时最先创建的线程：默认 4 个 V8 platform worker 与 1 个延迟任务调度线程
+const V8_PLATFORM_STARTUP_THREADS: usize = 5;
+
+impl ThreadKind {
+    /// 根据线程名分类，comm 最多 15 个字符，例如 `V8 DefaultWorker` 会被截断为 `V8 DefaultWorke`
+    ///
+    /// 较旧的 Node 版本不设置线程名，子线程与主线程同名，此时只能按创建顺序推断：
+    /// `unnamed_index` 为该线程在同名子线程中的创建顺序，最先创建的几个是 V8 platform 线程
+    pub fn classify(pid: u32, tid: u32, comm: &str, unnamed_index: Option<usize>) -> Self {
+        if tid == pid {
+            ThreadKind::Main
+        } else if comm.starts_with("libuv-worker") {
+            ThreadKind::LibuvThreadpool
+        } else if comm.starts_with("V8 ") {
+            ThreadKind::V8Platform
+        } else if comm.starts_with("WorkerThread") || comm.starts_with("worker") {
+            ThreadKind::WorkerThread
+        } else if unnamed_index.is_some_and(|index| index < V8_PLATFORM_STARTUP_THREADS) {
+            ThreadKind::V8Platform
+        } else {
+            ThreadKind::Other
+        }
+    }
+}
+
+/// 单个线程在最近一个采样间隔内的 CPU 使用情况，百分比为占单核的比例
+#[derive(Debug, Clone, Serialize)]
+pub struct ThreadSample {
+    pub tid: u32,
+    pub comm: String,
+    pub kind: ThreadKind,
+    pub state: char,
+    // 累计 CPU 时间，单位秒
+    pub utime: f64,
+    pub stime: f64,
+    // 首次采样到的线程没有使用率
+    pub user_percent: Option<f64>,
+    pub system_percent: Option<f64>,
+    pub cpu_percent: Option<f64>,
+}
+
+/// 进程最近一次的线程采样结果
+#[derive(Debug, Clone, Serialize)]
+pub struct ThreadsSnapshot {
+    // timestamp millisecond
+    pub timestamp: u64,
+    pub threads: Vec<ThreadSample>,
+    // 各类线程的 CPU 使用率之和
+    pub by_kind: BTreeMap<ThreadKind, f64>,
+}
+
+impl ThreadsSnapshot {
+    /// 占用的内存估算，单位 byte
+    pub fn estimated_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self
+                .threads
+                .iter()
+                .map(|thread| std::mem::size_of::<ThreadSample>() + thread.comm.len())
+                .sum::<usize>()
+            + self.by_kind.len() * std::mem::size_of::<(ThreadKind, f64)>()
+    }
+}
+
+/// 每个进程最近一次的线程采样，供 GET /processes/:pid/threads 使用
+pub static THREAD_SNAPSHOTS: LazyLock<ShardedMap<ThreadsSnapshot>> = LazyLock::new(ShardedMap::new);
+
+/// 上一次采样的 CPU 时间，用于计算采样间隔内的使用率
+struct CpuTicks {
+    // 进程启动时间，变化说明 pid 被复用
+    start_time: u64,
+    utime: u64,
+    stime: u64,
+    // tid -> (utime, stime)
+    threads: HashMap<u32, (u64, u64)>,
+    // timestamp millisecond
+    timestamp: u64,
+}
+
+fn ticks_to_secs(ticks: u64) -> f64 {
+    ticks as f64 / CLOCK_TICKS_PER_SEC as f64
+}
+
+/// 采样间隔内 CPU 时间占单核的百分比，多线程进程可能超过 100
+fn usage_percent(current: u64, previous: u64, elapsed_secs: f64) -> f64 {
+    ticks_to_secs(current.saturating_sub(previous)) / elapsed_secs * 100.0
+}
+
+/// 不依赖 Node 侧上报，直接从 /proc 采样进程的 CPU、内存与调度信息
+///
+/// 事件循环被阻塞时 JS 侧的 CPU 采集同样会停止，os 来源的序列可以继续反映进程状态
+#[derive(Default)]
+struct OsSampler {
+    previous: HashMap<u32, CpuTicks>,
+}
+
+impl OsSampler {
+    fn sample_once(&mut self) {
+        let processes = PROCESS_MAP_STORE.list();
+        let registered = |pid: &u32| processes.iter().any(|process| process.process_id == *pid);
+        self.previous.retain(|pid, _| registered(pid));
+        THREAD_SNAPSHOTS.retain(|pid, _| registered(pid));
+
+        for process in &processes {
+            if process.status == ProcessStatus::Exited {
+                continue;
+            }
+            let pid = process.process_id;
+            let (Ok(stat), Ok(status)) = (read_proc_stat(pid), read_proc_status(pid)) else {
+                continue;
+            };
+            // pid 已被其他进程复用，交给 reaper 处理
+            if process
+                .proc_start_time
+                .is_some_and(|start_time| start_time != stat.start_time)
+            {
+                continue;
+            }
+            let thread_stats = read_thread_stats(pid).unwrap_or_default();
+
+            let timestamp = now_millis();
+            let mut values = vec![
+                ("os.cpu.utime", ticks_to_secs(stat.utime)),
+                ("os.cpu.stime", ticks_to_secs(stat.stime)),
+                ("os.memory.rss", status.vm_rss as f64),
+                ("os.memory.hwm", status.vm_hwm as f64),
+                ("os.threads", status.threads as f64),
+                (
+                    "os.ctx_switches.voluntary",
+                    status.voluntary_ctxt_switches as f64,
+                ),
+                (
+                    "os.ctx_switches.nonvoluntary",
+                    status.nonvoluntary_ctxt_switches as f64,
+                ),
+            ];
+
+            let current = CpuTicks {
+                start_time: stat.start_time,
+                utime: stat.utime,
+                stime: stat.stime,
+                threads: thread_stats
+                    .iter()
+                    .map(|thread| (thread.pid, (thread.utime, thread.stime)))
+                    .collect(),
+                timestamp,
+            };
+            // pid 复用时丢弃上一次的采样
+            let previous = self
+                .previous
+                .insert(pid, current)
+                .filter(|previous| previous.start_time == stat.start_time);
+            let elapsed = previous
+                .as_ref()
+                .map(|previous| timestamp.saturating_sub(previous.timestamp) as f64 / 1000.0)
+                .filter(|elapsed| *elapsed > 0.0);
+
+            if let (Some(previous), Some(elapsed)) = (&previous, elapsed) {
+                let user = usage_percent(stat.utime, previous.utime, elapsed);
+                let system = usage_percent(stat.stime, previous.stime, elapsed);
+                values.push(("os.cpu.user", user));
+                values.push(("os.cpu.system", system));
+                values.push(("os.cpu.usage", user + system));
+            }
+
+            let snapshot = build_threads_snapshot(
+                pid,
+                timestamp,
+                &thread_stats,
+                previous.as_ref().zip(elapsed),
+            );
+            let kind_values: Vec<(String, f64)> = snapshot
+                .by_kind
+                .iter()
+                .map(|(kind, percent)| (format!("os.thread_cpu.{}", kind), *percent))
+                .collect();
+            values.extend(
+                kind_values
+                    .iter()
+                    .map(|(name, percent)| (name.as_str(), *percent)),
+            );
+
+            METRICS_STORE.record(pid, None, timestamp, &values);
+            observe_anomalies(pid, None, timestamp, &values);
+            THREAD_SNAPSHOTS.insert(pid, snapshot);
+        }
+    }
+}
+
+fn build_threads_snapshot(
+    pid: u32,
+    timestamp: u64,
+    thread_stats: &[ProcStat],
+    previous: Option<(&CpuTicks, f64)>,
+) -> ThreadsSnapshot {
+    let mut by_kind = BTreeMap::new();
+    let main_comm = thread_stats
+        .iter()
+        .find(|thread| thread.pid == pid)
+        .map(|thread| thread.comm.as_str());
+    // 与主线程同名的子线程按启动时间排序，得到创建顺序
+    let mut unnamed: Vec<&ProcStat> = thread_stats
+        .iter()
+        .filter(|thread| thread.pid != pid && Some(thread.comm.as_str()) == main_comm)
+        .collect();
+    unnamed.sort_by_key(|thread| (thread.start_time, thread.pid));
+    let unnamed_index = |tid: u32| unnamed.iter().position(|thread| thread.pid == tid);
+
+    let threads = thread_stats
+        .iter()
+        .map(|thread| {
+            let kind =
+                ThreadKind::classify(pid, thread.pid, &thread.comm, unnamed_index(thread.pid));
+            // 新创建的线程在上一次采样中不存在，无法计算使用率
+            let usage = previous.and_then(|(previous, elapsed)| {
+                let (utime, stime) = previous.threads.get(&thread.pid)?;
+                Some((
+                    usage_percent(thread.utime, *utime, elapsed),
+                    usage_percent(thread.stime, *stime, elapsed),
+                ))
+            });
+            if let Some((user, system)) = usage {
+                *by_kind.entry(kind).or_insert(0.0) += user + system;
+            }
+            ThreadSample {
+                tid: thread.pid,
+                comm: thread.comm.clone(),
+                kind,
+                state: thread.state,
+                utime: ticks_to_secs(thread.utime),
+                stime: ticks_to_secs(thread.stime),
+                user_percent: usage.map(|(user, _)| user),
+                system_percent: usage.map(|(_, system)| system),
+                cpu_percent: usage.map(|(user, system)| user + system),
+            }
+        })
+        .collect();
+    ThreadsSnapshot {
+        timestamp,
+        threads,
+        by_kind,
+    }
+}
+
+/// 在后台定时采样所有已注册进程，interval 为 0 时不启动
+pub fn start_os_sampler(interval_secs: u64) {
+    if interval_secs == 0 {
+        return;
+    }
+    log_print!("🔍 启动 /proc 采样，每 {}s", interval_secs);
+    task::spawn(async move {
+        let mut sampler = OsSampler::default();
+        let mut ticker = interval(Duration::from_secs(interval_secs));
+        loop {
+            ticker.tick().await;
+            sampler.sample_once();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data_processor::{events::RemoveReason, store::ProcessStore},
+        helper::metrics::SeriesKey,
+    };
+
+    #[test]
+    fn usage_percent_is_relative_to_one_core() {
+        // 2 秒内用了 100 tick，即 1 秒 CPU 时间
+        assert_eq!(usage_percent(300, 200, 2.0), 50.0);
+        // 多线程进程可以超过 100
+        assert_eq!(usage_percent(400, 100, 1.0), 300.0);
+        // 计数回退时按 0 处理
+        assert_eq!(usage_percent(100, 200, 1.0), 0.0);
+    }
+
+    fn thread(tid: u32, comm: &str, start_time: u64, utime: u64, stime: u64) -> ProcStat {
+        ProcStat {
+            pid: tid,
+            comm: comm.to_string(),
+            state: 'S',
+            ppid: 1,
+            utime,
+            stime,
+            start_time,
+        }
+    }
+
+    #[test]
+    fn classify_uses_thread_names() {
+        assert_eq!(ThreadKind::classify(10, 10, "node", None), ThreadKind::Main);
+        assert_eq!(
+            ThreadKind::classify(10, 11, "libuv-worker", None),
+            ThreadKind::LibuvThreadpool
+        );
+        assert_eq!(
+            ThreadKind::classify(10, 12, "V8 DefaultWorke", None),
+            ThreadKind::V8Platform
+        );
+        assert_eq!(
+            ThreadKind::classify(10, 13, "WorkerThread", None),
+            ThreadKind::WorkerThread
+        );
+        assert_eq!(
+            ThreadKind::classify(10, 14, "node", None),
+            ThreadKind::Other
+        );
+    }
+
+    #[test]
+    fn unnamed_threads_are_classified_by_creation_order() {
+        let pid = 10;
+        // 旧版本 Node 的子线程与主线程同名，tid 顺序与创建顺序不一致
+        let mut stats = vec![thread(pid, "node", 100, 0, 0)];
+        stats.extend((0..7).map(|index| thread(30 - index, "node", 101 + index as u64, 0, 0)));
+        let snapshot = build_threads_snapshot(pid, 1_000, &stats, None);
+        let kind = |tid: u32| {
+            snapshot
+                .threads
+                .iter()
+                .find(|thread| thread.tid == tid)
+                .unwrap()
+                .kind
+        };
+        assert_eq!(kind(pid), ThreadKind::Main);
+        // 最先创建的 5 个是 V8 platform 线程
+        assert_eq!(kind(30), ThreadKind::V8Platform);
+        assert_eq!(kind(26), ThreadKind::V8Platform);
+        assert_eq!(kind(25), ThreadKind::Other);
+    }
+
+    #[test]
+    fn threads_snapshot_sums_cpu_by_kind() {
+        let pid = 10;
+        let previous = CpuTicks {
+            start_time: 100,
+            utime: 0,
+            stime: 0,
+            threads: HashMap::from([(10, (100, 0)), (11, (0, 0)), (12, (0, 0))]),
+            timestamp: 0,
+        };
+        let stats = [
+            thread(10, "node", 100, 150, 10),
+            thread(11, "libuv-worker", 101, 20, 0),
+            thread(12, "libuv-worker", 101, 30, 0),
+            // 上一次采样之后创建的线程
+            thread(13, "libuv-worker", 102, 50, 0),
+        ];
+        let snapshot = build_threads_snapshot(pid, 1_000, &stats, Some((&previous, 1.0)));
+        assert_eq!(snapshot.by_kind[&ThreadKind::Main], 60.0);
+        assert_eq!(snapshot.by_kind[&ThreadKind::LibuvThreadpool], 50.0);
+        let main = &snapshot.threads[0];
+        assert_eq!(
+            (main.user_percent, main.system_percent),
+            (Some(50.0), Some(10.0))
+        );
+        assert_eq!(snapshot.threads[3].cpu_percent, None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn sample_once_records_os_series_for_registered_process() {
+        let pid = std::process::id();
+        PROCESS_MAP_STORE.register(ProcessStore::for_test(pid, "self"));
+        let mut sampler = OsSampler::default();
+        sampler.sample_once();
+        let key = |name: &str| SeriesKey {
+            name: name.to_string(),
+            thread_id: None,
+        };
+        assert!(
+            METRICS_STORE
+                .latest_sample(pid, &key("os.memory.rss"))
+                .unwrap()
+                .value
+                > 0.0
+        );
+        assert!(
+            METRICS_STORE
+                .latest_sample(pid, &key("os.threads"))
+                .unwrap()
+                .value
+                >= 1.0
+        );
+        // 第一次采样没有上一次的 CPU 时间，无法计算使用率
+        assert!(METRICS_STORE
+            .latest_sample(pid, &key("os.cpu.usage"))
+            .is_none());
+
+        // 把上一次采样时间往前推，模拟经过了一个采样间隔
+        sampler.previous.get_mut(&pid).unwrap().timestamp -= 1_000;
+        sampler.sample_once();
+        assert!(METRICS_STORE
+            .latest_sample(pid, &key("os.cpu.usage"))
+            .is_some());
+        PROCESS_MAP_STORE.remove(&pid, RemoveReason::OverBudget);
+    }
+}
@@ -0,0 +1,1508 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock, Mutex,
+    },
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{task, time::interval};
+
+use crate::{
+    data_processor::store::PROCESS_MAP_STORE,
+    error_print,
+    helper::{
+        config::SegmentConfig,
+        error::{AppError, AppResult},
+        metrics::{
+            reserve_seq_through, Bucket, RawCursor, RawSince, Sample, SeriesKey, METRICS_STORE,
+            RAW_CURSORS,
+        },
+        time::now_millis,
+    },
+    log_print,
+};
+
+const SEGMENT_DIR_NAME: &str = "segments";
+// 文件头：magic(8) + 分区起点(8) + flags(1)
+const SEGMENT_MAGIC: &[u8; 8] = b"MITOSEG1";
+const HEADER_LEN: u64 = 17;
+const FLAG_COMPACTED: u8 = 1;
+// 每个 segment 覆盖 1 小时
+const PARTITION_MS: u64 = 3_600_000;
+// 压缩后的聚合宽度
+const COMPACTED_WIDTH: u64 = 60_000;
+// 单条记录长度上限，超出视为文件损坏
+const MAX_RECORD_LEN: u32 = 64 * 1024;
+
+const RECORD_BUCKET: u8 = 0;
+const RECORD_PROCESS: u8 = 1;
+
+/// CRC-32 (IEEE)，用于识别写到一半的记录
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// segment 中的一条记录
+#[derive(Debug, Clone)]
+enum Record {
+    // 原始样本为 count 为 1 的桶，压缩后为 1m 聚合桶
+    Bucket {
+        pid: u32,
+        key: SeriesKey,
+        bucket: Bucket,
+    },
+    // 进程在该 segment 中第一次出现时写入，用于按 app 分组
+    Process {
+        pid: u32,
+        app_name: String,
+    },
+}
+
+/// 编码为 [长度 u32][crc u32][内容]，整数均为小端
+fn encode_record(record: &Record, out: &mut Vec<u8>) {
+    let mut payload = Vec::with_capacity(64);
+    match record {
+        Record::Bucket { pid, key, bucket } => {
+            payload.push(RECORD_BUCKET);
+            payload.extend_from_slice(&pid.to_le_bytes());
+            payload.extend_from_slice(&bucket.timestamp.to_le_bytes());
+            payload.extend_from_slice(&bucket.count.to_le_bytes());
+            payload.extend_from_slice(&bucket.sum.to_le_bytes());
+            payload.extend_from_slice(&bucket.min.to_le_bytes());
+            payload.extend_from_slice(&bucket.max.to_le_bytes());
+            payload.push(key.thread_id.is_some() as u8);
+            payload.extend_from_slice(&key.thread_id.unwrap_or(0).to_le_bytes());
+            write_str(&mut payload, &key.name);
+        }
+        Record::Process { pid, app_name } => {
+            payload.push(RECORD_PROCESS);
+            payload.extend_from_slice(&pid.to_le_bytes());
+            write_str(&mut payload, app_name);
+        }
+    }
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(&crc32(&payload).to_le_bytes());
+    out.extend_from_slice(&payload);
+}
+
+fn write_str(out: &mut Vec<u8>, value: &str) {
+    // 名称超长时截断，u16 足够容纳序列名与 app 名
+    let bytes = &value.as_bytes()[..value.len().min(u16::MAX as usize)];
+    out.extend_from_slice(&(bytes.len() as u16).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+/// 按顺序读取字段的游标，越界时返回 None
+struct Cursor<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn f64(&mut self) -> Option<f64> {
+        Some(f64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.u16()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+}
+
+fn decode_record(payload: &[u8]) -> Option<Record> {
+    let mut cursor = Cursor { data: payload };
+    match cursor.u8()? {
+        RECORD_BUCKET => {
+            let pid = cursor.u32()?;
+            let bucket = Bucket {
+                timestamp: cursor.u64()?,
+                count: cursor.u32()?,
+                sum: cursor.f64()?,
+                min: cursor.f64()?,
+                max: cursor.f64()?,
+            };
+            let has_thread = cursor.u8()? != 0;
+            let thread_id = cursor.u32()?;
+            let name = cursor.string()?;
+            Some(Record::Bucket {
+                pid,
+                key: SeriesKey {
+                    name,
+                    thread_id: has_thread.then_some(thread_id),
+                },
+                bucket,
+            })
+        }
+        RECORD_PROCESS => Some(Record::Process {
+            pid: cursor.u32()?,
+            app_name: cursor.string()?,
+        }),
+        _ => None,
+    }
+}
+
+/// 读取文件头，返回 (分区起点, 是否已压缩)
+fn read_header(file: &mut File) -> Option<(u64, bool)> {
+    let mut header = [0u8; HEADER_LEN as usize];
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_exact(&mut header).ok()?;
+    if &header[..8] != SEGMENT_MAGIC {
+        return None;
+    }
+    let start = u64::from_le_bytes(header[8..16].try_into().ok()?);
+    Some((start, header[16] & FLAG_COMPACTED != 0))
+}
+
+fn write_header(file: &mut impl Write, start: u64, compacted: bool) -> io::Result<()> {
+    file.write_all(SEGMENT_MAGIC)?;
+    file.write_all(&start.to_le_bytes())?;
+    file.write_all(&[if compacted { FLAG_COMPACTED } else { 0 }])
+}
+
+/// 从 offset 开始顺序读取记录，遇到不完整或校验失败的记录时停止
+///
+/// 回调参数为 (记录起点, 记录终点, 记录)，返回最后一条有效记录的终点
+fn scan_records(
+    file: &mut File,
+    offset: u64,
+    limit: Option<u64>,
+    mut f: impl FnMut(u64, u64, Record),
+) -> io::Result<u64> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut reader = BufReader::new(file);
+    let mut position = offset;
+    let end = limit.map(|len| offset + len);
+    loop {
+        if end.is_some_and(|end| position >= end) {
+            break;
+        }
+        let mut frame = [0u8; 8];
+        if read_full(&mut reader, &mut frame)? < frame.len() {
+            break;
+        }
+        let len = u32::from_le_bytes(frame[..4].try_into().unwrap());
+        let crc = u32::from_le_bytes(frame[4..].try_into().unwrap());
+        if len == 0 || len > MAX_RECORD_LEN {
+            break;
+        }
+        let mut payload = vec![0u8; len as usize];
+        if read_full(&mut reader, &mut payload)? < payload.len() || crc32(&payload) != crc {
+            break;
+        }
+        let Some(record) = decode_record(&payload) else {
+            break;
+        };
+        let next = position + 8 + len as u64;
+        f(position, next, record);
+        position = next;
+    }
+    Ok(position)
+}
+
+/// 尽量读满 buf，返回实际读取的字节数，文件末尾不足时小于 buf 长度
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// 单条序列在 segment 中的索引，压缩后的 segment 中同一序列的记录连续存放，可按 offset 直接读取
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SeriesIndex {
+    pid: u32,
+    key: SeriesKey,
+    // timestamp millisecond
+    min_ts: u64,
+    max_ts: u64,
+    // 只有压缩后的 segment 有
+    #[serde(default)]
+    offset: Option<u64>,
+    #[serde(default)]
+    len: Option<u64>,
+}
+
+/// segment 的索引，封存和压缩时写入同名的 .idx 文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SegmentIndex {
+    // 分区起点 timestamp millisecond
+    start: u64,
+    // 索引对应的文件长度，与实际文件长度不一致时重新扫描
+    bytes: u64,
+    records: u64,
+    compacted: bool,
+    // pid -> app_name
+    processes: BTreeMap<u32, String>,
+    series: Vec<SeriesIndex>,
+    // pid -> 已落盘的最大写入序号，只有写入时为当前分区的 segment 会更新
+    #[serde(default)]
+    cursors: BTreeMap<u32, u64>,
+}
+
+impl SegmentIndex {
+    fn new(start: u64, compacted: bool) -> Self {
+        Self {
+            start,
+            bytes: HEADER_LEN,
+            records: 0,
+            compacted,
+            processes: BTreeMap::new(),
+            series: Vec::new(),
+            cursors: BTreeMap::new(),
+        }
+    }
+
+    fn time_range(&self) -> Option<(u64, u64)> {
+        let min = self.series.iter().map(|series| series.min_ts).min()?;
+        let max = self.series.iter().map(|series| series.max_ts).max()?;
+        Some((min, max))
+    }
+
+    fn overlaps(&self, from: u64, to: u64) -> bool {
+        self.time_range()
+            .is_some_and(|(min, max)| min <= to && max >= from)
+    }
+}
+
+/// 边写边更新的索引，positions 用于快速定位序列
+struct IndexBuilder {
+    index: SegmentIndex,
+    positions: HashMap<(u32, SeriesKey), usize>,
+}
+
+impl IndexBuilder {
+    fn new(index: SegmentIndex) -> Self {
+        let positions = index
+            .series
+            .iter()
+            .enumerate()
+            .map(|(position, series)| ((series.pid, series.key.clone()), position))
+            .collect();
+        Self { index, positions }
+    }
+
+    fn add(&mut self, start: u64, end: u64, record: &Record) {
+        self.index.records += 1;
+        self.index.bytes = end;
+        match record {
+            Record::Process { pid, app_name } => {
+                self.index.processes.insert(*pid, app_name.clone());
+            }
+            Record::Bucket { pid, key, bucket } => {
+                let compacted = self.index.compacted;
+                let position = *self
+                    .positions
+                    .entry((*pid, key.clone()))
+                    .or_insert_with(|| {
+                        self.index.series.push(SeriesIndex {
+                            pid: *pid,
+                            key: key.clone(),
+                            min_ts: bucket.timestamp,
+                            max_ts: bucket.timestamp,
+                            offset: compacted.then_some(start),
+                            len: compacted.then_some(0),
+                        });
+                        self.index.series.len() - 1
+                    });
+                let series = &mut self.index.series[position];
+                series.min_ts = series.min_ts.min(bucket.timestamp);
+                series.max_ts = series.max_ts.max(bucket.timestamp);
+                if let Some(offset) = series.offset {
+                    series.len = Some(end - offset);
+                }
+            }
+        }
+    }
+}
+
+fn segment_path(dir: &Path, start: u64) -> PathBuf {
+    dir.join(format!("seg-{}.log", start))
+}
+
+fn index_path(segment: &Path) -> PathBuf {
+    segment.with_extension("idx")
+}
+
+/// 先写临时文件再重命名，避免留下写了一半的索引
+fn write_index(segment: &Path, index: &SegmentIndex) -> AppResult<()> {
+    let path = index_path(segment);
+    let tmp_path = path.with_extension("idx.tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(&serde_json::to_vec(index)?)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+/// 加载 segment 的索引，索引缺失或与文件长度不一致时重新扫描文件
+///
+/// 扫描时遇到写了一半的记录（崩溃时的尾部）会把文件截断到最后一条完整记录
+///
+/// 索引过期时沿用其中的游标，游标只在样本写入成功后才写入索引，最多重复落盘之后的样本
+fn recover_segment(path: &Path) -> AppResult<SegmentIndex> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let file_len = file.metadata()?.len();
+    let (start, compacted) = read_header(&mut file)
+        .ok_or_else(|| AppError::DataProcessing(format!("{} 文件头无效", path.display())))?;
+
+    let cursors = match fs::read(index_path(path))
+        .ok()
+        .and_then(|content| serde_json::from_slice::<SegmentIndex>(&content).ok())
+        .filter(|index| index.start == start)
+    {
+        Some(index) if index.bytes == file_len => return Ok(index),
+        Some(index) => index.cursors,
+        None => BTreeMap::new(),
+    };
+
+    let mut builder = IndexBuilder::new(SegmentIndex::new(start, compacted));
+    builder.index.cursors = cursors;
+    let valid_len = scan_records(&mut file, HEADER_LEN, None, |begin, end, record| {
+        builder.add(begin, end, &record)
+    })?;
+    if valid_len < file_len {
+        log_print!(
+            "🩹 {} 尾部有 {} 字节不完整的数据，已截断",
+            path.display(),
+            file_len - valid_len
+        );
+        file.set_len(valid_len)?;
+        file.sync_all()?;
+    }
+    builder.index.bytes = valid_len;
+    Ok(builder.index)
+}
+
+/// 已封存的 segment
+struct Segment {
+    path: PathBuf,
+    index: SegmentIndex,
+}
+
+/// 正在追加写入的 segment
+struct ActiveSegment {
+    path: PathBuf,
+    file: File,
+    builder: IndexBuilder,
+}
+
+impl ActiveSegment {
+    fn create(dir: &Path, start: u64) -> AppResult<Self> {
+        let path = segment_path(dir, start);
+        let mut file = OpenOptions::new()
+            .create_new(true)
+            .append(true)
+            .open(&path)?;
+        write_header(&mut file, start, false)?;
+        file.sync_all()?;
+        Ok(Self {
+            path,
+            file,
+            builder: IndexBuilder::new(SegmentIndex::new(start, false)),
+        })
+    }
+
+    fn reopen(path: PathBuf, index: SegmentIndex) -> AppResult<Self> {
+        let file = OpenOptions::new().append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file,
+            builder: IndexBuilder::new(index),
+        })
+    }
+
+    fn start(&self) -> u64 {
+        self.builder.index.start
+    }
+
+    /// 追加一批记录并 fsync，写入成功后才更新索引
+    fn append(&mut self, records: &[Record]) -> AppResult<()> {
+        let mut buf = Vec::new();
+        let mut bounds = Vec::with_capacity(records.len());
+        let mut position = self.builder.index.bytes;
+        for record in records {
+            let before = buf.len();
+            encode_record(record, &mut buf);
+            let end = position + (buf.len() - before) as u64;
+            bounds.push((position, end));
+            position = end;
+        }
+        if let Err(e) = self
+            .file
+            .write_all(&buf)
+            .and_then(|_| self.file.sync_data())
+        {
+            // 写入失败时截断到写入前的长度，避免留下半条记录
+            let _ = self.file.set_len(self.builder.index.bytes);
+            return Err(e.into());
+        }
+        for (record, (begin, end)) in records.iter().zip(bounds) {
+            self.builder.add(begin, end, record);
+        }
+        Ok(())
+    }
+
+    /// 追加进程的样本，进程第一次写入该 segment 时先写入进程记录
+    fn append_samples(
+        &mut self,
+        pid: u32,
+        app_name: &str,
+        points: &[(SeriesKey, Sample)],
+    ) -> AppResult<()> {
+        let mut records = Vec::with_capacity(points.len() + 1);
+        if !self.builder.index.processes.contains_key(&pid) {
+            records.push(Record::Process {
+                pid,
+                app_name: app_name.to_string(),
+            });
+        }
+        records.extend(points.iter().map(|(key, sample)| Record::Bucket {
+            pid,
+            key: key.clone(),
+            bucket: Bucket::from(sample),
+        }));
+        self.append(&records)
+    }
+
+    fn seal(self) -> AppResult<Segment> {
+        self.file.sync_all()?;
+        write_index(&self.path, &self.builder.index)?;
+        Ok(Segment {
+            path: self.path,
+            index: self.builder.index,
+        })
+    }
+}
+
+/// 落盘存储的统计信息
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentStats {
+    pub segments: usize,
+    pub bytes: u64,
+    pub compacted: usize,
+    // 最早数据的时间 timestamp millisecond
+    pub oldest: Option<u64>,
+}
+
+/// 查询时在锁内复制的 segment 信息，读取文件时不再持有锁
+struct SegmentSnapshot {
+    path: PathBuf,
+    compacted: bool,
+    // 快照时的文件长度，之后追加的记录不读取
+    bytes: u64,
+    // 压缩后的 segment 中匹配序列的 (offset, len)
+    ranges: Vec<(u64, u64)>,
+}
+
+/// 按小时分区的追加写 segment 存储
+///
+/// 和导出器一样按写入序号记录每个进程已落盘的位置，游标登记在 RAW_CURSORS 中
+pub struct SegmentStore {
+    dir: PathBuf,
+    sealed: Vec<Segment>,
+    active: Option<ActiveSegment>,
+    cursors: RawCursor,
+}
+
+impl SegmentStore {
+    /// 打开 agent_dir 下的 segment 目录，恢复索引并截断不完整的尾部
+    pub fn open(agent_dir: &str) -> AppResult<Self> {
+        let dir = Path::new(agent_dir).join(SEGMENT_DIR_NAME);
+        fs::create_dir_all(&dir)?;
+
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("");
+            // 压缩或写索引时崩溃留下的临时文件
+            if name.ends_with(".tmp") {
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+            if let Some(start) = name
+                .strip_prefix("seg-")
+                .and_then(|name| name.strip_suffix(".log"))
+                .and_then(|start| start.parse::<u64>().ok())
+            {
+                paths.push((start, path));
+            }
+        }
+        paths.sort_by_key(|(start, _)| *start);
+
+        let current = partition_start(now_millis());
+        let mut store = Self {
+            dir,
+            sealed: Vec::new(),
+            active: None,
+            cursors: RAW_CURSORS.register(),
+        };
+        for (start, path) in paths {
+            let index = match recover_segment(&path) {
+                Ok(index) => index,
+                Err(e) => {
+                    error_print!("segment {} 无法恢复，已删除: {}", path.display(), e);
+                    remove_segment(&path);
+                    continue;
+                }
+            };
+            if start == current && !index.compacted {
+                store.active = Some(ActiveSegment::reopen(path, index)?);
+            } else {
+                write_index(&path, &index)?;
+                store.sealed.push(Segment { path, index });
+            }
+        }
+        // 从索引恢复游标，快照早于最后一次落盘时，之后写入的样本序号也要大于已落盘的序号
+        for (pid, seq) in store.flushed_seqs() {
+            reserve_seq_through(seq);
+            store.cursors.advance(pid, seq);
+        }
+        Ok(store)
+    }
+
+    /// 所有 segment 索引中记录的各进程已落盘的最大写入序号
+    fn flushed_seqs(&self) -> BTreeMap<u32, u64> {
+        let mut flushed = BTreeMap::new();
+        for index in self.segments() {
+            for (pid, seq) in &index.cursors {
+                let max = flushed.entry(*pid).or_insert(0);
+                *max = (*max).max(*seq);
+            }
+        }
+        flushed
+    }
+
+    /// 按分区起点顺序插入封存的 segment，保留策略从最旧的开始删除
+    fn insert_sealed(&mut self, segment: Segment) {
+        let position = self
+            .sealed
+            .partition_point(|sealed| sealed.index.start < segment.index.start);
+        self.sealed.insert(position, segment);
+    }
+
+    /// 切换到当前分区的 segment，上一个分区的 segment 封存
+    ///
+    /// 新的 segment 继承已落盘的游标，旧 segment 被保留策略删除后游标仍然可以恢复
+    fn rotate(&mut self, now: u64) -> AppResult<&mut ActiveSegment> {
+        let current = partition_start(now);
+        if self
+            .active
+            .as_ref()
+            .is_some_and(|active| active.start() != current)
+        {
+            let segment = self.active.take().unwrap().seal()?;
+            log_print!("📦 segment {} 已封存", segment.path.display());
+            self.insert_sealed(segment);
+        }
+        if self.active.is_none() {
+            let cursors = self.flushed_seqs();
+            // 时钟超前的样本可能已经写入了当前分区的 segment
+            let mut active = match self
+                .sealed
+                .iter()
+                .position(|segment| segment.index.start == current)
+            {
+                Some(position) => {
+                    let segment = &self.sealed[position];
+                    let active =
+                        ActiveSegment::reopen(segment.path.clone(), segment.index.clone())?;
+                    self.sealed.remove(position);
+                    active
+                }
+                None => ActiveSegment::create(&self.dir, current)?,
+            };
+            active.builder.index.cursors = cursors;
+            self.active = Some(active);
+        }
+        Ok(self.active.as_mut().unwrap())
+    }
+
+    /// 把样本追加到 start 分区的 segment
+    ///
+    /// 不属于当前分区的样本（延迟上报或时钟超前）写入对应分区的 segment，写完后重新封存；
+    /// 已压缩的 segment 中追加的是原始样本，查询时与 1m 聚合桶一起合并
+    fn append_partition(
+        &mut self,
+        start: u64,
+        pid: u32,
+        app_name: &str,
+        points: &[(SeriesKey, Sample)],
+    ) -> AppResult<()> {
+        if let Some(active) = self
+            .active
+            .as_mut()
+            .filter(|active| active.start() == start)
+        {
+            return active.append_samples(pid, app_name, points);
+        }
+        let position = self
+            .sealed
+            .iter()
+            .position(|segment| segment.index.start == start);
+        let mut segment = match position {
+            Some(position) => {
+                let segment = &self.sealed[position];
+                ActiveSegment::reopen(segment.path.clone(), segment.index.clone())?
+            }
+            None => ActiveSegment::create(&self.dir, start)?,
+        };
+        let result = segment.append_samples(pid, app_name, points);
+        let segment = segment.seal()?;
+        match position {
+            Some(position) => self.sealed[position] = segment,
+            None => self.insert_sealed(segment),
+        }
+        result
+    }
+
+    /// 把内存中新写入的原始样本按样本时间追加到对应小时的 segment，返回写入的样本数
+    ///
+    /// 单个进程写入失败时记录日志并继续写入其他进程，该进程的游标不推进，下一轮重试
+    ///
+    /// 写入成功后把各进程的写入序号记录到当前分区 segment 的索引中，重新打开时据此恢复游标
+    pub fn flush(&mut self) -> AppResult<usize> {
+        let processes = PROCESS_MAP_STORE.list();
+        self.cursors
+            .retain(|pid| processes.iter().any(|process| process.process_id == pid));
+        // 没有新数据时也按时切换分区，让上一个分区及时封存
+        if self.active.is_some() {
+            self.rotate(now_millis())?;
+        }
+        let mut written = 0;
+        for process in processes {
+            let pid = process.process_id;
+            let RawSince { points, seq } = METRICS_STORE.raw_since(pid, self.cursors.get(pid));
+            if points.is_empty() {
+                continue;
+            }
+            let count = points.len();
+            let mut partitions: BTreeMap<u64, Vec<(SeriesKey, Sample)>> = BTreeMap::new();
+            for (_, key, sample) in points {
+                partitions
+                    .entry(partition_start(sample.timestamp))
+                    .or_default()
+                    .push((key, sample));
+            }
+            let result = partitions.iter().try_for_each(|(start, points)| {
+                self.append_partition(*start, pid, &process.app_name, points)
+            });
+            if let Err(e) = result {
+                error_print!("进程 {} 的 {} 个样本写入 segment 失败: {}", pid, count, e);
+                continue;
+            }
+            written += count;
+            self.cursors.advance(pid, seq);
+            self.rotate(now_millis())?
+                .builder
+                .index
+                .cursors
+                .insert(pid, seq);
+        }
+        if written > 0 {
+            let active = self.rotate(now_millis())?;
+            write_index(&active.path, &active.builder.index)?;
+        }
+        Ok(written)
+    }
+
+    /// 封存超过 compact_after 且尚未压缩的 segment
+    fn compaction_due(&self, now: u64, compact_after: u64) -> Vec<(PathBuf, SegmentIndex)> {
+        self.sealed
+            .iter()
+            .filter(|segment| {
+                !segment.index.compacted
+                    && segment.index.start + PARTITION_MS + compact_after <= now
+            })
+            .map(|segment| (segment.path.clone(), segment.index.clone()))
+            .collect()
+    }
+
+    /// 压缩完成后更新 segment 的索引
+    fn replace_index(&mut self, path: &Path, index: SegmentIndex) {
+        if let Some(segment) = self.sealed.iter_mut().find(|segment| segment.path == path) {
+            segment.index = index;
+        }
+    }
+
+    /// 删除超过保留时间的 segment，总大小超出上限时继续删除最旧的 segment
+    pub fn enforce_retention(&mut self, now: u64, retention: u64, max_bytes: u64) {
+        let expire_before = now.saturating_sub(retention);
+        self.sealed.retain(|segment| {
+            let expired = segment.index.start + PARTITION_MS <= expire_before;
+            if expired {
+                log_print!("🧹 segment {} 超过保留时间，已删除", segment.path.display());
+                remove_segment(&segment.path);
+            }
+            !expired
+        });
+        while self.total_bytes() > max_bytes && !self.sealed.is_empty() {
+            let segment = self.sealed.remove(0);
+            log_print!("🧹 segment 总大小超出上限，删除 {}", segment.path.display());
+            remove_segment(&segment.path);
+        }
+    }
+
+    fn total_bytes(&self) -> u64 {
+        self.segments().map(|index| index.bytes).sum()
+    }
+
+    fn segments(&self) -> impl Iterator<Item = &SegmentIndex> {
+        self.sealed
+            .iter()
+            .map(|segment| &segment.index)
+            .chain(self.active.iter().map(|active| &active.builder.index))
+    }
+
+    fn segment_paths(&self) -> impl Iterator<Item = (&Path, &SegmentIndex)> {
+        self.sealed
+            .iter()
+            .map(|segment| (segment.path.as_path(), &segment.index))
+            .chain(
+                self.active
+                    .iter()
+                    .map(|active| (active.path.as_path(), &active.builder.index)),
+            )
+    }
+
+    pub fn stats(&self) -> SegmentStats {
+        SegmentStats {
+            segments: self.sealed.len() + self.active.is_some() as usize,
+            bytes: self.total_bytes(),
+            compacted: self
+                .sealed
+                .iter()
+                .filter(|segment| segment.index.compacted)
+                .count(),
+            oldest: self
+                .segments()
+                .filter_map(|index| index.time_range().map(|(min, _)| min))
+                .min(),
+        }
+    }
+
+    /// [from, to] 范围内有数据的进程及其 app_name
+    pub fn processes(&self, from: u64, to: u64) -> BTreeMap<u32, String> {
+        let mut processes = BTreeMap::new();
+        for index in self.segments().filter(|index| index.overlaps(from, to)) {
+            for series in &index.series {
+                if series.min_ts <= to && series.max_ts >= from {
+                    let app_name = index
+                        .processes
+                        .get(&series.pid)
+                        .cloned()
+                        .unwrap_or_default();
+                    processes.entry(series.pid).or_insert(app_name);
+                }
+            }
+        }
+        processes
+    }
+
+    /// 复制与查询条件相关的 segment 信息
+    fn snapshot_matching(
+        &self,
+        pid: u32,
+        filter: &impl Fn(&SeriesKey) -> bool,
+        from: u64,
+        to: u64,
+    ) -> Vec<SegmentSnapshot> {
+        self.segment_paths()
+            .filter_map(|(path, index)| {
+                let matching: Vec<&SeriesIndex> = index
+                    .series
+                    .iter()
+                    .filter(|series| {
+                        series.pid == pid
+                            && series.min_ts <= to
+                            && series.max_ts >= from
+                            && filter(&series.key)
+                    })
+                    .collect();
+                if matching.is_empty() {
+                    return None;
+                }
+                Some(SegmentSnapshot {
+                    path: path.to_path_buf(),
+                    compacted: index.compacted,
+                    bytes: index.bytes,
+                    ranges: matching
+                        .iter()
+                        .filter_map(|series| Some((series.offset?, series.len?)))
+                        .collect(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// 读取快照中的 segment，查询进程下满足条件的序列，数据按 width 对齐合并为桶
+///
+/// 文件已被保留策略删除时跳过，快照之后被压缩时按新文件头扫描整个文件
+fn read_matching(
+    snapshots: Vec<SegmentSnapshot>,
+    pid: u32,
+    filter: impl Fn(&SeriesKey) -> bool,
+    from: u64,
+    to: u64,
+    width: u64,
+) -> AppResult<Vec<(SeriesKey, Vec<Bucket>)>> {
+    let width = width.max(1);
+    let mut merged: BTreeMap<SeriesKey, BTreeMap<u64, Bucket>> = BTreeMap::new();
+    let mut add = |key: &SeriesKey, bucket: &Bucket| {
+        if bucket.timestamp < from || bucket.timestamp > to {
+            return;
+        }
+        let start = bucket.timestamp - bucket.timestamp % width;
+        merged
+            .entry(key.clone())
+            .or_default()
+            .entry(start)
+            .and_modify(|existing| existing.merge(bucket))
+            .or_insert(Bucket {
+                timestamp: start,
+                ..*bucket
+            });
+    };
+
+    for snapshot in snapshots {
+        let mut file = match File::open(&snapshot.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let Some((_, compacted)) = read_header(&mut file) else {
+            continue;
+        };
+        let mut visit = |_: u64, _: u64, record: Record| {
+            if let Record::Bucket {
+                pid: record_pid,
+                key,
+                bucket,
+            } = record
+            {
+                if record_pid == pid && filter(&key) {
+                    add(&key, &bucket);
+                }
+            }
+        };
+        if compacted != snapshot.compacted {
+            scan_records(&mut file, HEADER_LEN, None, &mut visit)?;
+        } else if compacted {
+            // 压缩后的 segment 按索引直接读取对应序列
+            for (offset, len) in &snapshot.ranges {
+                scan_records(&mut file, *offset, Some(*len), &mut visit)?;
+            }
+        } else {
+            scan_records(
+                &mut file,
+                HEADER_LEN,
+                Some(snapshot.bytes - HEADER_LEN),
+                &mut visit,
+            )?;
+        }
+    }
+
+    Ok(merged
+        .into_iter()
+        .map(|(key, buckets)| (key, buckets.into_values().collect()))
+        .collect())
+}
+
+fn partition_start(timestamp: u64) -> u64 {
+    timestamp - timestamp % PARTITION_MS
+}
+
+fn remove_segment(path: &Path) {
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(index_path(path));
+}
+
+/// 重写 segment：进程记录在前，之后按 (pid, 序列, 时间) 写入 1m 聚合桶
+///
+/// 先写临时文件并 fsync，再重命名覆盖原文件，最后写索引；任何一步崩溃都能在启动时恢复
+fn compact_segment(path: &Path, index: &SegmentIndex) -> AppResult<SegmentIndex> {
+    let mut processes = index.processes.clone();
+    let mut buckets: BTreeMap<(u32, SeriesKey, u64), Bucket> = BTreeMap::new();
+    let mut file = File::open(path)?;
+    scan_records(
+        &mut file,
+        HEADER_LEN,
+        Some(index.bytes - HEADER_LEN),
+        |_, _, record| match record {
+            Record::Process { pid, app_name } => {
+                processes.insert(pid, app_name);
+            }
+            Record::Bucket { pid, key, bucket } => {
+                let start = bucket.timestamp - bucket.timestamp % COMPACTED_WIDTH;
+                buckets
+                    .entry((pid, key, start))
+                    .and_modify(|existing| existing.merge(&bucket))
+                    .or_insert(Bucket {
+                        timestamp: start,
+                        ..bucket
+                    });
+            }
+        },
+    )?;
+
+    let mut records: Vec<Record> = processes
+        .into_iter()
+        .map(|(pid, app_name)| Record::Process { pid, app_name })
+        .collect();
+    records.extend(
+        buckets
+            .into_iter()
+            .map(|((pid, key, _), bucket)| Record::Bucket { pid, key, bucket }),
+    );
+
+    let tmp_path = path.with_extension("log.tmp");
+    let mut builder = IndexBuilder::new(SegmentIndex::new(index.start, true));
+    builder.index.cursors = index.cursors.clone();
+    {
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        write_header(&mut writer, index.start, true)?;
+        let mut position = HEADER_LEN;
+        let mut buf = Vec::new();
+        for record in &records {
+            buf.clear();
+            encode_record(record, &mut buf);
+            writer.write_all(&buf)?;
+            let end = position + buf.len() as u64;
+            builder.add(position, end, record);
+            position = end;
+        }
+        writer
+            .into_inner()
+            .map_err(|e| AppError::Io(e.into_error()))?
+            .sync_all()?;
+    }
+    fs::rename(&tmp_path, path)?;
+    write_index(path, &builder.index)?;
+    Ok(builder.index)
+}
+
+/// 把封存超过 compact_after 的原始 segment 压缩为 1m 聚合，按序列连续存放
+///
+/// 读写文件时不持有锁，单个 segment 压缩失败时记录日志并继续压缩其余 segment
+fn compact(store: &Mutex<Option<SegmentStore>>, now: u64, compact_after: u64) {
+    let due = match store.lock().unwrap().as_ref() {
+        Some(store) => store.compaction_due(now, compact_after),
+        None => return,
+    };
+    for (path, index) in due {
+        match compact_segment(&path, &index) {
+            Ok(compacted) => {
+                log_print!(
+                    "🗜️ segment {} 已压缩: {}KB -> {}KB",
+                    path.display(),
+                    index.bytes / 1024,
+                    compacted.bytes / 1024
+                );
+                if let Some(store) = store.lock().unwrap().as_mut() {
+                    store.replace_index(&path, compacted);
+                }
+            }
+            Err(e) => error_print!("压缩 segment {} 失败: {}", path.display(), e),
+        }
+    }
+}
+
+/// 未启用落盘时为 None
+///
+/// 只在锁内追加写入和读写索引，压缩与查询读取文件时不持有锁
+pub static SEGMENT_STORE: LazyLock<Mutex<Option<SegmentStore>>> =
+    LazyLock::new(|| Mutex::new(None));
+
+// 落盘存储打开成功后置为 true，查询时判断数据来源不需要加锁
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// 是否启用了落盘存储
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn segment_stats() -> Option<SegmentStats> {
+    SEGMENT_STORE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(SegmentStore::stats)
+}
+
+/// 写入新样本、压缩并清理过期的 segment，涉及文件读写，在阻塞线程池中执行
+fn maintain(config: &SegmentConfig) {
+    if let Some(store) = SEGMENT_STORE.lock().unwrap().as_mut() {
+        if let Err(e) = store.flush() {
+            error_print!("写入 segment 失败: {}", e);
+        }
+    }
+    let now = now_millis();
+    compact(&SEGMENT_STORE, now, config.compact_after * 1000);
+    // 压缩与清理在同一个任务中依次执行，不会删除正在压缩的 segment
+    if let Some(store) = SEGMENT_STORE.lock().unwrap().as_mut() {
+        store.enforce_retention(now, config.retention * 1000, config.max_bytes);
+    }
+}
+
+/// 打开落盘存储并在后台定时写入、压缩和清理，flush_interval 为 0 时不启用
+pub fn start_segments(agent_dir: &str, config: SegmentConfig) {
+    if config.flush_interval == 0 {
+        return;
+    }
+    match SegmentStore::open(agent_dir) {
+        Ok(store) => {
+            let stats = store.stats();
+            log_print!(
+                "💾 已加载 {} 个 segment，共 {}KB",
+                stats.segments,
+                stats.bytes / 1024
+            );
+            *SEGMENT_STORE.lock().unwrap() = Some(store);
+            ENABLED.store(true, Ordering::Relaxed);
+        }
+        Err(e) => {
+            error_print!("打开 segment 存储失败，指标不落盘: {}", e);
+            return;
+        }
+    }
+
+    task::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(config.flush_interval));
+        loop {
+            ticker.tick().await;
+            let config = config.clone();
+            if let Err(e) = task::spawn_blocking(move || maintain(&config)).await {
+                error_print!("segment 后台任务异常: {}", e);
+            }
+        }
+    });
+}
+
+/// 查询落盘数据中进程下满足条件的序列，数据按 width 对齐合并为桶
+///
+/// 会读取 segment 文件，异步上下文中需要放到 spawn_blocking 中调用
+pub fn query_matching(
+    pid: u32,
+    filter: impl Fn(&SeriesKey) -> bool,
+    from: u64,
+    to: u64,
+    width: u64,
+) -> AppResult<Vec<(SeriesKey, Vec<Bucket>)>> {
+    let snapshots = match SEGMENT_STORE.lock().unwrap().as_ref() {
+        Some(store) => store.snapshot_matching(pid, &filter, from, to),
+        None => return Ok(Vec::new()),
+    };
+    read_matching(snapshots, pid, filter, from, to, width)
+}
+
+/// 落盘数据中 [from, to] 范围内有数据的进程及其 app_name
+pub fn processes(from: u64, to: u64) -> BTreeMap<u32, String> {
+    SEGMENT_STORE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|store| store.processes(from, to))
+        .unwrap_or_default()
+}
+
+/// 退出前把尚未落盘的样本写入 segment
+pub async fn flush_segments() {
+    let result = task::spawn_blocking(|| {
+        if let Some(store) = SEGMENT_STORE.lock().unwrap().as_mut() {
+            if let Err(e) = store.flush() {
+                error_print!("写入 segment 失败: {}", e);
+            }
+        }
+    })
+    .await;
+    if let Err(e) = result {
+        error_print!("写入 segment 失败: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_processor::{events::RemoveReason, store::ProcessStore};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("mito-segments-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn key(name: &str) -> SeriesKey {
+        SeriesKey {
+            name: name.to_string(),
+            thread_id: None,
+        }
+    }
+
+    fn bucket_record(pid: u32, name: &str, timestamp: u64, value: f64) -> Record {
+        Record::Bucket {
+            pid,
+            key: key(name),
+            bucket: Bucket {
+                timestamp,
+                count: 1,
+                sum: value,
+                min: value,
+                max: value,
+            },
+        }
+    }
+
+    /// 在 start 分区写入记录后封存
+    fn sealed_segment(dir: &Path, start: u64, records: &[Record]) -> Segment {
+        let mut active = ActiveSegment::create(dir, start).unwrap();
+        active.append(records).unwrap();
+        active.seal().unwrap()
+    }
+
+    fn store(dir: &Path, sealed: Vec<Segment>) -> SegmentStore {
+        SegmentStore {
+            dir: dir.to_path_buf(),
+            sealed,
+            active: None,
+            cursors: RAW_CURSORS.register(),
+        }
+    }
+
+    fn query(store: &SegmentStore, pid: u32, width: u64) -> Vec<(SeriesKey, Vec<Bucket>)> {
+        let all = |_: &SeriesKey| true;
+        read_matching(
+            store.snapshot_matching(pid, &all, 0, u64::MAX),
+            pid,
+            all,
+            0,
+            u64::MAX,
+            width,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn crc32_matches_ieee_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let records = [
+            Record::Process {
+                pid: 7,
+                app_name: "demo".to_string(),
+            },
+            Record::Bucket {
+                pid: 7,
+                key: SeriesKey {
+                    name: "cpu.load".to_string(),
+                    thread_id: Some(3),
+                },
+                bucket: Bucket {
+                    timestamp: 1_000,
+                    count: 2,
+                    sum: 1.5,
+                    min: 0.5,
+                    max: 1.0,
+                },
+            },
+        ];
+        for record in &records {
+            let mut buf = Vec::new();
+            encode_record(record, &mut buf);
+            let len = u32::from_le_bytes(buf[..4].try_into().unwrap()) as usize;
+            assert_eq!(len, buf.len() - 8);
+            let decoded = decode_record(&buf[8..]).unwrap();
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", record));
+        }
+        assert!(decode_record(&[9]).is_none());
+        assert!(decode_record(&[RECORD_BUCKET, 1, 2]).is_none());
+    }
+
+    #[test]
+    fn recover_truncates_torn_tail() {
+        let dir = temp_dir("torn");
+        let segment = sealed_segment(
+            &dir,
+            0,
+            &[
+                bucket_record(1, "cpu.load", 1_000, 1.0),
+                bucket_record(1, "cpu.load", 2_000, 2.0),
+            ],
+        );
+        let valid_len = segment.index.bytes;
+        // 崩溃时写了一半的记录，索引也已过期
+        let mut tail = Vec::new();
+        encode_record(&bucket_record(1, "cpu.load", 3_000, 3.0), &mut tail);
+        let mut file = OpenOptions::new().append(true).open(&segment.path).unwrap();
+        file.write_all(&tail[..tail.len() - 3]).unwrap();
+        drop(file);
+
+        let index = recover_segment(&segment.path).unwrap();
+        assert_eq!(index.records, 2);
+        assert_eq!(index.bytes, valid_len);
+        assert_eq!(fs::metadata(&segment.path).unwrap().len(), valid_len);
+        assert_eq!(index.time_range(), Some((1_000, 2_000)));
+
+        // 校验失败的记录同样截断
+        let mut corrupted = tail.clone();
+        *corrupted.last_mut().unwrap() ^= 0xFF;
+        let mut file = OpenOptions::new().append(true).open(&segment.path).unwrap();
+        file.write_all(&corrupted).unwrap();
+        drop(file);
+        assert_eq!(recover_segment(&segment.path).unwrap().records, 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compaction_merges_into_minute_buckets_by_series() {
+        let dir = temp_dir("compact");
+        let segment = sealed_segment(
+            &dir,
+            0,
+            &[
+                Record::Process {
+                    pid: 1,
+                    app_name: "demo".to_string(),
+                },
+                bucket_record(1, "cpu.load", 1_000, 1.0),
+                bucket_record(2, "cpu.load", 1_000, 9.0),
+                bucket_record(1, "memory.rss", 2_000, 5.0),
+                bucket_record(1, "cpu.load", 30_000, 3.0),
+                bucket_record(1, "cpu.load", 61_000, 4.0),
+            ],
+        );
+        let store = Mutex::new(Some(store(&dir, vec![segment])));
+        let before = query(store.lock().unwrap().as_ref().unwrap(), 1, 60_000);
+
+        // 还没到压缩时间
+        compact(&store, PARTITION_MS, 1);
+        assert!(
+            !store.lock().unwrap().as_ref().unwrap().sealed[0]
+                .index
+                .compacted
+        );
+
+        // 压缩前的查询快照在压缩后读取时按新文件头扫描
+        let guard = store.lock().unwrap();
+        let all = |_: &SeriesKey| true;
+        let stale = guard
+            .as_ref()
+            .unwrap()
+            .snapshot_matching(1, &all, 0, u64::MAX);
+        drop(guard);
+        compact(&store, PARTITION_MS, 0);
+
+        let guard = store.lock().unwrap();
+        let store = guard.as_ref().unwrap();
+        let index = &store.sealed[0].index;
+        assert!(index.compacted);
+        assert_eq!(index.records, 1 + 4);
+        assert_eq!(store.processes(0, u64::MAX).get(&1).unwrap(), "demo");
+
+        let after = query(store, 1, 60_000);
+        assert_eq!(after, before);
+        let cpu = &after
+            .iter()
+            .find(|(key, _)| key.name == "cpu.load")
+            .unwrap()
+            .1;
+        assert_eq!(cpu.len(), 2);
+        assert_eq!((cpu[0].count, cpu[0].sum, cpu[0].max), (2, 4.0, 3.0));
+        let stale = read_matching(stale, 1, all, 0, u64::MAX, 60_000).unwrap();
+        assert_eq!(stale, before);
+        // 压缩后的索引与重新扫描的结果一致
+        let recovered = recover_segment(&store.sealed[0].path).unwrap();
+        assert_eq!(recovered.records, index.records);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compaction_failure_does_not_stop_other_segments() {
+        let dir = temp_dir("compact-error");
+        let missing = sealed_segment(&dir, 0, &[bucket_record(1, "cpu.load", 1_000, 1.0)]);
+        let kept = sealed_segment(
+            &dir,
+            PARTITION_MS,
+            &[bucket_record(1, "cpu.load", PARTITION_MS + 1_000, 2.0)],
+        );
+        fs::remove_file(&missing.path).unwrap();
+        let store = Mutex::new(Some(store(&dir, vec![missing, kept])));
+
+        compact(&store, 3 * PARTITION_MS, 0);
+        let guard = store.lock().unwrap();
+        let sealed = &guard.as_ref().unwrap().sealed;
+        assert!(!sealed[0].index.compacted);
+        assert!(sealed[1].index.compacted);
+        // 被删除的文件在查询时跳过
+        assert_eq!(query(guard.as_ref().unwrap(), 1, 1)[0].1.len(), 1);
+        drop(guard);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn retention_removes_expired_then_oldest_segments() {
+        let dir = temp_dir("retention");
+        let segments: Vec<Segment> = (0..3)
+            .map(|hour| {
+                let start = hour * PARTITION_MS;
+                sealed_segment(&dir, start, &[bucket_record(1, "cpu.load", start, 1.0)])
+            })
+            .collect();
+        let paths: Vec<PathBuf> = segments
+            .iter()
+            .map(|segment| segment.path.clone())
+            .collect();
+        let mut store = store(&dir, segments);
+
+        // 第一个分区在 1h 时结束，保留 1h 时 2h 之前结束的分区过期
+        store.enforce_retention(2 * PARTITION_MS, PARTITION_MS, u64::MAX);
+        assert_eq!(store.sealed.len(), 2);
+        assert!(!paths[0].exists());
+        assert!(!index_path(&paths[0]).exists());
+
+        let one_segment = store.sealed[1].index.bytes;
+        store.enforce_retention(2 * PARTITION_MS, u64::MAX, one_segment);
+        assert_eq!(store.stats().segments, 1);
+        assert!(!paths[1].exists());
+        assert!(paths[2].exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn flush_uses_sequence_cursor_and_reopen_restores_it() {
+        let dir = temp_dir("flush");
+        let agent_dir = dir.to_str().unwrap();
+        let pid = 4_102_501;
+        PROCESS_MAP_STORE.register(ProcessStore::for_test(pid, "segments-test"));
+        let now = now_millis();
+        METRICS_STORE.record(pid, None, now, &[("cpu.load", 1.0), ("memory.rss", 2.0)]);
+
+        let count = |store: &SegmentStore| -> u32 {
+            query(store, pid, 1)
+                .iter()
+                .flat_map(|(_, buckets)| buckets.iter().map(|bucket| bucket.count))
+                .sum()
+        };
+        let mut store = SegmentStore::open(agent_dir).unwrap();
+        store.flush().unwrap();
+        assert_eq!(count(&store), 2);
+
+        // 迟到的样本时间更早，按写入序号仍会落盘
+        METRICS_STORE.record(pid, None, now - 1_000, &[("cpu.load", 0.5)]);
+        store.flush().unwrap();
+        assert_eq!(count(&store), 3);
+        store.flush().unwrap();
+        assert_eq!(count(&store), 3);
+        drop(store);
+
+        // 重新打开后根据索引中的写入序号恢复游标，不会重复写入
+        let mut store = SegmentStore::open(agent_dir).unwrap();
+        store.flush().unwrap();
+        assert_eq!(count(&store), 3);
+        METRICS_STORE.record(pid, None, now + 1_000, &[("cpu.load", 3.0)]);
+        store.flush().unwrap();
+        assert_eq!(count(&store), 4);
+
+        PROCESS_MAP_STORE.remove(&pid, RemoveReason::OverBudget);
+        drop(store);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn late_samples_are_written_to_their_own_partition() {
+        let dir = temp_dir("late");
+        let agent_dir = dir.to_str().unwrap();
+        let pid = 4_102_502;
+        PROCESS_MAP_STORE.register(ProcessStore::for_test(pid, "segments-test"));
+        let mut store = SegmentStore::open(agent_dir).unwrap();
+        let now = now_millis();
+        let late = now - 2 * PARTITION_MS;
+        METRICS_STORE.record(pid, None, now, &[("cpu.load", 1.0)]);
+        METRICS_STORE.record(pid, None, late, &[("cpu.load", 0.5)]);
+        store.flush().unwrap();
+
+        assert_eq!(store.active.as_ref().unwrap().start(), partition_start(now));
+        // 其他测试注册的进程也会落盘，只检查本测试的进程
+        let sealed = store
+            .sealed
+            .iter()
+            .find(|segment| segment.index.start == partition_start(late))
+            .unwrap();
+        let series: Vec<(u64, u64)> = sealed
+            .index
+            .series
+            .iter()
+            .filter(|series| series.pid == pid)
+            .map(|series| (series.min_ts, series.max_ts))
+            .collect();
+        assert_eq!(series, [(late, late)]);
+        assert_eq!(sealed.index.processes[&pid], "segments-test");
+
+        // 游标写入当前分区 segment 的索引
+        let seq = store.cursors.get(pid);
+        let active = store.active.as_ref().unwrap();
+        let index: SegmentIndex =
+            serde_json::from_slice(&fs::read(index_path(&active.path)).unwrap()).unwrap();
+        assert_eq!(index.cursors[&pid], seq);
+        drop(store);
+
+        let store = SegmentStore::open(agent_dir).unwrap();
+        assert_eq!(store.cursors.get(pid), seq);
+        assert_eq!(store.flushed_seqs()[&pid], seq);
+
+        PROCESS_MAP_STORE.remove(&pid, RemoveReason::OverBudget);
+        drop(store);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
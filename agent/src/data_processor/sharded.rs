@@ -0,0 +1,240 @@
+use std::{collections::HashMap, sync::RwLock};
+
+// 分片数量，pid 基本连续分配，直接取模即可分散到各个分片
+const SHARD_COUNT: usize = 16;
+
+/// 以 pid 为 key 的分片 map，每个分片独立加读写锁
+///
+/// 不同进程的数据落在不同分片上，读写互不阻塞；读取只返回克隆出来的快照，不对外暴露锁
+pub struct ShardedMap<V> {
+    shards: Vec<RwLock<HashMap<u32, V>>>,
+}
+
+impl<V> Default for ShardedMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> ShardedMap<V> {
+    pub fn new() -> Self {
+        Self {
+            shards: (0..SHARD_COUNT)
+                .map(|_| RwLock::new(HashMap::new()))
+                .collect(),
+        }
+    }
+
+    fn shard(&self, key: u32) -> &RwLock<HashMap<u32, V>> {
+        &self.shards[key as usize % SHARD_COUNT]
+    }
+
+    pub fn insert(&self, key: u32, value: V) -> Option<V> {
+        self.shard(key).write().unwrap().insert(key, value)
+    }
+
+    pub fn remove(&self, key: u32) -> Option<V> {
+        self.shard(key).write().unwrap().remove(&key)
+    }
+
+    pub fn contains_key(&self, key: u32) -> bool {
+        self.shard(key).read().unwrap().contains_key(&key)
+    }
+
+    /// 只读访问单个条目
+    pub fn read<R>(&self, key: u32, f: impl FnOnce(&V) -> R) -> Option<R> {
+        self.shard(key).read().unwrap().get(&key).map(f)
+    }
+
+    /// 原地更新单个条目，条目不存在时返回 None
+    pub fn update<R>(&self, key: u32, f: impl FnOnce(&mut V) -> R) -> Option<R> {
+        self.shard(key).write().unwrap().get_mut(&key).map(f)
+    }
+
+    /// 获取条目并原地更新，条目不存在时先用 default 创建
+    pub fn upsert<R>(
+        &self,
+        key: u32,
+        default: impl FnOnce() -> V,
+        f: impl FnOnce(&mut V) -> R,
+    ) -> R {
+        let mut shard = self.shard(key).write().unwrap();
+        f(shard.entry(key).or_insert_with(default))
+    }
+
+    /// 逐个分片过滤条目，同一时间只锁一个分片
+    pub fn retain(&self, mut f: impl FnMut(&u32, &mut V) -> bool) {
+        for shard in &self.shards {
+            shard.write().unwrap().retain(&mut f);
+        }
+    }
+
+    /// 逐个分片遍历条目，同一时间只锁一个分片
+    pub fn for_each(&self, mut f: impl FnMut(&u32, &V)) {
+        for shard in &self.shards {
+            for (key, value) in shard.read().unwrap().iter() {
+                f(key, value);
+            }
+        }
+    }
+}
+
+impl<V: Clone> ShardedMap<V> {
+    pub fn get(&self, key: u32) -> Option<V> {
+        self.read(key, V::clone)
+    }
+
+    /// 所有条目的快照
+    pub fn values(&self) -> Vec<V> {
+        let mut values = Vec::new();
+        self.for_each(|_, value| values.push(value.clone()));
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use crate::data_processor::{
+        events::RemoveReason,
+        store::{ProcessStore, PROCESS_MAP_STORE},
+    };
+
+    // 并发连接数，对应 100+ 个 Node 进程同时上报
+    const CONCURRENT_TASKS: usize = 128;
+    const TOTAL_OPS: usize = CONCURRENT_TASKS * 2_000;
+    // 每处理一批让出执行权，让各任务交错执行
+    const YIELD_EVERY: usize = 64;
+
+    /// 把 TOTAL_OPS 次写入平均分给 tasks 个任务执行，返回每秒写入次数
+    async fn measure(tasks: usize, op: impl Fn(usize) + Send + Sync + 'static) -> f64 {
+        let op = Arc::new(op);
+        let per_task = TOTAL_OPS / tasks;
+        let started = Instant::now();
+        let handles: Vec<_> = (0..tasks)
+            .map(|task| {
+                let op = op.clone();
+                tokio::spawn(async move {
+                    for i in 0..per_task {
+                        op(task);
+                        if i % YIELD_EVERY == 0 {
+                            tokio::task::yield_now().await;
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        let elapsed = started.elapsed().max(Duration::from_micros(1));
+        TOTAL_OPS as f64 / elapsed.as_secs_f64()
+    }
+
+    /// 并发写入的吞吐不应明显低于单任务，允许调度带来的波动
+    fn assert_no_degradation(name: &str, single: f64, concurrent: f64) {
+        assert!(
+            concurrent >= single * 0.5,
+            "{} 并发写入吞吐下降: {:.0} -> {:.0} ops/s",
+            name,
+            single,
+            concurrent
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_upserts_are_not_lost() {
+        let map: Arc<ShardedMap<u64>> = Arc::new(ShardedMap::new());
+        let handles: Vec<_> = (0..CONCURRENT_TASKS)
+            .map(|task| {
+                let map = map.clone();
+                tokio::spawn(async move {
+                    for i in 0..100 {
+                        // 相邻任务写入同一个 key，分散到不同的分片
+                        map.upsert((task / 2) as u32, || 0, |count| *count += 1);
+                        if i % 10 == 0 {
+                            tokio::task::yield_now().await;
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        let mut total = 0;
+        map.for_each(|_, count| total += count);
+        assert_eq!(total, CONCURRENT_TASKS as u64 * 100);
+    }
+
+    // 依赖机器负载的吞吐测试，手动执行：cargo test -- --ignored
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    #[ignore = "吞吐对比依赖机器负载"]
+    async fn upsert_throughput_does_not_degrade_with_concurrent_tasks() {
+        let map: Arc<ShardedMap<u64>> = Arc::new(ShardedMap::new());
+        let key = |task: usize| 1_000 + task as u32;
+
+        let single_map = map.clone();
+        let single = measure(1, move |task| {
+            single_map.upsert(key(task), || 0, |count| *count += 1);
+        })
+        .await;
+        let concurrent_map = map.clone();
+        let concurrent = measure(CONCURRENT_TASKS, move |task| {
+            concurrent_map.upsert(key(task), || 0, |count| *count += 1);
+        })
+        .await;
+
+        // 并发写入不丢失更新
+        let mut total = 0;
+        map.for_each(|_, count| total += count);
+        assert_eq!(total, 2 * TOTAL_OPS as u64);
+        assert_no_degradation("ShardedMap::upsert", single, concurrent);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    #[ignore = "吞吐对比依赖机器负载"]
+    async fn store_update_throughput_does_not_degrade_with_concurrent_tasks() {
+        let pid = |task: usize| 4_110_000 + task as u32;
+        let mut initial = HashMap::new();
+        for task in 0..CONCURRENT_TASKS {
+            let process = ProcessStore::for_test(pid(task), "bench");
+            initial.insert(pid(task), process.latest_heartbeat_time);
+            PROCESS_MAP_STORE.register(process);
+        }
+        let heartbeat = move |task: usize| {
+            PROCESS_MAP_STORE.update_with(&pid(task), |process| {
+                process.latest_heartbeat_time += 1;
+            });
+        };
+
+        // 单任务轮流更新所有进程，与并发时的写入分布一致
+        let single = measure(1, {
+            let next = std::sync::atomic::AtomicUsize::new(0);
+            move |_| {
+                let task = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                heartbeat(task % CONCURRENT_TASKS);
+            }
+        })
+        .await;
+        let concurrent = measure(CONCURRENT_TASKS, heartbeat).await;
+
+        let updates: Vec<u64> = (0..CONCURRENT_TASKS)
+            .map(|task| {
+                let process = PROCESS_MAP_STORE
+                    .remove(&pid(task), RemoveReason::OverBudget)
+                    .unwrap();
+                process.latest_heartbeat_time - initial[&pid(task)]
+            })
+            .collect();
+        assert!(updates
+            .iter()
+            .all(|updates| *updates as usize == 2 * TOTAL_OPS / CONCURRENT_TASKS));
+        assert_no_degradation("Store::update_with", single, concurrent);
+    }
+}
@@ -1,25 +1,36 @@
-use std::{
-    collections::HashMap,
-    sync::{LazyLock, Mutex},
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    LazyLock,
 };
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use strum::{Display, EnumString};
 
-#[derive(Debug, Deserialize, Serialize, EnumString, Display)]
+use crate::{
+    data_processor::{
+        events::{publish, RemoveReason, StoreEvent},
+        sharded::ShardedMap,
+    },
+    helper::{
+        constants::PID_MAX_LIMIT,
+        histogram::{valid_bucket_index, Histogram, MAX_REPORTED_COUNT},
+        metrics::remove_process_data,
+        procfs::ProcessMetadata,
+        time::{now_millis, now_secs},
+    },
+    log_print,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, EnumString, Display)]
 #[serde(rename_all = "snake_case")]
 #[strum(serialize_all = "snake_case")]
 pub enum MetricType {
     Cpu,
     Memory,
-}
-
-#[derive(Debug, Deserialize, Serialize, EnumString, Display)]
-#[serde(rename_all = "snake_case")]
-#[strum(serialize_all = "snake_case")]
-pub enum ActionType {
-    GetCpuProfile,
-    GetMemoryProfile,
+    EventLoop,
+    Gc,
+    Handles,
+    Histogram,
 }
 
 #[derive(Debug, Deserialize, Serialize, EnumString, Display)]
@@ -30,42 +41,344 @@ pub enum CommandType {
     Metric,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
 pub struct CpuMetricData {
-    load: f32,
-    user_load: f32,
+    pub load: f32,
+    // Node 侧 CPUCollector 上报的字段名为 useLoad
+    #[serde(alias = "useLoad", alias = "userLoad")]
+    pub user_load: f32,
+}
+
+/// 事件循环延迟与利用率，延迟来自 monitorEventLoopDelay，单位毫秒
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EventLoopMetricData {
+    #[serde(alias = "lagP50")]
+    pub lag_p50: f64,
+    #[serde(alias = "lagP99")]
+    pub lag_p99: f64,
+    #[serde(alias = "lagMax")]
+    pub lag_max: f64,
+    // performance.eventLoopUtilization() 的 utilization，范围 0~1
+    pub utilization: f64,
+}
+
+impl EventLoopMetricData {
+    pub fn validate(&self) -> Result<(), String> {
+        let lags = [self.lag_p50, self.lag_p99, self.lag_max];
+        if lags.iter().any(|lag| !lag.is_finite() || *lag < 0.0) {
+            return Err("事件循环延迟必须为非负数".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.utilization) {
+            return Err(format!(
+                "utilization 必须在 0~1 之间，实际为 {}",
+                self.utilization
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// 统计区间内各类 GC 的次数，对应 PerformanceObserver 中 gc entry 的 kind
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GcCounts {
+    pub scavenge: u64,
+    #[serde(
+        alias = "markSweep",
+        alias = "mark_sweep_compact",
+        alias = "markSweepCompact"
+    )]
+    pub mark_sweep: u64,
+    pub incremental: u64,
+    #[serde(
+        alias = "weakCallbacks",
+        alias = "process_weak_callbacks",
+        alias = "processWeakCallbacks"
+    )]
+    pub weak_callbacks: u64,
+}
+
+/// 统计区间内的 GC 信息，时间单位毫秒
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GcMetricData {
+    pub counts: GcCounts,
+    #[serde(alias = "totalPause")]
+    pub total_pause: f64,
+    #[serde(alias = "maxPause")]
+    pub max_pause: f64,
+    // 统计区间长度，未上报时使用与上一次 GC 指标的时间间隔
+    #[serde(default)]
+    pub duration: Option<f64>,
+}
+
+impl GcMetricData {
+    pub fn validate(&self) -> Result<(), String> {
+        let times = [
+            self.total_pause,
+            self.max_pause,
+            self.duration.unwrap_or_default(),
+        ];
+        if times.iter().any(|time| !time.is_finite() || *time < 0.0) {
+            return Err("GC 时间必须为非负数".to_string());
+        }
+        if self.max_pause > self.total_pause {
+            return Err(format!(
+                "max_pause {} 不能大于 total_pause {}",
+                self.max_pause, self.total_pause
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// 按创建栈聚合的活跃句柄数量
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HandleGroup {
+    // 类型，例如 setTimeout、setInterval、TCPWrap、FSReqCallback
+    #[serde(alias = "name")]
+    pub kind: String,
+    pub stack: String,
+    pub count: u64,
+}
+
+/// 活跃的定时器、句柄与请求，groups 为按创建栈聚合的数量
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HandlesMetricData {
+    #[serde(default)]
+    pub timers: u64,
+    #[serde(default)]
+    pub handles: u64,
+    #[serde(default)]
+    pub requests: u64,
+    #[serde(default, alias = "stacks")]
+    pub groups: Vec<HandleGroup>,
+}
+
+/// 统计区间内的分布数据，例如请求耗时，可以上报原始值或按 agent 的 log-linear 规则分好的桶
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HistogramMetricData {
+    // 直方图名称，例如 http.server.duration
+    pub name: String,
+    #[serde(default)]
+    pub values: Vec<f64>,
+    // [桶序号, 数量]，桶序号规则见 helper::histogram::bucket_index
+    #[serde(default)]
+    pub buckets: Vec<(i32, u64)>,
+    // 值为 0 的数量，只在上报 buckets 时使用
+    #[serde(default, alias = "zeroCount")]
+    pub zero_count: u64,
+}
+
+impl HistogramMetricData {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty()
+            || !self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        {
+            return Err(format!(
+                "直方图名称 {:?} 无效，只能包含字母、数字、.、_、-",
+                self.name
+            ));
+        }
+        if self
+            .values
+            .iter()
+            .any(|value| !value.is_finite() || *value < 0.0)
+        {
+            return Err("直方图的值必须为非负数".to_string());
+        }
+        if let Some((index, _)) = self
+            .buckets
+            .iter()
+            .find(|(index, _)| !valid_bucket_index(*index))
+        {
+            return Err(format!("直方图桶序号 {} 超出范围", index));
+        }
+        if self.zero_count > MAX_REPORTED_COUNT
+            || self
+                .buckets
+                .iter()
+                .any(|(_, count)| *count > MAX_REPORTED_COUNT)
+        {
+            return Err(format!("直方图单个桶的数量不能超过 {}", MAX_REPORTED_COUNT));
+        }
+        Ok(())
+    }
+
+    pub fn to_histogram(&self) -> Histogram {
+        let mut histogram = Histogram::default();
+        for value in &self.values {
+            histogram.record(*value);
+        }
+        for (index, count) in &self.buckets {
+            histogram.record_bucket(*index, *count);
+        }
+        histogram.record_zeros(self.zero_count);
+        histogram
+    }
 }
 
+/// process.memoryUsage() 的返回值，单位 byte
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryUsage {
+    pub rss: u64,
+    pub heap_total: u64,
+    pub heap_used: u64,
+    pub external: u64,
+    #[serde(default)]
+    pub array_buffers: u64,
+}
+
+/// v8.getHeapStatistics() 的返回值，单位 byte
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HeapInfo {
+    pub total_heap_size: u64,
+    pub total_heap_size_executable: u64,
+    pub total_physical_size: u64,
+    pub total_available_size: u64,
+    pub used_heap_size: u64,
+    pub heap_size_limit: u64,
+    pub malloced_memory: u64,
+    pub peak_malloced_memory: u64,
+    pub does_zap_garbage: u8,
+    pub number_of_native_contexts: u64,
+    pub number_of_detached_contexts: u64,
+    // 以下字段在较新的 Node 版本才会返回
+    #[serde(default)]
+    pub total_global_handles_size: u64,
+    #[serde(default)]
+    pub used_global_handles_size: u64,
+    #[serde(default)]
+    pub external_memory: u64,
+}
+
+/// v8.getHeapSpaceStatistics() 中单个堆空间的统计，单位 byte
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HeapSpaceInfo {
+    pub space_name: String,
+    pub space_size: u64,
+    pub space_used_size: u64,
+    pub space_available_size: u64,
+    pub physical_space_size: u64,
+}
+
+/// 与 Node 侧 MemoryCollector 上报的 MemoryData 保持一致
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct MemoryMetricData {
-    memory: u64,
+    pub heap_info: HeapInfo,
+    pub heap_spaces: Vec<HeapSpaceInfo>,
+    #[serde(alias = "memoryUsage")]
+    pub memory: MemoryUsage,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
 pub struct GetCpuProfileActionData {
-    duration: i32,
-    interval: i32,
+    // 采集时长 millisecond
+    pub duration: u64,
+    // 采样间隔 microsecond
+    pub interval: u64,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
 pub struct GetMemoryProfileActionData {
-    duration: i32,
+    // 采集时长 millisecond
+    pub duration: u64,
+}
+
+/// 指标数据，metric_type 决定 data 的结构
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "metric_type", content = "data", rename_all = "snake_case")]
+pub enum MetricPayload {
+    Cpu(CpuMetricData),
+    Memory(MemoryMetricData),
+    EventLoop(EventLoopMetricData),
+    Gc(GcMetricData),
+    Handles(HandlesMetricData),
+    Histogram(HistogramMetricData),
 }
 
-// todo 约束 T 和 DataType 的关系
+impl MetricPayload {
+    pub fn metric_type(&self) -> MetricType {
+        match self {
+            MetricPayload::Cpu(_) => MetricType::Cpu,
+            MetricPayload::Memory(_) => MetricType::Memory,
+            MetricPayload::EventLoop(_) => MetricType::EventLoop,
+            MetricPayload::Gc(_) => MetricType::Gc,
+            MetricPayload::Handles(_) => MetricType::Handles,
+            MetricPayload::Histogram(_) => MetricType::Histogram,
+        }
+    }
+}
+
+/// 操作数据，action_type 决定 data 的结构
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "action_type", content = "data", rename_all = "snake_case")]
+pub enum ActionPayload {
+    GetCpuProfile(GetCpuProfileActionData),
+    GetMemoryProfile(GetMemoryProfileActionData),
+}
+
+/// 校验 pid 的取值范围，超出范围时返回带具体数值的错误信息
+pub fn deserialize_pid<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = i64::deserialize(deserializer)?;
+    if value < 1 || value > PID_MAX_LIMIT as i64 {
+        return Err(serde::de::Error::custom(format!(
+            "{} 超出 pid 有效范围 1..={}",
+            value, PID_MAX_LIMIT
+        )));
+    }
+    Ok(value as u32)
+}
+
+/// thread_id 是 Node 的 worker threadId，主线程为 0，只要求在 u32 范围内
+pub fn deserialize_optional_thread_id<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(value) = Option::<i64>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    u32::try_from(value).map(Some).map_err(|_| {
+        serde::de::Error::custom(format!(
+            "{} 超出 thread_id 有效范围 0..={}",
+            value,
+            u32::MAX
+        ))
+    })
+}
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ProcessMetricInfo {
-    pub process_id: u16,
-    pub thread_id: Option<u16>,
-    pub metric_type: MetricType,
+    #[serde(deserialize_with = "deserialize_pid")]
+    pub process_id: u32,
+    #[serde(default, deserialize_with = "deserialize_optional_thread_id")]
+    pub thread_id: Option<u32>,
     pub command_type: CommandType,
-    // data: Box<T>,
+    // 采集时间 timestamp millisecond，未上报时使用 agent 接收时间，与 agent 时间相差超过 5 分钟时拒绝
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+    // metric_type 与 data 两个字段
+    #[serde(flatten)]
+    pub payload: MetricPayload,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ProcessActionInfo {
-    pub process_id: u16,
-    pub thread_id: Option<u16>,
-    pub action_type: ActionType,
+    #[serde(deserialize_with = "deserialize_pid")]
+    pub process_id: u32,
+    #[serde(default, deserialize_with = "deserialize_optional_thread_id")]
+    pub thread_id: Option<u32>,
     pub command_type: CommandType,
-    // data: Box<T>,
+    // action_type 与 data 两个字段
+    #[serde(flatten)]
+    pub payload: ActionPayload,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -73,23 +386,123 @@ pub struct BaseCommandData {
     pub command_type: CommandType,
 }
 
-#[derive(Debug, Clone)]
+/// 进程在 agent 中的存活状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ProcessStatus {
+    // 心跳正常
+    Active,
+    // 心跳超时，等待恢复或被清理
+    Stale,
+    // 通过 /proc 检测到进程已退出或 pid 已被复用
+    Exited,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessStore {
-    pub uds_port: u16,
+    pub process_id: u32,
+    // agent 分配的会话 id，每次注册都会重新生成
+    pub session_id: String,
+    // 进程监听的 UDS 路径
+    pub uds_path: String,
+    pub app_name: String,
+    // 进程启动时间 timestamp second
+    pub start_time: u64,
+    // 内核记录的进程启动时间（/proc/<pid>/stat 第 22 个字段），用于识别 pid 复用
+    pub proc_start_time: Option<u64>,
+    pub node_version: String,
+    // timestamp second
+    pub register_time: u64,
     // timestamp second
     pub latest_heartbeat_time: u64,
+    pub status: ProcessStatus,
+    // 最近一次状态变化的时间 timestamp second
+    pub status_update_time: u64,
+    // 检测到进程退出的时间 timestamp second
+    pub exited_time: Option<u64>,
+    // 注册时从 /proc 采集的元信息，非 Linux 平台为空
+    #[serde(default)]
+    pub metadata: Option<ProcessMetadata>,
+    // 超出内存预算时优先清理优先级低的进程，数值越大越重要
+    #[serde(default)]
+    pub priority: i32,
 }
 
-#[derive(Debug)]
+impl ProcessStore {
+    /// 占用的内存估算，单位 byte
+    pub fn estimated_bytes(&self) -> usize {
+        let strings = [
+            &self.session_id,
+            &self.uds_path,
+            &self.app_name,
+            &self.node_version,
+        ];
+        let metadata = self.metadata.as_ref().map_or(0, |metadata| {
+            let optional = [
+                &metadata.cwd,
+                &metadata.exe,
+                &metadata.node_options,
+                &metadata.node_env,
+            ];
+            std::mem::size_of::<ProcessMetadata>()
+                + metadata
+                    .cmdline
+                    .iter()
+                    .map(|arg| std::mem::size_of::<String>() + arg.len())
+                    .sum::<usize>()
+                + optional
+                    .iter()
+                    .map(|value| value.as_ref().map_or(0, |value| value.len()))
+                    .sum::<usize>()
+        });
+        std::mem::size_of::<Self>()
+            + strings.iter().map(|value| value.len()).sum::<usize>()
+            + metadata
+    }
+}
+
+#[cfg(test)]
+impl ProcessStore {
+    /// 测试用的进程记录，心跳时间为当前时间
+    pub fn for_test(process_id: u32, app_name: &str) -> Self {
+        let now = now_secs();
+        Self {
+            process_id,
+            session_id: generate_session_id(),
+            uds_path: format!("/tmp/{}.sock", process_id),
+            app_name: app_name.to_string(),
+            start_time: now,
+            proc_start_time: None,
+            node_version: "v20.0.0".to_string(),
+            register_time: now,
+            latest_heartbeat_time: now,
+            status: ProcessStatus::Active,
+            status_update_time: now,
+            exited_time: None,
+            metadata: None,
+            priority: 0,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct PartialProcessStore {
-    pub uds_port: Option<u16>,
+    pub uds_path: Option<String>,
     // timestamp second
     pub latest_heartbeat_time: Option<u64>,
 }
 
-// 将数据存储改为静态变量，只对数据加锁
-pub static PROCESS_DATA: LazyLock<Mutex<HashMap<u16, ProcessStore>>> =
-    LazyLock::new(|| Mutex::new(HashMap::new()));
+// 将数据存储改为静态变量，按 pid 分片加锁，不同进程的读写互不阻塞
+pub static PROCESS_DATA: LazyLock<ShardedMap<ProcessStore>> = LazyLock::new(ShardedMap::new);
+
+static SESSION_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// 生成 agent 内唯一的会话 id：毫秒时间戳 + 自增序号
+pub fn generate_session_id() -> String {
+    let seq = SESSION_COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{:x}-{:04x}", now_millis(), seq)
+}
 
 #[derive(Debug)]
 pub struct Store;
@@ -99,37 +512,200 @@ impl Store {
         Self
     }
 
-    pub fn get_data(&self) -> std::sync::MutexGuard<HashMap<u16, ProcessStore>> {
-        PROCESS_DATA.lock().unwrap()
+    pub fn set(&self, key: &u32, value: ProcessStore) {
+        PROCESS_DATA.insert(*key, value);
     }
 
-    pub fn set(&self, key: &u16, value: ProcessStore) -> () {
-        PROCESS_DATA.lock().unwrap().insert(*key, value);
+    /// 注册进程并发布 ProcessRegistered 事件
+    pub fn register(&self, value: ProcessStore) {
+        let event = StoreEvent::ProcessRegistered {
+            process_id: value.process_id,
+            session_id: value.session_id.clone(),
+            app_name: value.app_name.clone(),
+            time: value.register_time,
+        };
+        let pid = value.process_id;
+        self.set(&pid, value);
+        publish(event);
     }
 
-    pub fn update(&self, key: &u16, value: PartialProcessStore) -> () {
-        let mut data = PROCESS_DATA.lock().unwrap();
-        let old = data.get(key);
+    /// 更新已注册进程的部分字段，进程未注册时返回 false
+    pub fn update(&self, key: &u32, value: PartialProcessStore) -> bool {
+        let heartbeat_time = value.latest_heartbeat_time;
+        // 如果没有旧值，则不设置新值，由调用方提示先注册
+        let updated = self
+            .update_with(key, |old| {
+                if let Some(uds_path) = value.uds_path {
+                    old.uds_path = uds_path;
+                }
+                if let Some(latest_heartbeat_time) = value.latest_heartbeat_time {
+                    old.latest_heartbeat_time = latest_heartbeat_time;
+                    // 收到心跳后从 stale 恢复为 active
+                    if old.status == ProcessStatus::Stale {
+                        log_print!("💚 进程 {} 恢复心跳: stale -> active", key);
+                        old.status = ProcessStatus::Active;
+                        old.status_update_time = latest_heartbeat_time;
+                    }
+                }
+            })
+            .is_some();
 
-        if let Some(old) = old {
-            let new = ProcessStore {
-                uds_port: value.uds_port.unwrap_or(old.uds_port),
-                latest_heartbeat_time: value
-                    .latest_heartbeat_time
-                    .unwrap_or(old.latest_heartbeat_time),
-            };
-            data.insert(*key, new);
+        if let (true, Some(time)) = (updated, heartbeat_time) {
+            publish(StoreEvent::HeartbeatReceived {
+                process_id: *key,
+                time,
+            });
         }
-        // 如果没有旧值，则不设置新值
+        updated
+    }
+
+    /// 原地更新单个进程，只锁该进程所在的分片
+    pub fn update_with<R>(&self, key: &u32, f: impl FnOnce(&mut ProcessStore) -> R) -> Option<R> {
+        PROCESS_DATA.update(*key, f)
+    }
+
+    /// 按分片过滤进程，返回 false 的进程会被移除
+    pub fn retain(&self, f: impl FnMut(&u32, &mut ProcessStore) -> bool) {
+        PROCESS_DATA.retain(f);
     }
 
-    pub fn get(&self, pid: &u16) -> Option<ProcessStore> {
-        PROCESS_DATA.lock().unwrap().get(pid).cloned()
+    pub fn contains(&self, pid: &u32) -> bool {
+        PROCESS_DATA.contains_key(*pid)
     }
 
-    pub fn remove(&self, pid: &u16) -> Option<ProcessStore> {
-        PROCESS_DATA.lock().unwrap().remove(pid)
+    /// 返回所有进程的快照
+    pub fn list(&self) -> Vec<ProcessStore> {
+        PROCESS_DATA.values()
+    }
+
+    pub fn get(&self, pid: &u32) -> Option<ProcessStore> {
+        PROCESS_DATA.get(*pid)
+    }
+
+    /// 移除进程并同步清理其指标数据
+    pub fn remove(&self, pid: &u32, reason: RemoveReason) -> Option<ProcessStore> {
+        let removed = PROCESS_DATA.remove(*pid);
+        if removed.is_some() {
+            remove_process_data(*pid);
+            publish(StoreEvent::ProcessRemoved {
+                process_id: *pid,
+                reason,
+                time: now_secs(),
+            });
+        }
+        removed
     }
 }
 
-pub static PROCESS_MAP_STORE: LazyLock<Store> = LazyLock::new(|| Store::new());
+pub static PROCESS_MAP_STORE: LazyLock<Store> = LazyLock::new(Store::new);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::metrics::METRICS_STORE;
+
+    fn metric(process_id: &str, thread_id: &str) -> serde_json::Result<ProcessMetricInfo> {
+        serde_json::from_str(&format!(
+            r#"{{"process_id":{},"thread_id":{},"command_type":"metric","metric_type":"cpu","data":{{"load":1,"useLoad":1}}}}"#,
+            process_id, thread_id
+        ))
+    }
+
+    #[test]
+    fn pid_above_u16_is_accepted() {
+        let info = metric("4194304", "70000").unwrap();
+        assert_eq!(info.process_id, PID_MAX_LIMIT);
+        assert_eq!(info.thread_id, Some(70000));
+    }
+
+    #[test]
+    fn pid_out_of_range_is_rejected() {
+        for pid in ["0", "-1", "4194305"] {
+            let error = metric(pid, "null").unwrap_err().to_string();
+            assert!(error.contains("超出 pid 有效范围"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn main_thread_id_zero_is_accepted() {
+        // Node 主线程的 threadId 为 0
+        assert_eq!(metric("1", "0").unwrap().thread_id, Some(0));
+        assert_eq!(metric("1", "4294967295").unwrap().thread_id, Some(u32::MAX));
+        for thread_id in ["-1", "4294967296"] {
+            let error = metric("1", thread_id).unwrap_err().to_string();
+            assert!(error.contains("超出 thread_id 有效范围"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn metric_payload_is_typed_by_metric_type() {
+        let info = metric("1", "null").unwrap();
+        assert_eq!(info.payload.metric_type(), MetricType::Cpu);
+        let MetricPayload::Cpu(data) = info.payload else {
+            panic!("expected cpu payload");
+        };
+        assert_eq!(data.load, 1.0);
+        assert_eq!(data.user_load, 1.0);
+    }
+
+    #[test]
+    fn metric_payload_rejects_unknown_type_and_mismatched_data() {
+        let unknown = r#"{"process_id":1,"command_type":"metric","metric_type":"gpu","data":{}}"#;
+        assert!(serde_json::from_str::<ProcessMetricInfo>(unknown).is_err());
+        // metric_type 为 cpu 但 data 是内存数据
+        let mismatched =
+            r#"{"process_id":1,"command_type":"metric","metric_type":"cpu","data":{"rss":1}}"#;
+        assert!(serde_json::from_str::<ProcessMetricInfo>(mismatched).is_err());
+    }
+
+    #[test]
+    fn action_payload_is_typed_by_action_type() {
+        let action = r#"{"process_id":1,"command_type":"action","action_type":"get_cpu_profile","data":{"duration":1000,"interval":10}}"#;
+        let info: ProcessActionInfo = serde_json::from_str(action).unwrap();
+        let ActionPayload::GetCpuProfile(data) = info.payload else {
+            panic!("expected cpu profile action");
+        };
+        assert_eq!(data.duration, 1000);
+
+        let negative = action.replace("1000", "-1");
+        assert!(serde_json::from_str::<ProcessActionInfo>(&negative).is_err());
+    }
+
+    #[test]
+    fn remove_clears_process_data() {
+        let pid = 4_100_901;
+        PROCESS_MAP_STORE.register(ProcessStore::for_test(pid, "demo"));
+        METRICS_STORE.record(pid, None, now_millis(), &[("cpu.load", 1.0)]);
+
+        let removed = PROCESS_MAP_STORE.remove(&pid, RemoveReason::OverBudget);
+        assert_eq!(removed.unwrap().process_id, pid);
+        assert!(!PROCESS_MAP_STORE.contains(&pid));
+        assert!(METRICS_STORE.series_keys(pid).is_empty());
+        assert!(PROCESS_MAP_STORE
+            .remove(&pid, RemoveReason::OverBudget)
+            .is_none());
+    }
+
+    #[test]
+    fn histogram_counts_above_cap_are_rejected() {
+        let histogram = |buckets: Vec<(i32, u64)>, zero_count: u64| HistogramMetricData {
+            name: "http.server.duration".to_string(),
+            values: vec![1.5],
+            buckets,
+            zero_count,
+        };
+        assert!(histogram(vec![(0, MAX_REPORTED_COUNT)], MAX_REPORTED_COUNT)
+            .validate()
+            .is_ok());
+        let error = histogram(vec![(0, MAX_REPORTED_COUNT + 1)], 0)
+            .validate()
+            .unwrap_err();
+        assert!(error.contains("数量"), "{}", error);
+        assert!(histogram(Vec::new(), u64::MAX).validate().is_err());
+    }
+
+    #[test]
+    fn thread_id_is_optional() {
+        assert_eq!(metric("1", "null").unwrap().thread_id, None);
+    }
+}
@@ -1,13 +1,29 @@
 use std::sync::Arc;
 
 use crate::{
-    data_processor::store::{
-        ActionType, BaseCommandData, CommandType, MetricType, ProcessActionInfo, ProcessMetricInfo,
+    data_processor::{
+        anomaly::observe_anomalies,
+        events::{publish, StoreEvent},
+        histograms::record_histogram,
+        leaks::record_handles,
+        store::{
+            ActionPayload, BaseCommandData, CommandType, GcMetricData, MetricPayload,
+            ProcessActionInfo, ProcessMetricInfo, PROCESS_MAP_STORE,
+        },
+    },
+    helper::{
+        config::AppConfig,
+        metrics::{SeriesKey, METRICS_STORE},
+        stats::AGENT_STATS,
+        time::{now_millis, now_secs},
     },
     ipc::tcp::DataCallback,
     {error_print, log_print},
 };
 
+// 客户端上报的采集时间与 agent 时间允许的最大偏差，单位毫秒
+const MAX_CLOCK_SKEW: u64 = 300_000;
+
 /// 监听 UDS 的数据通信回调
 pub fn data_subscription() -> DataCallback {
     Arc::new(|data: &str| {
@@ -18,7 +34,10 @@ pub fn data_subscription() -> DataCallback {
         log_print!("📥 接收到数据: {}", data);
 
         match process_data(data) {
-            Ok(_) => log_print!("✅ 数据处理成功"),
+            Ok(_) => {
+                AGENT_STATS.message_ingested();
+                log_print!("✅ 数据处理成功")
+            }
             Err(e) => error_print!("❌ 数据处理失败: {}", e),
         }
     })
@@ -26,53 +45,476 @@ pub fn data_subscription() -> DataCallback {
 
 fn process_data(data: &str) -> Result<(), String> {
     // 首先尝试解析基础命令数据
-    let base_data: BaseCommandData =
-        serde_json::from_str(data).map_err(|e| format!("解析基础命令数据失败: {}", e))?;
+    let base_data: BaseCommandData = serde_json::from_str(data).map_err(|e| {
+        AGENT_STATS.parse_failed();
+        format!("解析基础命令数据失败: {}", e)
+    })?;
 
     match base_data.command_type {
         CommandType::Metric => {
-            let metric_info: ProcessMetricInfo =
-                serde_json::from_str(data).map_err(|e| format!("解析指标数据失败: {}", e))?;
+            let metric_info: ProcessMetricInfo = serde_json::from_str(data).map_err(|e| {
+                AGENT_STATS.parse_failed();
+                format!(
+                    "解析指标数据失败: {}",
+                    describe_payload_error(data, "metric_type", e)
+                )
+            })?;
             handle_metric(metric_info)
         }
         CommandType::Action => {
-            let action_info: ProcessActionInfo =
-                serde_json::from_str(data).map_err(|e| format!("解析操作数据失败: {}", e))?;
+            let action_info: ProcessActionInfo = serde_json::from_str(data).map_err(|e| {
+                AGENT_STATS.parse_failed();
+                format!(
+                    "解析操作数据失败: {}",
+                    describe_payload_error(data, "action_type", e)
+                )
+            })?;
             handle_action(action_info)
         }
     }
 }
 
+/// 在解析错误中带上声明的类型，便于定位 data 与类型不匹配的问题
+fn describe_payload_error(data: &str, tag: &str, error: serde_json::Error) -> String {
+    let declared = serde_json::from_str::<serde_json::Value>(data)
+        .ok()
+        .and_then(|value| value.get(tag)?.as_str().map(|tag| tag.to_string()));
+    match declared {
+        Some(declared) => format!("{}={}: {}", tag, declared, error),
+        None => error.to_string(),
+    }
+}
+
+/// 只处理已注册进程上报的数据，未注册的进程需要先调用 /register
+fn ensure_registered(process_id: u32) -> Result<(), String> {
+    if PROCESS_MAP_STORE.contains(&process_id) {
+        Ok(())
+    } else {
+        Err(format!(
+            "进程 {} 未注册，请先调用 POST /register 注册",
+            process_id
+        ))
+    }
+}
+
+/// 校验客户端上报的采集时间，未上报时使用 agent 接收时间
+///
+/// 偏差过大的时间戳（例如误用秒作为单位）会让聚合层越过真实时间，之后正常的样本都会被当作过期数据丢弃
+fn resolve_timestamp(timestamp: Option<u64>, now: u64) -> Result<u64, String> {
+    let Some(timestamp) = timestamp else {
+        return Ok(now);
+    };
+    if timestamp.abs_diff(now) > MAX_CLOCK_SKEW {
+        return Err(format!(
+            "timestamp {} 与 agent 时间 {} 相差超过 {}s，timestamp 应为毫秒时间戳",
+            timestamp,
+            now,
+            MAX_CLOCK_SKEW / 1000
+        ));
+    }
+    Ok(timestamp)
+}
+
 fn handle_metric(metric_info: ProcessMetricInfo) -> Result<(), String> {
     log_print!("📊 处理指标数据: {:?}", metric_info);
+    ensure_registered(metric_info.process_id)?;
 
-    match metric_info.metric_type {
-        MetricType::Cpu => {
+    let pid = metric_info.process_id;
+    let thread_id = metric_info.thread_id;
+    let timestamp = resolve_timestamp(metric_info.timestamp, now_millis())?;
+    let metric_type = metric_info.payload.metric_type();
+    match metric_info.payload {
+        MetricPayload::Cpu(data) => {
             log_print!("🖥️  处理 CPU 指标");
-            // TODO: 实现 CPU 指标处理逻辑
-            Ok(())
+            let values = [
+                ("cpu.load", data.load as f64),
+                ("cpu.user_load", data.user_load as f64),
+            ];
+            METRICS_STORE.record(pid, thread_id, timestamp, &values);
+            observe_anomalies(pid, thread_id, timestamp, &values);
         }
-        MetricType::Memory => {
+        MetricPayload::Memory(data) => {
             log_print!("🧠 处理内存指标");
-            // TODO: 实现内存指标处理逻辑
-            Ok(())
+            let memory = &data.memory;
+            let heap = &data.heap_info;
+            let values = [
+                ("memory.rss", memory.rss as f64),
+                ("memory.heap_total", memory.heap_total as f64),
+                ("memory.heap_used", memory.heap_used as f64),
+                ("memory.external", memory.external as f64),
+                ("memory.array_buffers", memory.array_buffers as f64),
+                ("memory.heap_size_limit", heap.heap_size_limit as f64),
+                (
+                    "memory.total_physical_size",
+                    heap.total_physical_size as f64,
+                ),
+                ("memory.malloced_memory", heap.malloced_memory as f64),
+                (
+                    "memory.detached_contexts",
+                    heap.number_of_detached_contexts as f64,
+                ),
+            ];
+            METRICS_STORE.record(pid, thread_id, timestamp, &values);
+            observe_anomalies(pid, thread_id, timestamp, &values);
+            // 每个堆空间单独一条序列，例如 memory.space.old_space.used
+            let spaces: Vec<(String, f64)> = data
+                .heap_spaces
+                .iter()
+                .map(|space| {
+                    (
+                        format!("memory.space.{}.used", space.space_name),
+                        space.space_used_size as f64,
+                    )
+                })
+                .collect();
+            let spaces: Vec<(&str, f64)> = spaces
+                .iter()
+                .map(|(name, value)| (name.as_str(), *value))
+                .collect();
+            METRICS_STORE.record(pid, thread_id, timestamp, &spaces);
+        }
+        MetricPayload::EventLoop(data) => {
+            log_print!("🔁 处理事件循环指标");
+            data.validate()?;
+            let blocked = check_event_loop_blocked(pid, thread_id, data.lag_max);
+            let values = [
+                ("event_loop.lag_p50", data.lag_p50),
+                ("event_loop.lag_p99", data.lag_p99),
+                ("event_loop.lag_max", data.lag_max),
+                ("event_loop.utilization", data.utilization),
+                ("event_loop.blocked", if blocked { 1.0 } else { 0.0 }),
+            ];
+            METRICS_STORE.record(pid, thread_id, timestamp, &values);
+            observe_anomalies(pid, thread_id, timestamp, &values);
+        }
+        MetricPayload::Gc(data) => {
+            log_print!("🧹 处理 GC 指标");
+            data.validate()?;
+            let counts = &data.counts;
+            let mut values = vec![
+                ("gc.count.scavenge", counts.scavenge as f64),
+                ("gc.count.mark_sweep", counts.mark_sweep as f64),
+                ("gc.count.incremental", counts.incremental as f64),
+                ("gc.count.weak_callbacks", counts.weak_callbacks as f64),
+                ("gc.total_pause", data.total_pause),
+                ("gc.max_pause", data.max_pause),
+            ];
+            if let Some(ratio) = gc_time_ratio(pid, thread_id, timestamp, &data) {
+                values.push(("gc.time_ratio", ratio));
+            }
+            METRICS_STORE.record(pid, thread_id, timestamp, &values);
         }
+        MetricPayload::Handles(data) => {
+            log_print!("⏱️ 处理活跃句柄指标");
+            METRICS_STORE.record(
+                pid,
+                thread_id,
+                timestamp,
+                &[
+                    ("handles.timers", data.timers as f64),
+                    ("handles.handles", data.handles as f64),
+                    ("handles.requests", data.requests as f64),
+                ],
+            );
+            // 创建栈数量不固定，不写入时序存储，单独跟踪增长趋势
+            let window = AppConfig::global().leak_window * 1000;
+            record_handles(pid, timestamp, &data.groups, window);
+        }
+        MetricPayload::Histogram(data) => {
+            log_print!("📶 处理直方图指标: {}", data.name);
+            data.validate()?;
+            record_histogram(pid, thread_id, timestamp, &data.name, &data.to_histogram());
+        }
+    }
+
+    publish(StoreEvent::MetricIngested {
+        process_id: pid,
+        thread_id,
+        metric_type,
+        time: now_secs(),
+    });
+    Ok(())
+}
+
+/// GC 暂停时间占统计区间的比例，区间长度未知时返回 None
+fn gc_time_ratio(
+    pid: u32,
+    thread_id: Option<u32>,
+    timestamp: u64,
+    data: &GcMetricData,
+) -> Option<f64> {
+    let duration = data.duration.or_else(|| {
+        let key = SeriesKey {
+            name: "gc.total_pause".to_string(),
+            thread_id,
+        };
+        let previous = METRICS_STORE.latest_sample(pid, &key)?;
+        Some(timestamp.checked_sub(previous.timestamp)? as f64)
+    })?;
+    if duration <= 0.0 {
+        return None;
+    }
+    // 上报时间与暂停时间的误差可能使比例略大于 1
+    Some((data.total_pause / duration).min(1.0))
+}
+
+/// 根据最大延迟判断事件循环是否被阻塞，与上一次的状态不同时发布事件
+fn check_event_loop_blocked(pid: u32, thread_id: Option<u32>, lag_max: f64) -> bool {
+    let threshold = AppConfig::global().event_loop_blocked_threshold;
+    let blocked = lag_max > threshold;
+    let key = SeriesKey {
+        name: "event_loop.blocked".to_string(),
+        thread_id,
+    };
+    let was_blocked = METRICS_STORE
+        .latest_sample(pid, &key)
+        .is_some_and(|sample| sample.value > 0.0);
+
+    if blocked && !was_blocked {
+        log_print!(
+            "⚠️ 进程 {} 事件循环阻塞，最大延迟 {}ms 超过阈值 {}ms",
+            pid,
+            lag_max,
+            threshold
+        );
+        publish(StoreEvent::EventLoopBlocked {
+            process_id: pid,
+            thread_id,
+            lag_max,
+            threshold,
+            time: now_secs(),
+        });
+    } else if !blocked && was_blocked {
+        log_print!("✅ 进程 {} 事件循环恢复，最大延迟 {}ms", pid, lag_max);
+        publish(StoreEvent::EventLoopRecovered {
+            process_id: pid,
+            thread_id,
+            lag_max,
+            time: now_secs(),
+        });
     }
+    blocked
 }
 
 fn handle_action(action_info: ProcessActionInfo) -> Result<(), String> {
     log_print!("⚡ 处理操作数据: {:?}", action_info);
+    ensure_registered(action_info.process_id)?;
 
-    match action_info.action_type {
-        ActionType::GetCpuProfile => {
-            log_print!("🖥️  获取 CPU Profile");
+    match action_info.payload {
+        ActionPayload::GetCpuProfile(data) => {
+            log_print!(
+                "🖥️  获取 CPU Profile: {}ms, 采样间隔 {}us",
+                data.duration,
+                data.interval
+            );
             // TODO: 实现 CPU Profile 获取逻辑
             Ok(())
         }
-        ActionType::GetMemoryProfile => {
-            log_print!("🧠 获取 Memory Profile");
+        ActionPayload::GetMemoryProfile(data) => {
+            log_print!("🧠 获取 Memory Profile: {}ms", data.duration);
             // TODO: 实现 Memory Profile 获取逻辑
             Ok(())
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_from_unregistered_process_are_rejected() {
+        let data = r#"{"process_id":4100201,"command_type":"metric","metric_type":"cpu","data":{"load":1,"useLoad":1}}"#;
+        let error = process_data(data).unwrap_err();
+        assert!(error.contains("未注册"), "{}", error);
+    }
+
+    #[test]
+    fn payload_errors_name_the_declared_type() {
+        let data = r#"{"process_id":1,"command_type":"metric","metric_type":"cpu","data":{"heapInfo":{}}}"#;
+        let error = process_data(data).unwrap_err();
+        assert!(error.contains("metric_type=cpu"), "{}", error);
+        assert!(error.contains("load"), "{}", error);
+    }
+
+    #[test]
+    fn timestamp_within_skew_is_accepted() {
+        let now = 1_800_000_000_000;
+        assert_eq!(resolve_timestamp(None, now), Ok(now));
+        assert_eq!(resolve_timestamp(Some(now - 60_000), now), Ok(now - 60_000));
+        assert_eq!(
+            resolve_timestamp(Some(now + MAX_CLOCK_SKEW), now),
+            Ok(now + MAX_CLOCK_SKEW)
+        );
+    }
+
+    #[test]
+    fn timestamp_outside_skew_is_rejected() {
+        let now = 1_800_000_000_000;
+        // 误用秒作为单位
+        let error = resolve_timestamp(Some(now / 1000), now).unwrap_err();
+        assert!(error.contains("毫秒"), "{}", error);
+        assert!(resolve_timestamp(Some(now + MAX_CLOCK_SKEW + 1), now).is_err());
+    }
+
+    #[test]
+    fn cpu_and_memory_metrics_are_recorded() {
+        let pid = 4_100_921;
+        PROCESS_MAP_STORE.register(crate::data_processor::store::ProcessStore::for_test(
+            pid, "demo",
+        ));
+        let cpu = format!(
+            r#"{{"process_id":{},"command_type":"metric","metric_type":"cpu","data":{{"load":1.5,"useLoad":0.5}}}}"#,
+            pid
+        );
+        process_data(&cpu).unwrap();
+        let memory = format!(
+            r#"{{"process_id":{},"command_type":"metric","metric_type":"memory","data":{{"heapInfo":{{"total_heap_size":1,"total_heap_size_executable":1,"total_physical_size":1,"total_available_size":1,"used_heap_size":1,"heap_size_limit":1,"malloced_memory":1,"peak_malloced_memory":1,"does_zap_garbage":0,"number_of_native_contexts":1,"number_of_detached_contexts":0,"total_global_handles_size":1,"used_global_handles_size":1,"external_memory":1}},"heapSpaces":[],"memory":{{"rss":4096,"heapTotal":1,"heapUsed":2048,"external":1,"arrayBuffers":1}}}}}}"#,
+            pid
+        );
+        process_data(&memory).unwrap();
+
+        let latest: std::collections::HashMap<String, f64> = METRICS_STORE
+            .latest(pid)
+            .into_iter()
+            .map(|(key, sample)| (key.name, sample.value))
+            .collect();
+        assert_eq!(latest["cpu.load"], 1.5);
+        assert_eq!(latest["cpu.user_load"], 0.5);
+        assert_eq!(latest["memory.rss"], 4096.0);
+        assert_eq!(latest["memory.heap_used"], 2048.0);
+    }
+
+    #[test]
+    fn far_future_metric_is_not_recorded() {
+        let pid = 4_100_911;
+        PROCESS_MAP_STORE.register(crate::data_processor::store::ProcessStore::for_test(
+            pid, "demo",
+        ));
+        let data = format!(
+            r#"{{"process_id":{},"command_type":"metric","timestamp":{},"metric_type":"cpu","data":{{"load":1,"useLoad":1}}}}"#,
+            pid,
+            now_millis() + 86_400_000
+        );
+        assert!(process_data(&data).is_err());
+        assert!(METRICS_STORE.series_keys(pid).is_empty());
+    }
+
+    fn register(pid: u32) {
+        PROCESS_MAP_STORE.register(crate::data_processor::store::ProcessStore::for_test(
+            pid, "demo",
+        ));
+    }
+
+    fn metric(pid: u32, metric_type: &str, data: &str) -> String {
+        format!(
+            r#"{{"process_id":{},"command_type":"metric","metric_type":"{}","data":{}}}"#,
+            pid, metric_type, data
+        )
+    }
+
+    fn latest_value(pid: u32, name: &str) -> Option<f64> {
+        let key = SeriesKey {
+            name: name.to_string(),
+            thread_id: None,
+        };
+        METRICS_STORE
+            .latest_sample(pid, &key)
+            .map(|sample| sample.value)
+    }
+
+    #[test]
+    fn event_loop_blocked_signal_follows_lag_threshold() {
+        let pid = 4_101_701;
+        register(pid);
+        let mut receiver = crate::data_processor::events::subscribe();
+        let event_loop = |lag_max: f64| {
+            metric(
+                pid,
+                "event_loop",
+                &format!(
+                    r#"{{"lagP50":1,"lagP99":2,"lagMax":{},"utilization":0.5}}"#,
+                    lag_max
+                ),
+            )
+        };
+
+        // 默认阈值为 100ms
+        process_data(&event_loop(50.0)).unwrap();
+        assert_eq!(latest_value(pid, "event_loop.blocked"), Some(0.0));
+        process_data(&event_loop(250.0)).unwrap();
+        assert_eq!(latest_value(pid, "event_loop.blocked"), Some(1.0));
+        // 持续阻塞时不重复发布事件
+        process_data(&event_loop(300.0)).unwrap();
+        process_data(&event_loop(20.0)).unwrap();
+        assert_eq!(latest_value(pid, "event_loop.blocked"), Some(0.0));
+        assert_eq!(latest_value(pid, "event_loop.utilization"), Some(0.5));
+
+        let mut transitions = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            match event {
+                StoreEvent::EventLoopBlocked { process_id, .. } if process_id == pid => {
+                    transitions.push("blocked")
+                }
+                StoreEvent::EventLoopRecovered { process_id, .. } if process_id == pid => {
+                    transitions.push("recovered")
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(transitions, ["blocked", "recovered"]);
+    }
+
+    #[test]
+    fn invalid_event_loop_metric_is_rejected() {
+        let pid = 4_101_702;
+        register(pid);
+        let data = metric(
+            pid,
+            "event_loop",
+            r#"{"lagP50":1,"lagP99":2,"lagMax":3,"utilization":1.5}"#,
+        );
+        let error = process_data(&data).unwrap_err();
+        assert!(error.contains("utilization"), "{}", error);
+        let data = metric(
+            pid,
+            "event_loop",
+            r#"{"lagP50":-1,"lagP99":2,"lagMax":3,"utilization":0.5}"#,
+        );
+        assert!(process_data(&data).is_err());
+        assert!(METRICS_STORE.series_keys(pid).is_empty());
+    }
+
+    #[test]
+    fn gc_time_ratio_uses_reported_or_elapsed_duration() {
+        let pid = 4_101_801;
+        register(pid);
+        let gc = |timestamp: u64, total_pause: f64, duration: &str| {
+            format!(
+                r#"{{"process_id":{},"command_type":"metric","metric_type":"gc","timestamp":{},"data":{{"counts":{{"scavenge":3,"markSweepCompact":1}},"totalPause":{},"maxPause":1{}}}}}"#,
+                pid, timestamp, total_pause, duration
+            )
+        };
+        let now = now_millis();
+
+        // 第一次上报没有区间长度，不计算比例
+        process_data(&gc(now - 20_000, 100.0, "")).unwrap();
+        assert_eq!(latest_value(pid, "gc.time_ratio"), None);
+        assert_eq!(latest_value(pid, "gc.count.mark_sweep"), Some(1.0));
+        // 使用与上一次 GC 指标的时间间隔
+        process_data(&gc(now - 10_000, 500.0, "")).unwrap();
+        assert_eq!(latest_value(pid, "gc.time_ratio"), Some(0.05));
+        // 优先使用上报的区间长度，比例最大为 1
+        process_data(&gc(now, 200.0, r#","duration":100"#)).unwrap();
+        assert_eq!(latest_value(pid, "gc.time_ratio"), Some(1.0));
+    }
+
+    #[test]
+    fn gc_max_pause_above_total_is_rejected() {
+        let pid = 4_101_802;
+        register(pid);
+        let data = metric(pid, "gc", r#"{"counts":{},"totalPause":1,"maxPause":5}"#);
+        let error = process_data(&data).unwrap_err();
+        assert!(error.contains("max_pause"), "{}", error);
+    }
+}
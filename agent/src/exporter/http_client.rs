@@ -0,0 +1,231 @@
+use std::{io, time::Duration};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+// 错误响应中保留的 body 长度
+const MAX_ERROR_BODY_LEN: usize = 512;
+
+/// 解析后的 http 地址，只支持明文 http
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpUrl {
+    // IPv6 地址不带方括号
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+impl HttpUrl {
+    pub fn parse(url: &str) -> Result<Self, String> {
+        if url.starts_with("https://") {
+            return Err(format!("{} 使用 https，目前只支持明文 http://", url));
+        }
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("{} 不是 http:// 地址", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        let invalid_port = || format!("{} 端口无效", url);
+        // IPv6 地址写在方括号中，例如 [::1]:4318
+        let (host, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+            let (host, rest) = bracketed
+                .split_once(']')
+                .ok_or_else(|| format!("{} 的 IPv6 地址缺少 ]", url))?;
+            let port = match rest {
+                "" => 80,
+                _ => rest
+                    .strip_prefix(':')
+                    .and_then(|port| port.parse().ok())
+                    .ok_or_else(invalid_port)?,
+            };
+            (host, port)
+        } else {
+            match authority.rsplit_once(':') {
+                Some((host, port)) => (host, port.parse().map_err(|_| invalid_port())?),
+                None => (authority, 80),
+            }
+        };
+        if host.is_empty() {
+            return Err(format!("{} 缺少主机名", url));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// Host 请求头的值，IPv6 地址加上方括号
+    fn authority(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// http 响应的状态码与 body 前缀
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+impl HttpResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// 发送一次 POST 请求，每次请求使用独立连接，headers 为额外的请求头
+pub async fn post(
+    url: &HttpUrl,
+    content_type: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+    request_timeout: Duration,
+) -> io::Result<HttpResponse> {
+    timeout(request_timeout, send_post(url, content_type, headers, body))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "请求超时"))?
+}
+
+async fn send_post(
+    url: &HttpUrl,
+    content_type: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+) -> io::Result<HttpResponse> {
+    let mut stream = TcpStream::connect((url.host.as_str(), url.port)).await?;
+    let mut head = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        url.path,
+        url.authority(),
+        content_type,
+        body.len()
+    );
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.flush().await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    parse_response(&response)
+}
+
+fn parse_response(response: &[u8]) -> io::Result<HttpResponse> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "无效的 http 响应");
+    let text = String::from_utf8_lossy(response);
+    // 状态行形如 HTTP/1.1 200 OK
+    let status = text
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(invalid)?;
+    let body = text
+        .split_once("\r\n\r\n")
+        .map(|(_, body)| body.chars().take(MAX_ERROR_BODY_LEN).collect())
+        .unwrap_or_default();
+    Ok(HttpResponse { status, body })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn parse_splits_host_port_and_path() {
+        assert_eq!(
+            HttpUrl::parse("http://collector:4318/v1/metrics").unwrap(),
+            HttpUrl {
+                host: "collector".to_string(),
+                port: 4318,
+                path: "/v1/metrics".to_string(),
+            }
+        );
+        let url = HttpUrl::parse("http://localhost").unwrap();
+        assert_eq!((url.port, url.path.as_str()), (80, "/"));
+        assert!(HttpUrl::parse("http://:4318/").is_err());
+        assert!(HttpUrl::parse("http://host:port/").is_err());
+        assert!(HttpUrl::parse("collector:4318").is_err());
+    }
+
+    #[test]
+    fn parse_handles_bracketed_ipv6_hosts() {
+        let url = HttpUrl::parse("http://[::1]:4318/v1/metrics").unwrap();
+        assert_eq!(url.host, "::1");
+        assert_eq!(url.port, 4318);
+        assert_eq!(url.authority(), "[::1]:4318");
+        assert_eq!(HttpUrl::parse("http://[fe80::1]").unwrap().port, 80);
+        assert!(HttpUrl::parse("http://[::1:4318/").is_err());
+        assert!(HttpUrl::parse("http://[::1]4318/").is_err());
+    }
+
+    #[test]
+    fn parse_rejects_https_explicitly() {
+        let err = HttpUrl::parse("https://collector:4318/v1/metrics").unwrap_err();
+        assert!(err.contains("https"), "{}", err);
+    }
+
+    #[test]
+    fn parse_response_reads_status_and_body() {
+        let response = parse_response(b"HTTP/1.1 400 Bad Request\r\nX: y\r\n\r\nbad data").unwrap();
+        assert_eq!(response.status, 400);
+        assert_eq!(response.body, "bad data");
+        assert!(!response.is_success());
+        assert!(parse_response(b"garbage").is_err());
+    }
+
+    #[tokio::test]
+    async fn post_sends_headers_and_body() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            // 读到完整的 body 后再响应
+            while !request.ends_with(b"payload") {
+                let read = stream.read(&mut buf).await.unwrap();
+                assert!(read > 0);
+                request.extend_from_slice(&buf[..read]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let url = HttpUrl::parse(&format!("http://127.0.0.1:{}/v1/metrics", port)).unwrap();
+        let response = post(
+            &url,
+            "application/x-protobuf",
+            &[("Authorization", "Bearer token")],
+            b"payload",
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        assert!(response.is_success());
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /v1/metrics HTTP/1.1\r\n"));
+        assert!(request.contains(&format!("Host: 127.0.0.1:{}\r\n", port)));
+        assert!(request.contains("Content-Type: application/x-protobuf\r\n"));
+        assert!(request.contains("Content-Length: 7\r\n"));
+        assert!(request.contains("Authorization: Bearer token\r\n"));
+    }
+}
@@ -0,0 +1,382 @@
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+use tokio::{fs::OpenOptions, io::AsyncWriteExt, net::UdpSocket};
+
+use crate::helper::{config::InfluxConfig, metrics::split_series_name};
+
+use super::{
+    http_client::{post, HttpUrl},
+    ExportBatch, ExportError, ExportFuture, Exporter,
+};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// measurement 前缀
+const MEASUREMENT_PREFIX: &str = "mitojs";
+// 单个 UDP 包的大小上限，避免超过常见 MTU 被分片
+const MAX_DATAGRAM_SIZE: usize = 1400;
+
+/// line protocol 的写入目标，通过地址的 scheme 区分
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InfluxTarget {
+    // http://host:8086/api/v2/write?org=..&bucket=..
+    Http(HttpUrl),
+    // udp://host:8089
+    Udp(String),
+    // file:///var/log/mitojs/metrics.lp
+    File(PathBuf),
+}
+
+impl InfluxTarget {
+    pub fn parse(target: &str) -> Result<Self, String> {
+        if let Some(addr) = target.strip_prefix("udp://") {
+            if addr.rsplit_once(':').is_none() {
+                return Err(format!("{} 缺少端口", target));
+            }
+            return Ok(InfluxTarget::Udp(addr.to_string()));
+        }
+        if let Some(path) = target.strip_prefix("file://") {
+            if path.is_empty() {
+                return Err(format!("{} 缺少文件路径", target));
+            }
+            return Ok(InfluxTarget::File(PathBuf::from(path)));
+        }
+        if target.starts_with("http://") {
+            return HttpUrl::parse(target).map(InfluxTarget::Http);
+        }
+        Err(format!(
+            "{} 不支持，仅支持 http://、udp://、file://",
+            target
+        ))
+    }
+}
+
+/// tag key、tag value 与 field key 需要转义逗号、等号、空格和反斜杠
+///
+/// 换行无法转义，会把一行截断，替换为转义后的空格
+fn escape_key(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ',' | '=' | ' ' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' | '\r' => escaped.push_str("\\ "),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// measurement 只需要转义逗号和空格，等号和反斜杠按原样写入
+///
+/// 换行的处理与 escape_key 相同
+fn escape_measurement(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ',' | ' ' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' | '\r' => escaped.push_str("\\ "),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 批次转换为 line protocol，同一时刻、同一组 tag 的样本合并为一行
+fn encode_lines(batch: &ExportBatch) -> Vec<String> {
+    // (measurement, tags, timestamp) -> fields
+    let mut lines: BTreeMap<(String, String, u64), Vec<String>> = BTreeMap::new();
+    for process in &batch.processes {
+        let pid = process.process.process_id.to_string();
+        for (key, sample) in &process.points {
+            if !sample.value.is_finite() {
+                continue;
+            }
+            let (name, extra_label) = split_series_name(&key.name);
+            // cpu.load 写为 mitojs_cpu 的 load 字段
+            let (group, field) = name.split_once('.').unwrap_or(("agent", name.as_str()));
+
+            let mut tags = vec![
+                ("pid", pid.clone()),
+                ("app", process.process.app_name.clone()),
+                ("host", batch.host.clone()),
+            ];
+            if let Some(thread_id) = key.thread_id {
+                tags.push(("thread_id", thread_id.to_string()));
+            }
+            if let Some((label, value)) = extra_label {
+                tags.push((label, value.to_string()));
+            }
+            // tag 为空字符串时 line protocol 无法解析，直接省略
+            let tags: String = tags
+                .iter()
+                .filter(|(_, value)| !value.is_empty())
+                .map(|(key, value)| format!(",{}={}", key, escape_key(value)))
+                .collect();
+
+            lines
+                .entry((
+                    escape_measurement(&format!("{}_{}", MEASUREMENT_PREFIX, group)),
+                    tags,
+                    sample.timestamp,
+                ))
+                .or_default()
+                .push(format!(
+                    "{}={}",
+                    escape_key(&field.replace('.', "_")),
+                    sample.value
+                ));
+        }
+    }
+
+    lines
+        .into_iter()
+        .map(|((measurement, tags, timestamp), fields)| {
+            // 时间精度为纳秒
+            format!(
+                "{}{} {} {}",
+                measurement,
+                tags,
+                fields.join(","),
+                timestamp.saturating_mul(1_000_000)
+            )
+        })
+        .collect()
+}
+
+/// 按行拼接为不超过 MAX_DATAGRAM_SIZE 的 UDP 包，单行超长时单独发送
+fn pack_datagrams(lines: &[String]) -> Vec<String> {
+    let mut datagrams = Vec::new();
+    let mut current = String::new();
+    for line in lines {
+        if !current.is_empty() && current.len() + line.len() + 1 > MAX_DATAGRAM_SIZE {
+            datagrams.push(std::mem::take(&mut current));
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+    if !current.is_empty() {
+        datagrams.push(current);
+    }
+    datagrams
+}
+
+/// 以 InfluxDB line protocol 写入 http、udp 或本地文件
+pub struct InfluxExporter {
+    target: InfluxTarget,
+    // InfluxDB 2.x 的 API token
+    token: Option<String>,
+}
+
+impl InfluxExporter {
+    pub fn new(config: &InfluxConfig) -> Result<Self, String> {
+        Ok(Self {
+            target: InfluxTarget::parse(&config.target)?,
+            token: config.token.clone(),
+        })
+    }
+
+    async fn send(&self, batch: &ExportBatch) -> Result<(), ExportError> {
+        let lines = encode_lines(batch);
+        if lines.is_empty() {
+            return Ok(());
+        }
+        match &self.target {
+            InfluxTarget::Http(url) => self.write_http(url, &lines).await,
+            InfluxTarget::Udp(addr) => write_udp(addr, &lines).await,
+            InfluxTarget::File(path) => write_file(path, &lines).await,
+        }
+    }
+
+    async fn write_http(&self, url: &HttpUrl, lines: &[String]) -> Result<(), ExportError> {
+        let authorization = self.token.as_ref().map(|token| format!("Token {}", token));
+        let headers: Vec<(&str, &str)> = authorization
+            .iter()
+            .map(|value| ("Authorization", value.as_str()))
+            .collect();
+        let body = lines.join("\n");
+        let response = post(
+            url,
+            "text/plain; charset=utf-8",
+            &headers,
+            body.as_bytes(),
+            REQUEST_TIMEOUT,
+        )
+        .await
+        .map_err(|e| ExportError::Retryable(format!("请求 InfluxDB 失败: {}", e)))?;
+        if response.is_success() {
+            return Ok(());
+        }
+        let msg = format!("InfluxDB 返回 {}: {}", response.status, response.body);
+        if response.status == 429 || response.status >= 500 {
+            Err(ExportError::Retryable(msg))
+        } else {
+            Err(ExportError::Fatal(msg))
+        }
+    }
+}
+
+async fn write_udp(addr: &str, lines: &[String]) -> Result<(), ExportError> {
+    let retryable =
+        |e: std::io::Error| ExportError::Retryable(format!("UDP 发送到 {} 失败: {}", addr, e));
+    let socket = UdpSocket::bind("0.0.0.0:0").await.map_err(retryable)?;
+    socket.connect(addr).await.map_err(retryable)?;
+    for datagram in pack_datagrams(lines) {
+        socket.send(datagram.as_bytes()).await.map_err(retryable)?;
+    }
+    Ok(())
+}
+
+async fn write_file(path: &PathBuf, lines: &[String]) -> Result<(), ExportError> {
+    let retryable =
+        |e: std::io::Error| ExportError::Retryable(format!("写入 {} 失败: {}", path.display(), e));
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .map_err(retryable)?;
+    let mut content = lines.join("\n");
+    content.push('\n');
+    file.write_all(content.as_bytes())
+        .await
+        .map_err(retryable)?;
+    file.flush().await.map_err(retryable)
+}
+
+impl Exporter for InfluxExporter {
+    fn name(&self) -> &'static str {
+        "influx"
+    }
+
+    fn export<'a>(&'a self, batch: &'a ExportBatch) -> ExportFuture<'a> {
+        Box::pin(self.send(batch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data_processor::store::ProcessStore,
+        exporter::ProcessPoints,
+        helper::metrics::{Sample, SeriesKey},
+    };
+
+    fn point(
+        name: &str,
+        thread_id: Option<u32>,
+        timestamp: u64,
+        value: f64,
+    ) -> (SeriesKey, Sample) {
+        (
+            SeriesKey {
+                name: name.to_string(),
+                thread_id,
+            },
+            Sample { timestamp, value },
+        )
+    }
+
+    fn batch(app_name: &str, points: Vec<(SeriesKey, Sample)>) -> ExportBatch {
+        ExportBatch {
+            host: "test-host".to_string(),
+            processes: vec![ProcessPoints {
+                process: ProcessStore::for_test(4_101_401, app_name),
+                points,
+            }],
+        }
+    }
+
+    #[test]
+    fn escape_key_escapes_separators_and_strips_newlines() {
+        assert_eq!(escape_key("a,b=c d"), "a\\,b\\=c\\ d");
+        assert_eq!(escape_key("C:\\app"), "C:\\\\app");
+        assert_eq!(escape_key("line1\nline2\r"), "line1\\ line2\\ ");
+    }
+
+    #[test]
+    fn escape_measurement_keeps_equals_and_backslash() {
+        assert_eq!(escape_measurement("a,b=c d\\e"), "a\\,b=c\\ d\\e");
+        assert_eq!(escape_measurement("line1\nline2"), "line1\\ line2");
+        let lines = encode_lines(&batch("demo", vec![point("a=b.load", None, 1, 1.0)]));
+        assert!(lines[0].starts_with("mitojs_a=b,"), "{}", lines[0]);
+    }
+
+    #[test]
+    fn encode_lines_merges_fields_with_same_tags_and_time() {
+        let lines = encode_lines(&batch(
+            "demo",
+            vec![
+                point("cpu.load", None, 1_000, 0.5),
+                point("cpu.user_load", None, 1_000, 0.25),
+                point("cpu.load", Some(7), 1_000, 0.1),
+                point("uptime", None, 2_000, 3.0),
+                point("cpu.load", None, 3_000, f64::NAN),
+            ],
+        ));
+        assert_eq!(
+            lines,
+            [
+                "mitojs_agent,pid=4101401,app=demo,host=test-host uptime=3 2000000000",
+                "mitojs_cpu,pid=4101401,app=demo,host=test-host load=0.5,user_load=0.25 1000000000",
+                "mitojs_cpu,pid=4101401,app=demo,host=test-host,thread_id=7 load=0.1 1000000000",
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_lines_keeps_each_line_on_one_line() {
+        let lines = encode_lines(&batch("my app\nx", vec![point("cpu.load", None, 1, 1.0)]));
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains(",app=my\\ app\\ x,"), "{}", lines[0]);
+        // tag 为空字符串时省略
+        let lines = encode_lines(&batch("", vec![point("cpu.load", None, 1, 1.0)]));
+        assert!(!lines[0].contains("app="));
+    }
+
+    #[test]
+    fn pack_datagrams_splits_at_size_limit() {
+        let line = "x".repeat(MAX_DATAGRAM_SIZE / 2);
+        let datagrams = pack_datagrams(&[line.clone(), line.clone(), "y".repeat(2000)]);
+        assert_eq!(datagrams.len(), 3);
+        assert_eq!(datagrams[0], format!("{}\n", line));
+        assert_eq!(datagrams[2].len(), 2001);
+    }
+
+    #[test]
+    fn target_parse_accepts_supported_schemes() {
+        assert_eq!(
+            InfluxTarget::parse("udp://localhost:8089").unwrap(),
+            InfluxTarget::Udp("localhost:8089".to_string())
+        );
+        assert_eq!(
+            InfluxTarget::parse("file:///tmp/metrics.lp").unwrap(),
+            InfluxTarget::File(PathBuf::from("/tmp/metrics.lp"))
+        );
+        assert!(matches!(
+            InfluxTarget::parse("http://localhost:8086/api/v2/write").unwrap(),
+            InfluxTarget::Http(_)
+        ));
+        assert!(InfluxTarget::parse("udp://localhost").is_err());
+        assert!(InfluxTarget::parse("tcp://localhost:8089").is_err());
+    }
+
+    #[tokio::test]
+    async fn file_target_appends_lines() {
+        let path = std::env::temp_dir().join(format!("mito-influx-{}.lp", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let lines = vec!["a value=1 1".to_string(), "b value=2 2".to_string()];
+        write_file(&path, &lines).await.unwrap();
+        write_file(&path, &lines[..1]).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "a value=1 1\nb value=2 2\na value=1 1\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,507 @@
+pub mod http_client;
+pub mod influx;
+pub mod otlp;
+
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use tokio::{
+    task,
+    time::{interval, sleep, MissedTickBehavior},
+};
+
+use crate::{
+    data_processor::store::{ProcessStore, PROCESS_MAP_STORE},
+    error_print,
+    helper::{
+        config::AppConfig,
+        metrics::{RawCursor, RawSince, Sample, SeriesKey, METRICS_STORE, RAW_CURSORS},
+        procfs::read_hostname,
+    },
+    log_print,
+};
+
+// 单个批次失败后的最大重试次数
+const MAX_RETRIES: u32 = 3;
+// 第一次重试前的等待时间，之后每次翻倍
+#[cfg(not(test))]
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+// 测试中不等待真实的退避时间
+#[cfg(test)]
+const INITIAL_BACKOFF: Duration = Duration::from_millis(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+// 每轮待导出数据最多占用内存预算的比例（1/N），超出的进程留到下一轮
+const EXPORT_BUDGET_SHARE: usize = 10;
+// 内存超出预算时每次把单轮导出的上限减半，最多缩小到 1/2^MAX_SHRINK_SHIFT
+const MAX_SHRINK_SHIFT: u32 = 4;
+
+// 所有导出器当前待导出数据占用的内存，单位 byte
+static BUFFERED_BYTES: AtomicUsize = AtomicUsize::new(0);
+// 单轮导出上限缩小的次数
+static SHRINK_SHIFT: AtomicU32 = AtomicU32::new(0);
+// 每次要求释放待导出数据时递增，正在导出的任务发现变化后停止发送剩余批次
+static SHRINK_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// 待导出数据占用的内存，单位 byte
+pub fn buffered_bytes() -> usize {
+    BUFFERED_BYTES.load(Ordering::Relaxed)
+}
+
+/// 内存超出预算时调用，正在导出的任务发送完当前批次后释放剩余数据，之后单轮导出的上限减半
+///
+/// 被释放的批次没有推进游标，下一轮重新导出
+pub fn shrink_buffers() {
+    SHRINK_GENERATION.fetch_add(1, Ordering::Relaxed);
+    let _ = SHRINK_SHIFT.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |shift| {
+        (shift < MAX_SHRINK_SHIFT).then_some(shift + 1)
+    });
+}
+
+/// 内存回到预算以内时逐步恢复单轮导出的上限
+pub fn relax_buffers() {
+    let _ = SHRINK_SHIFT.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |shift| {
+        shift.checked_sub(1)
+    });
+}
+
+/// 单轮待导出数据的内存上限，单位 byte
+fn export_limit(budget: usize) -> usize {
+    (budget / EXPORT_BUDGET_SHARE) >> SHRINK_SHIFT.load(Ordering::Relaxed)
+}
+
+pub type ExportFuture<'a> = Pin<Box<dyn Future<Output = Result<(), ExportError>> + Send + 'a>>;
+
+/// 导出失败的原因，只有 Retryable 会重试
+#[derive(Debug)]
+pub enum ExportError {
+    // 网络错误、5xx、429 等临时错误
+    Retryable(String),
+    // 数据被拒绝等重试也无法成功的错误
+    Fatal(String),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Retryable(msg) => write!(f, "{}", msg),
+            ExportError::Fatal(msg) => write!(f, "{} (不再重试)", msg),
+        }
+    }
+}
+
+/// 单个进程待导出的样本
+#[derive(Debug, Clone)]
+pub struct ProcessPoints {
+    pub process: ProcessStore,
+    pub points: Vec<(SeriesKey, Sample)>,
+}
+
+impl ProcessPoints {
+    /// 占用的内存估算，单位 byte
+    pub fn estimated_bytes(&self) -> usize {
+        self.process.estimated_bytes()
+            + self.points.capacity() * std::mem::size_of::<(SeriesKey, Sample)>()
+            + self
+                .points
+                .iter()
+                .map(|(key, _)| key.name.len())
+                .sum::<usize>()
+    }
+}
+
+/// 从内存中读出的单个进程样本，拆分批次前保留每个样本的写入序号
+struct RawProcess {
+    process: ProcessStore,
+    // (写入序号, 序列, 样本)，按写入序号排序
+    points: Vec<(u64, SeriesKey, Sample)>,
+}
+
+impl RawProcess {
+    /// 占用的内存估算，单位 byte
+    fn estimated_bytes(&self) -> usize {
+        self.process.estimated_bytes()
+            + self.points.capacity() * std::mem::size_of::<(u64, SeriesKey, Sample)>()
+            + self
+                .points
+                .iter()
+                .map(|(_, key, _)| key.name.len())
+                .sum::<usize>()
+    }
+}
+
+/// 一次请求导出的数据
+#[derive(Debug, Clone)]
+pub struct ExportBatch {
+    pub host: String,
+    pub processes: Vec<ProcessPoints>,
+}
+
+impl ExportBatch {
+    pub fn len(&self) -> usize {
+        self.processes
+            .iter()
+            .map(|process| process.points.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 占用的内存估算，单位 byte
+    pub fn estimated_bytes(&self) -> usize {
+        self.processes
+            .iter()
+            .map(ProcessPoints::estimated_bytes)
+            .sum()
+    }
+}
+
+/// 指标导出目标
+pub trait Exporter: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn export<'a>(&'a self, batch: &'a ExportBatch) -> ExportFuture<'a>;
+}
+
+/// 拆分后的批次，以及批次发送成功后可以推进游标的进程
+struct PendingBatch {
+    batch: ExportBatch,
+    // (pid, 批次内该进程最大的写入序号)，进程的样本拆到多个批次时每个批次各自记录
+    completed: Vec<(u32, u64)>,
+}
+
+/// 按 batch_size 拆分批次，同一进程的样本可能分到多个批次
+fn split_batches(host: &str, processes: Vec<RawProcess>, batch_size: usize) -> Vec<PendingBatch> {
+    let mut batches = Vec::new();
+    let mut current = PendingBatch {
+        batch: ExportBatch {
+            host: host.to_string(),
+            processes: Vec::new(),
+        },
+        completed: Vec::new(),
+    };
+
+    for RawProcess { process, points } in processes {
+        let pid = process.process_id;
+        let mut points = points.into_iter().peekable();
+        while points.peek().is_some() {
+            let room = batch_size - current.batch.len();
+            let mut seq = 0;
+            let chunk: Vec<_> = points
+                .by_ref()
+                .take(room)
+                .map(|(point_seq, key, sample)| {
+                    seq = point_seq;
+                    (key, sample)
+                })
+                .collect();
+            current.batch.processes.push(ProcessPoints {
+                process: process.clone(),
+                points: chunk,
+            });
+            // 样本按写入序号排序，发送成功后游标推进到该批次的最后一个样本
+            current.completed.push((pid, seq));
+            if current.batch.len() >= batch_size {
+                let full = std::mem::replace(
+                    &mut current,
+                    PendingBatch {
+                        batch: ExportBatch {
+                            host: host.to_string(),
+                            processes: Vec::new(),
+                        },
+                        completed: Vec::new(),
+                    },
+                );
+                batches.push(full);
+            }
+        }
+    }
+    if !current.batch.is_empty() {
+        batches.push(current);
+    }
+    batches
+}
+
+async fn export_with_retry(
+    exporter: &dyn Exporter,
+    batch: &ExportBatch,
+) -> Result<(), ExportError> {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        match exporter.export(batch).await {
+            Err(ExportError::Retryable(msg)) if attempt < MAX_RETRIES => {
+                attempt += 1;
+                error_print!(
+                    "{} 导出失败，{}s 后第 {} 次重试: {}",
+                    exporter.name(),
+                    backoff.as_secs(),
+                    attempt,
+                    msg
+                );
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// 增量导出新写入的原始样本
+///
+/// 每个进程记录已导出的写入序号，每个批次发送成功后推进到批次内的最大序号，可重试的失败只在下一轮重新导出未成功的批次
+///
+/// 游标登记在 RAW_CURSORS 中，按内存预算清理时不会移除尚未导出的样本
+struct ExportRunner {
+    exporter: Box<dyn Exporter>,
+    batch_size: usize,
+    host: String,
+    cursors: RawCursor,
+}
+
+impl ExportRunner {
+    fn collect(&mut self) -> Vec<RawProcess> {
+        let mut processes = PROCESS_MAP_STORE.list();
+        // 已移除的进程不再需要游标
+        self.cursors
+            .retain(|pid| processes.iter().any(|process| process.process_id == pid));
+        // 游标按进程推进，超出预算时整个进程留到下一轮，至少导出一个进程
+        // 游标最旧的进程优先，避免一直轮不到排在后面的进程
+        processes.sort_by_key(|process| self.cursors.get(process.process_id));
+        let limit = export_limit(AppConfig::global().memory_budget);
+        let mut collected = Vec::new();
+        let mut bytes = 0;
+        for process in processes {
+            if bytes >= limit && !collected.is_empty() {
+                break;
+            }
+            let after = self.cursors.get(process.process_id);
+            let RawSince { points, .. } = METRICS_STORE.raw_since(process.process_id, after);
+            if points.is_empty() {
+                continue;
+            }
+            let raw = RawProcess { process, points };
+            bytes += raw.estimated_bytes();
+            collected.push(raw);
+        }
+        collected
+    }
+
+    async fn run_once(&mut self) {
+        let processes = self.collect();
+        self.export(processes).await;
+    }
+
+    /// 依次发送批次，每个批次结束后释放其占用的内存
+    async fn export(&mut self, processes: Vec<RawProcess>) {
+        let generation = SHRINK_GENERATION.load(Ordering::Relaxed);
+        let batches = split_batches(&self.host, processes, self.batch_size);
+        let bytes: usize = batches
+            .iter()
+            .map(|pending| pending.batch.estimated_bytes())
+            .sum();
+        BUFFERED_BYTES.fetch_add(bytes, Ordering::Relaxed);
+        let mut released = 0;
+
+        let mut batches = batches.into_iter();
+        for pending in batches.by_ref() {
+            let bytes = pending.batch.estimated_bytes();
+            let result = export_with_retry(self.exporter.as_ref(), &pending.batch).await;
+            BUFFERED_BYTES.fetch_sub(bytes, Ordering::Relaxed);
+            released += bytes;
+            match result {
+                Ok(()) => {}
+                Err(e @ ExportError::Fatal(_)) => {
+                    // 重试也无法成功，丢弃该批次并推进游标，避免每轮都重复发送
+                    error_print!(
+                        "{} 导出被拒绝，丢弃 {} 个数据点: {}",
+                        self.exporter.name(),
+                        pending.batch.len(),
+                        e
+                    );
+                }
+                Err(e @ ExportError::Retryable(_)) => {
+                    error_print!(
+                        "{} 导出 {} 个数据点失败: {}",
+                        self.exporter.name(),
+                        pending.batch.len(),
+                        e
+                    );
+                    // 剩余批次留到下一轮，避免持续请求不可用的目标
+                    break;
+                }
+            }
+            for (pid, seq) in pending.completed {
+                self.cursors.advance(pid, seq);
+            }
+            if SHRINK_GENERATION.load(Ordering::Relaxed) != generation {
+                log_print!(
+                    "🧹 内存超出预算，{} 剩余的待导出数据留到下一轮",
+                    self.exporter.name()
+                );
+                break;
+            }
+        }
+        drop(batches);
+        BUFFERED_BYTES.fetch_sub(bytes - released, Ordering::Relaxed);
+    }
+}
+
+/// 在后台定时导出指标
+pub fn start_exporter(exporter: Box<dyn Exporter>, interval_secs: u64, batch_size: usize) {
+    log_print!(
+        "📤 启动 {} 指标导出，每 {}s",
+        exporter.name(),
+        interval_secs
+    );
+    let mut runner = ExportRunner {
+        exporter,
+        batch_size,
+        host: read_hostname(),
+        cursors: RAW_CURSORS.register(),
+    };
+    task::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(interval_secs));
+        // 重试耗时超过间隔时不补发积压的 tick
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            runner.run_once().await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // 释放待导出数据的信号是全局的，发送批次的测试依次执行
+    static EXPORT_TEST_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    /// 生成 count 个样本，写入序号从 first_seq 开始递增
+    fn points(pid: u32, count: usize, first_seq: u64) -> RawProcess {
+        let key = SeriesKey {
+            name: "cpu.load".to_string(),
+            thread_id: None,
+        };
+        RawProcess {
+            process: ProcessStore::for_test(pid, "demo"),
+            points: (0..count)
+                .map(|index| {
+                    (
+                        first_seq + index as u64,
+                        key.clone(),
+                        Sample {
+                            timestamp: index as u64,
+                            value: 1.0,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// 按顺序返回预设结果，shrink_on 指定的批次发送时要求释放待导出数据
+    struct ScriptedExporter {
+        results: Mutex<Vec<Result<(), ExportError>>>,
+        shrink_on: Option<usize>,
+        sent: AtomicUsize,
+    }
+
+    impl Exporter for ScriptedExporter {
+        fn name(&self) -> &'static str {
+            "scripted"
+        }
+
+        fn export<'a>(&'a self, _batch: &'a ExportBatch) -> ExportFuture<'a> {
+            let sent = self.sent.fetch_add(1, Ordering::Relaxed);
+            if self.shrink_on == Some(sent) {
+                shrink_buffers();
+            }
+            let result = self.results.lock().unwrap().remove(0);
+            Box::pin(async move { result })
+        }
+    }
+
+    fn runner(results: Vec<Result<(), ExportError>>, shrink_on: Option<usize>) -> ExportRunner {
+        ExportRunner {
+            exporter: Box::new(ScriptedExporter {
+                results: Mutex::new(results),
+                shrink_on,
+                sent: AtomicUsize::new(0),
+            }),
+            batch_size: 3,
+            host: "test-host".to_string(),
+            cursors: RAW_CURSORS.register(),
+        }
+    }
+
+    #[test]
+    fn split_batches_records_cursor_for_every_chunk() {
+        let batches = split_batches("host", vec![points(1, 4, 10), points(2, 1, 20)], 3);
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].batch.len(), 3);
+        assert_eq!(batches[0].completed, [(1, 12)]);
+        assert_eq!(batches[1].batch.len(), 2);
+        assert_eq!(batches[1].completed, [(1, 13), (2, 20)]);
+    }
+
+    #[tokio::test]
+    async fn retryable_failure_keeps_accepted_chunks() {
+        let _guard = EXPORT_TEST_LOCK.lock().await;
+        let mut results = vec![Ok(())];
+        results.extend(
+            (0..=MAX_RETRIES).map(|_| Err(ExportError::Retryable("unavailable".to_string()))),
+        );
+        let mut runner = runner(results, None);
+        runner.export(vec![points(1, 5, 10)]).await;
+        // 第一个批次已被接收，游标停在该批次的最后一个样本
+        assert_eq!(runner.cursors.get(1), 12);
+        assert_eq!(buffered_bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn fatal_batches_are_dropped_and_cursor_advances() {
+        let _guard = EXPORT_TEST_LOCK.lock().await;
+        let mut runner = runner(
+            vec![Err(ExportError::Fatal("rejected".to_string())), Ok(())],
+            None,
+        );
+        runner
+            .export(vec![points(1, 3, 10), points(2, 3, 20)])
+            .await;
+        // 第一个批次被拒绝后仍继续发送后续批次
+        assert_eq!(runner.cursors.get(1), 12);
+        assert_eq!(runner.cursors.get(2), 22);
+        assert_eq!(buffered_bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn shrink_releases_remaining_batches_without_advancing() {
+        let _guard = EXPORT_TEST_LOCK.lock().await;
+        let mut runner = runner(vec![Ok(()), Ok(())], Some(0));
+        runner
+            .export(vec![points(1, 3, 10), points(2, 3, 20)])
+            .await;
+        // 当前批次发送完后停止，剩余批次留到下一轮
+        assert_eq!(runner.cursors.get(1), 12);
+        assert_eq!(runner.cursors.get(2), 0);
+        assert_eq!(buffered_bytes(), 0);
+
+        let before = export_limit(1_000_000);
+        relax_buffers();
+        assert!(export_limit(1_000_000) >= before);
+        for _ in 0..MAX_SHRINK_SHIFT {
+            relax_buffers();
+        }
+        assert_eq!(export_limit(1_000_000), 100_000);
+    }
+}
@@ -0,0 +1,552 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use serde_json::{json, Value};
+
+use crate::{
+    data_processor::store::ProcessStore,
+    helper::{
+        config::{AppConfig, OtlpConfig, OtlpEncoding},
+        metrics::split_series_name,
+        procfs::redact_value,
+    },
+};
+
+use super::{
+    http_client::{post, HttpUrl},
+    ExportBatch, ExportError, ExportFuture, Exporter,
+};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// OTLP 指标名前缀
+const METRIC_PREFIX: &str = "mitojs";
+const SCOPE_NAME: &str = "mitojs-agent";
+
+/// OTLP 属性值
+#[derive(Debug, Clone)]
+enum AttrValue {
+    Str(String),
+    Int(i64),
+}
+
+type Attributes = Vec<(&'static str, AttrValue)>;
+
+struct DataPoint {
+    // timestamp millisecond
+    timestamp: u64,
+    value: f64,
+    attributes: Attributes,
+}
+
+/// 单个进程的 resource 与按指标名分组的数据点
+struct ResourceMetrics {
+    resource: Attributes,
+    metrics: BTreeMap<String, Vec<DataPoint>>,
+}
+
+/// 进程信息转换为 resource 属性，命令行参数按脱敏配置处理
+fn resource_attributes(process: &ProcessStore, host: &str) -> Attributes {
+    let mut attributes = vec![
+        ("service.name", AttrValue::Str(process.app_name.clone())),
+        (
+            "service.instance.id",
+            AttrValue::Str(process.session_id.clone()),
+        ),
+        ("host.name", AttrValue::Str(host.to_string())),
+        ("process.pid", AttrValue::Int(process.process_id as i64)),
+        ("process.runtime.name", AttrValue::Str("nodejs".to_string())),
+        (
+            "process.runtime.version",
+            AttrValue::Str(process.node_version.clone()),
+        ),
+    ];
+    let Some(metadata) = &process.metadata else {
+        return attributes;
+    };
+    if !metadata.cmdline.is_empty() {
+        let patterns = &AppConfig::global().redact.patterns;
+        attributes.push((
+            "process.command_line",
+            AttrValue::Str(redact_value(&metadata.cmdline.join(" "), patterns)),
+        ));
+    }
+    if let Some(exe) = &metadata.exe {
+        attributes.push(("process.executable.path", AttrValue::Str(exe.clone())));
+    }
+    if let Some(cwd) = &metadata.cwd {
+        attributes.push(("process.working_directory", AttrValue::Str(cwd.clone())));
+    }
+    if let Some(ppid) = metadata.ppid {
+        attributes.push(("process.parent_pid", AttrValue::Int(ppid as i64)));
+    }
+    if let Some(node_env) = &metadata.node_env {
+        attributes.push(("deployment.environment", AttrValue::Str(node_env.clone())));
+    }
+    attributes
+}
+
+fn group_batch(batch: &ExportBatch) -> Vec<ResourceMetrics> {
+    batch
+        .processes
+        .iter()
+        .map(|process| {
+            let mut metrics: BTreeMap<String, Vec<DataPoint>> = BTreeMap::new();
+            for (key, sample) in &process.points {
+                let (name, extra_label) = split_series_name(&key.name);
+                let mut attributes: Attributes = Vec::new();
+                if let Some(thread_id) = key.thread_id {
+                    attributes.push(("thread.id", AttrValue::Int(thread_id as i64)));
+                }
+                if let Some((label, value)) = extra_label {
+                    attributes.push((label, AttrValue::Str(value.to_string())));
+                }
+                metrics
+                    .entry(format!("{}.{}", METRIC_PREFIX, name))
+                    .or_default()
+                    .push(DataPoint {
+                        timestamp: sample.timestamp,
+                        value: sample.value,
+                        attributes,
+                    });
+            }
+            ResourceMetrics {
+                resource: resource_attributes(&process.process, &batch.host),
+                metrics,
+            }
+        })
+        .collect()
+}
+
+fn unix_nanos(timestamp: u64) -> u64 {
+    timestamp.saturating_mul(1_000_000)
+}
+
+/// 按 OTLP/JSON 编码，64 位整数按规范编码为字符串
+fn encode_json(resources: &[ResourceMetrics]) -> Vec<u8> {
+    let attributes_json = |attributes: &Attributes| -> Vec<Value> {
+        attributes
+            .iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    AttrValue::Str(value) => json!({ "stringValue": value }),
+                    AttrValue::Int(value) => json!({ "intValue": value.to_string() }),
+                };
+                json!({ "key": key, "value": value })
+            })
+            .collect()
+    };
+
+    let resource_metrics: Vec<Value> = resources
+        .iter()
+        .map(|resource| {
+            let metrics: Vec<Value> = resource
+                .metrics
+                .iter()
+                .map(|(name, points)| {
+                    let data_points: Vec<Value> = points
+                        .iter()
+                        .map(|point| {
+                            json!({
+                                "timeUnixNano": unix_nanos(point.timestamp).to_string(),
+                                "asDouble": point.value,
+                                "attributes": attributes_json(&point.attributes),
+                            })
+                        })
+                        .collect();
+                    json!({ "name": name, "gauge": { "dataPoints": data_points } })
+                })
+                .collect();
+            json!({
+                "resource": { "attributes": attributes_json(&resource.resource) },
+                "scopeMetrics": [{
+                    "scope": { "name": SCOPE_NAME, "version": env!("CARGO_PKG_VERSION") },
+                    "metrics": metrics,
+                }],
+            })
+        })
+        .collect();
+
+    json!({ "resourceMetrics": resource_metrics })
+        .to_string()
+        .into_bytes()
+}
+
+/// 最小化的 protobuf 编码器，只包含 OTLP 指标用到的字段类型
+#[derive(Default)]
+struct ProtoWriter {
+    buf: Vec<u8>,
+}
+
+impl ProtoWriter {
+    const VARINT: u64 = 0;
+    const FIXED64: u64 = 1;
+    const LEN: u64 = 2;
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buf.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.buf.push(value as u8);
+    }
+
+    fn tag(&mut self, field: u64, wire_type: u64) {
+        self.varint((field << 3) | wire_type);
+    }
+
+    fn bytes(&mut self, field: u64, bytes: &[u8]) {
+        self.tag(field, Self::LEN);
+        self.varint(bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn string(&mut self, field: u64, value: &str) {
+        self.bytes(field, value.as_bytes());
+    }
+
+    fn int64(&mut self, field: u64, value: i64) {
+        self.tag(field, Self::VARINT);
+        self.varint(value as u64);
+    }
+
+    fn fixed64(&mut self, field: u64, value: u64) {
+        self.tag(field, Self::FIXED64);
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn double(&mut self, field: u64, value: f64) {
+        self.fixed64(field, value.to_bits());
+    }
+
+    /// 编码嵌套消息，先写入临时缓冲区以得到长度
+    fn message(&mut self, field: u64, f: impl FnOnce(&mut ProtoWriter)) {
+        let mut nested = ProtoWriter::default();
+        f(&mut nested);
+        self.bytes(field, &nested.buf);
+    }
+}
+
+/// 按 opentelemetry-proto 中 ExportMetricsServiceRequest 的字段编号编码
+fn encode_protobuf(resources: &[ResourceMetrics]) -> Vec<u8> {
+    // KeyValue { key = 1, value = 2 }，AnyValue { string_value = 1, int_value = 3 }
+    let write_attributes = |w: &mut ProtoWriter, field: u64, attributes: &Attributes| {
+        for (key, value) in attributes {
+            w.message(field, |kv| {
+                kv.string(1, key);
+                kv.message(2, |any| match value {
+                    AttrValue::Str(value) => any.string(1, value),
+                    AttrValue::Int(value) => any.int64(3, *value),
+                });
+            });
+        }
+    };
+
+    let mut request = ProtoWriter::default();
+    for resource in resources {
+        // ExportMetricsServiceRequest.resource_metrics = 1
+        request.message(1, |rm| {
+            // ResourceMetrics.resource = 1，Resource.attributes = 1
+            rm.message(1, |r| write_attributes(r, 1, &resource.resource));
+            // ResourceMetrics.scope_metrics = 2
+            rm.message(2, |sm| {
+                // ScopeMetrics.scope = 1 { name = 1, version = 2 }
+                sm.message(1, |scope| {
+                    scope.string(1, SCOPE_NAME);
+                    scope.string(2, env!("CARGO_PKG_VERSION"));
+                });
+                for (name, points) in &resource.metrics {
+                    // ScopeMetrics.metrics = 2，Metric { name = 1, gauge = 5 }
+                    sm.message(2, |metric| {
+                        metric.string(1, name);
+                        metric.message(5, |gauge| {
+                            for point in points {
+                                // Gauge.data_points = 1
+                                // NumberDataPoint { time_unix_nano = 3, as_double = 4, attributes = 7 }
+                                gauge.message(1, |dp| {
+                                    dp.fixed64(3, unix_nanos(point.timestamp));
+                                    dp.double(4, point.value);
+                                    write_attributes(dp, 7, &point.attributes);
+                                });
+                            }
+                        });
+                    });
+                }
+            });
+        });
+    }
+    request.buf
+}
+
+/// 通过 OTLP/HTTP 推送到 OpenTelemetry collector
+pub struct OtlpExporter {
+    url: HttpUrl,
+    encoding: OtlpEncoding,
+}
+
+impl OtlpExporter {
+    pub fn new(config: &OtlpConfig) -> Result<Self, String> {
+        let mut url = HttpUrl::parse(&config.endpoint)?;
+        // 只配置了 collector 地址时使用默认路径
+        if url.path == "/" {
+            url.path = "/v1/metrics".to_string();
+        }
+        Ok(Self {
+            url,
+            encoding: config.encoding,
+        })
+    }
+
+    async fn send(&self, batch: &ExportBatch) -> Result<(), ExportError> {
+        let resources = group_batch(batch);
+        let (content_type, body) = match self.encoding {
+            OtlpEncoding::Json => ("application/json", encode_json(&resources)),
+            OtlpEncoding::Protobuf => ("application/x-protobuf", encode_protobuf(&resources)),
+        };
+        let response = post(&self.url, content_type, &[], &body, REQUEST_TIMEOUT)
+            .await
+            .map_err(|e| ExportError::Retryable(format!("请求 collector 失败: {}", e)))?;
+        if response.is_success() {
+            return Ok(());
+        }
+        let msg = format!("collector 返回 {}: {}", response.status, response.body);
+        // OTLP 规范中 429、502、503、504 可以重试
+        match response.status {
+            429 | 502 | 503 | 504 => Err(ExportError::Retryable(msg)),
+            _ => Err(ExportError::Fatal(msg)),
+        }
+    }
+}
+
+impl Exporter for OtlpExporter {
+    fn name(&self) -> &'static str {
+        "otlp"
+    }
+
+    fn export<'a>(&'a self, batch: &'a ExportBatch) -> ExportFuture<'a> {
+        Box::pin(self.send(batch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        exporter::ProcessPoints,
+        helper::metrics::{Sample, SeriesKey},
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+
+    /// 解码后的 protobuf 字段
+    #[derive(Debug)]
+    enum Field {
+        Varint(u64),
+        Fixed64(u64),
+        Len(Vec<u8>),
+    }
+
+    fn read_varint(data: &[u8], position: &mut usize) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = data[*position];
+            *position += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    fn decode(data: &[u8]) -> Vec<(u64, Field)> {
+        let mut fields = Vec::new();
+        let mut position = 0;
+        while position < data.len() {
+            let tag = read_varint(data, &mut position);
+            let field = match tag & 7 {
+                0 => Field::Varint(read_varint(data, &mut position)),
+                1 => {
+                    let bytes = data[position..position + 8].try_into().unwrap();
+                    position += 8;
+                    Field::Fixed64(u64::from_le_bytes(bytes))
+                }
+                2 => {
+                    let len = read_varint(data, &mut position) as usize;
+                    position += len;
+                    Field::Len(data[position - len..position].to_vec())
+                }
+                wire_type => panic!("unexpected wire type {}", wire_type),
+            };
+            fields.push((tag >> 3, field));
+        }
+        fields
+    }
+
+    /// 取出指定编号的嵌套消息或字符串
+    fn nested(fields: &[(u64, Field)], number: u64) -> Vec<&[u8]> {
+        fields
+            .iter()
+            .filter_map(|(field, value)| match value {
+                Field::Len(bytes) if *field == number => Some(bytes.as_slice()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn batch() -> ExportBatch {
+        let key = |thread_id| SeriesKey {
+            name: "cpu.load".to_string(),
+            thread_id,
+        };
+        ExportBatch {
+            host: "test-host".to_string(),
+            processes: vec![ProcessPoints {
+                process: ProcessStore::for_test(4_101_301, "demo"),
+                points: vec![
+                    (
+                        key(None),
+                        Sample {
+                            timestamp: 1_000,
+                            value: 0.5,
+                        },
+                    ),
+                    (
+                        key(Some(7)),
+                        Sample {
+                            timestamp: 2_000,
+                            value: 0.25,
+                        },
+                    ),
+                ],
+            }],
+        }
+    }
+
+    #[test]
+    fn encode_protobuf_follows_otlp_field_numbers() {
+        let body = encode_protobuf(&group_batch(&batch()));
+
+        let request = decode(&body);
+        let resource_metrics = nested(&request, 1);
+        assert_eq!(resource_metrics.len(), 1);
+        let resource_metrics = decode(resource_metrics[0]);
+
+        // Resource.attributes 中 service.name 为 app 名
+        let resource = decode(nested(&resource_metrics, 1)[0]);
+        let attributes: Vec<Vec<(u64, Field)>> =
+            nested(&resource, 1).into_iter().map(decode).collect();
+        let service_name = attributes
+            .iter()
+            .find(|kv| nested(kv, 1)[0] == b"service.name")
+            .unwrap();
+        assert_eq!(nested(&decode(nested(service_name, 2)[0]), 1)[0], b"demo");
+
+        let scope_metrics = decode(nested(&resource_metrics, 2)[0]);
+        let scope = decode(nested(&scope_metrics, 1)[0]);
+        assert_eq!(nested(&scope, 1)[0], SCOPE_NAME.as_bytes());
+
+        let metrics = nested(&scope_metrics, 2);
+        assert_eq!(metrics.len(), 1);
+        let metric = decode(metrics[0]);
+        assert_eq!(nested(&metric, 1)[0], b"mitojs.cpu.load");
+        let gauge = decode(nested(&metric, 5)[0]);
+        let points: Vec<Vec<(u64, Field)>> = nested(&gauge, 1).into_iter().map(decode).collect();
+        assert_eq!(points.len(), 2);
+        assert!(matches!(points[0][0], (3, Field::Fixed64(1_000_000_000))));
+        assert!(matches!(points[0][1], (4, Field::Fixed64(bits)) if f64::from_bits(bits) == 0.5));
+        // 线程序列带 thread.id 属性
+        let thread_attribute = decode(nested(&points[1], 7)[0]);
+        assert_eq!(nested(&thread_attribute, 1)[0], b"thread.id");
+        assert!(matches!(
+            decode(nested(&thread_attribute, 2)[0])[0],
+            (3, Field::Varint(7))
+        ));
+    }
+
+    #[test]
+    fn encode_json_uses_string_encoded_integers() {
+        let body = encode_json(&group_batch(&batch()));
+        let value: Value = serde_json::from_slice(&body).unwrap();
+        let metric = &value["resourceMetrics"][0]["scopeMetrics"][0]["metrics"][0];
+        assert_eq!(metric["name"], "mitojs.cpu.load");
+        let point = &metric["gauge"]["dataPoints"][0];
+        assert_eq!(point["timeUnixNano"], "1000000000");
+        assert_eq!(point["asDouble"], 0.5);
+    }
+
+    /// 本地模拟的 collector，接收一次请求并返回指定状态码，结果为请求头与 body
+    async fn stand_in_collector(status: u16) -> (String, JoinHandle<(String, Vec<u8>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            let header_end = loop {
+                let read = stream.read(&mut buf).await.unwrap();
+                assert!(read > 0, "连接在请求头结束前关闭");
+                request.extend_from_slice(&buf[..read]);
+                if let Some(index) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break index + 4;
+                }
+            };
+            let head = String::from_utf8(request[..header_end].to_vec()).unwrap();
+            let content_length: usize = head
+                .lines()
+                .find_map(|line| line.strip_prefix("Content-Length: "))
+                .unwrap()
+                .parse()
+                .unwrap();
+            while request.len() < header_end + content_length {
+                let read = stream.read(&mut buf).await.unwrap();
+                assert!(read > 0, "连接在 body 结束前关闭");
+                request.extend_from_slice(&buf[..read]);
+            }
+            let response = format!(
+                "HTTP/1.1 {} Status\r\nContent-Length: 8\r\nConnection: close\r\n\r\nresponse",
+                status
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            (head, request[header_end..].to_vec())
+        });
+        (endpoint, handle)
+    }
+
+    fn exporter(endpoint: String) -> OtlpExporter {
+        OtlpExporter::new(&OtlpConfig {
+            endpoint,
+            encoding: OtlpEncoding::Protobuf,
+            interval: 15,
+            batch_size: 1000,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn exporter_posts_protobuf_to_collector() {
+        let (endpoint, collector) = stand_in_collector(200).await;
+        let batch = batch();
+        exporter(endpoint).export(&batch).await.unwrap();
+
+        let (head, body) = collector.await.unwrap();
+        assert!(head.starts_with("POST /v1/metrics HTTP/1.1\r\n"));
+        assert!(head.contains("Content-Type: application/x-protobuf\r\n"));
+        assert_eq!(body, encode_protobuf(&group_batch(&batch)));
+    }
+
+    #[tokio::test]
+    async fn collector_errors_map_to_retryable_or_fatal() {
+        let (endpoint, collector) = stand_in_collector(503).await;
+        let result = exporter(endpoint).export(&batch()).await;
+        assert!(matches!(result, Err(ExportError::Retryable(_))));
+        collector.await.unwrap();
+
+        let (endpoint, collector) = stand_in_collector(400).await;
+        let result = exporter(endpoint).export(&batch()).await;
+        assert!(matches!(result, Err(ExportError::Fatal(msg)) if msg.contains("400")));
+        collector.await.unwrap();
+    }
+}
@@ -1,13 +1,47 @@
+use std::sync::OnceLock;
+
+use strum::{Display, EnumString};
+
 use crate::{
+    data_processor::leaks::MAX_LEAK_WINDOW,
     debug_print,
-    helper::constants::{AGENT_DIR, AGENT_TCP_PORT},
+    exporter::{http_client::HttpUrl, influx::InfluxTarget},
+    helper::{
+        constants::{AGENT_DIR, AGENT_TCP_PORT},
+        metrics::Resolution,
+    },
     log_print,
 };
+
+// 启动时初始化的全局配置，供 HTTP 接口等无法直接传参的地方读取
+static GLOBAL_CONFIG: OnceLock<AppConfig> = OnceLock::new();
+
 /// 应用程序配置
 #[derive(Debug, Clone)]
 pub struct AppConfig {
     pub tcp: TcpConfig,
     pub agent_dir: String,
+    pub reaper: ReaperConfig,
+    pub segments: SegmentConfig,
+    // store 快照保存间隔，单位秒
+    pub persist_interval: u64,
+    pub redact: RedactConfig,
+    // 事件循环最大延迟超过该值时认为被阻塞，单位毫秒
+    pub event_loop_blocked_threshold: f64,
+    // 判断句柄泄漏的时间窗口，单位秒
+    pub leak_window: u64,
+    // agent 内存储数据的内存预算，单位 byte
+    pub memory_budget: usize,
+    // 样本偏离基线的 z-score 绝对值超过该值时视为异常
+    pub anomaly_z_threshold: f64,
+    // 告警规则的执行间隔，单位秒
+    pub rules_interval: u64,
+    // agent 采样 /proc 的间隔，单位秒，为 0 时不采样
+    pub os_sample_interval: u64,
+    // 未配置 MITO_AGENT_OTLP_ENDPOINT 时不启用
+    pub otlp: Option<OtlpConfig>,
+    // 未配置 MITO_AGENT_INFLUX_TARGET 时不启用
+    pub influx: Option<InfluxConfig>,
 }
 
 /// TCP 服务器配置
@@ -17,11 +51,149 @@ pub struct TcpConfig {
     pub host: String,
 }
 
+/// 心跳超时清理配置，单位均为秒
+#[derive(Debug, Clone)]
+pub struct ReaperConfig {
+    // 扫描间隔
+    pub interval: u64,
+    // 超过该时间未收到心跳标记为 stale
+    pub stale_timeout: u64,
+    // 超过该时间未收到心跳从 store 中移除
+    pub evict_timeout: u64,
+}
+
+/// 指标落盘配置，时间单位秒
+#[derive(Debug, Clone)]
+pub struct SegmentConfig {
+    // 写入间隔，为 0 时不落盘
+    pub flush_interval: u64,
+    // segment 封存后经过该时间压缩为 1m 聚合
+    pub compact_after: u64,
+    // 超过该时间的 segment 被删除
+    pub retention: u64,
+    // segment 文件总大小上限，单位 byte，超出时删除最旧的 segment
+    pub max_bytes: u64,
+}
+
+/// 进程启动参数与环境变量脱敏配置
+#[derive(Debug, Clone)]
+pub struct RedactConfig {
+    // 参数名包含这些关键字（不区分大小写）时隐藏其值，为空时不脱敏
+    pub patterns: Vec<String>,
+}
+
+/// OTLP 导出的编码格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum OtlpEncoding {
+    Json,
+    Protobuf,
+}
+
+/// OTLP/HTTP 指标导出配置
+#[derive(Debug, Clone)]
+pub struct OtlpConfig {
+    // collector 地址，例如 http://localhost:4318/v1/metrics
+    pub endpoint: String,
+    pub encoding: OtlpEncoding,
+    // 导出间隔，单位秒
+    pub interval: u64,
+    // 单次请求最多携带的数据点数量
+    pub batch_size: usize,
+}
+
+impl OtlpConfig {
+    fn new(endpoint: String) -> Self {
+        Self {
+            endpoint,
+            encoding: OtlpEncoding::Protobuf,
+            interval: 15,
+            batch_size: 1000,
+        }
+    }
+}
+
+/// InfluxDB line protocol 导出配置
+#[derive(Debug, Clone)]
+pub struct InfluxConfig {
+    // 写入目标，支持 http://、udp://、file://
+    pub target: String,
+    // InfluxDB 2.x 的 API token，http 写入时通过 Authorization 头发送
+    pub token: Option<String>,
+    // 导出间隔，单位秒
+    pub interval: u64,
+    // 单次写入最多携带的数据点数量
+    pub batch_size: usize,
+}
+
+impl InfluxConfig {
+    fn new(target: String) -> Self {
+        Self {
+            target,
+            token: None,
+            interval: 10,
+            batch_size: 5000,
+        }
+    }
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
             tcp: TcpConfig::default(),
             agent_dir: "".to_string(),
+            reaper: ReaperConfig::default(),
+            segments: SegmentConfig::default(),
+            persist_interval: 30,
+            redact: RedactConfig::default(),
+            event_loop_blocked_threshold: 100.0,
+            leak_window: 600,
+            memory_budget: 64 * 1024 * 1024,
+            anomaly_z_threshold: 3.0,
+            rules_interval: 5,
+            os_sample_interval: 5,
+            otlp: None,
+            influx: None,
+        }
+    }
+}
+
+impl Default for RedactConfig {
+    fn default() -> Self {
+        Self {
+            patterns: [
+                "token",
+                "secret",
+                "password",
+                "passwd",
+                "key",
+                "auth",
+                "credential",
+            ]
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect(),
+        }
+    }
+}
+
+impl Default for ReaperConfig {
+    fn default() -> Self {
+        Self {
+            interval: 5,
+            stale_timeout: 30,
+            evict_timeout: 300,
+        }
+    }
+}
+
+impl Default for SegmentConfig {
+    fn default() -> Self {
+        Self {
+            flush_interval: 10,
+            compact_after: 3_600,
+            retention: 7 * 86_400,
+            max_bytes: 512 * 1024 * 1024,
         }
     }
 }
@@ -51,6 +223,83 @@ impl AppConfig {
             config.tcp.host = host;
         }
 
+        if let Some(secs) = parse_env::<u64>("MITO_AGENT_REAPER_INTERVAL") {
+            config.reaper.interval = secs;
+        }
+        if let Some(secs) = parse_env::<u64>("MITO_AGENT_STALE_TIMEOUT") {
+            config.reaper.stale_timeout = secs;
+        }
+        if let Some(secs) = parse_env::<u64>("MITO_AGENT_EVICT_TIMEOUT") {
+            config.reaper.evict_timeout = secs;
+        }
+        if let Some(secs) = parse_env::<u64>("MITO_AGENT_SEGMENT_FLUSH_INTERVAL") {
+            config.segments.flush_interval = secs;
+        }
+        if let Some(secs) = parse_env::<u64>("MITO_AGENT_SEGMENT_COMPACT_AFTER") {
+            config.segments.compact_after = secs;
+        }
+        if let Some(secs) = parse_env::<u64>("MITO_AGENT_SEGMENT_RETENTION") {
+            config.segments.retention = secs;
+        }
+        if let Some(mb) = parse_env::<u64>("MITO_AGENT_SEGMENT_MAX_MB") {
+            config.segments.max_bytes = mb.saturating_mul(1024 * 1024);
+        }
+        if let Some(secs) = parse_env::<u64>("MITO_AGENT_PERSIST_INTERVAL") {
+            config.persist_interval = secs;
+        }
+        if let Some(ms) = parse_env::<f64>("MITO_AGENT_EVENT_LOOP_BLOCKED_MS") {
+            config.event_loop_blocked_threshold = ms;
+        }
+        if let Some(secs) = parse_env::<u64>("MITO_AGENT_LEAK_WINDOW") {
+            config.leak_window = secs;
+        }
+        if let Some(mb) = parse_env::<usize>("MITO_AGENT_MEMORY_BUDGET_MB") {
+            config.memory_budget = mb.saturating_mul(1024 * 1024);
+        }
+        if let Some(z_score) = parse_env::<f64>("MITO_AGENT_ANOMALY_Z_THRESHOLD") {
+            config.anomaly_z_threshold = z_score;
+        }
+        if let Some(secs) = parse_env::<u64>("MITO_AGENT_RULES_INTERVAL") {
+            config.rules_interval = secs;
+        }
+        if let Some(secs) = parse_env::<u64>("MITO_AGENT_OS_SAMPLE_INTERVAL") {
+            config.os_sample_interval = secs;
+        }
+        // 逗号分隔，例如 MITO_AGENT_REDACT_PATTERNS=token,secret
+        if let Ok(patterns) = std::env::var("MITO_AGENT_REDACT_PATTERNS") {
+            config.redact.patterns = patterns
+                .split(',')
+                .map(|pattern| pattern.trim().to_lowercase())
+                .filter(|pattern| !pattern.is_empty())
+                .collect();
+        }
+
+        if let Ok(endpoint) = std::env::var("MITO_AGENT_OTLP_ENDPOINT") {
+            let mut otlp = OtlpConfig::new(endpoint);
+            if let Some(encoding) = parse_env::<OtlpEncoding>("MITO_AGENT_OTLP_ENCODING") {
+                otlp.encoding = encoding;
+            }
+            if let Some(secs) = parse_env::<u64>("MITO_AGENT_OTLP_INTERVAL") {
+                otlp.interval = secs;
+            }
+            if let Some(size) = parse_env::<usize>("MITO_AGENT_OTLP_BATCH_SIZE") {
+                otlp.batch_size = size;
+            }
+            config.otlp = Some(otlp);
+        }
+
+        if let Ok(target) = std::env::var("MITO_AGENT_INFLUX_TARGET") {
+            let mut influx = InfluxConfig::new(target);
+            influx.token = std::env::var("MITO_AGENT_INFLUX_TOKEN").ok();
+            if let Some(secs) = parse_env::<u64>("MITO_AGENT_INFLUX_INTERVAL") {
+                influx.interval = secs;
+            }
+            if let Some(size) = parse_env::<usize>("MITO_AGENT_INFLUX_BATCH_SIZE") {
+                influx.batch_size = size;
+            }
+            config.influx = Some(influx);
+        }
+
         // todo 在当前目录下创建 agent 目录，如果不行则在 tmp 下创建目录
         config.agent_dir = std::env::current_dir()
             .unwrap()
@@ -61,11 +310,85 @@ impl AppConfig {
         config
     }
 
+    /// 设置全局配置，只有第一次调用生效
+    pub fn init_global(config: AppConfig) {
+        let _ = GLOBAL_CONFIG.set(config);
+    }
+
+    /// 获取全局配置，未初始化时使用默认配置
+    pub fn global() -> &'static AppConfig {
+        GLOBAL_CONFIG.get_or_init(AppConfig::default)
+    }
+
     /// 验证配置的有效性
     pub fn validate(&self) -> Result<(), String> {
         if self.tcp.port == 0 {
             return Err("TCP 端口不能为 0".to_string());
         }
+        if self.reaper.interval == 0 {
+            return Err("心跳扫描间隔不能为 0".to_string());
+        }
+        if self.persist_interval == 0 {
+            return Err("快照保存间隔不能为 0".to_string());
+        }
+        if self.reaper.evict_timeout <= self.reaper.stale_timeout {
+            return Err("进程清理超时时间必须大于 stale 超时时间".to_string());
+        }
+        if !self.event_loop_blocked_threshold.is_finite()
+            || self.event_loop_blocked_threshold <= 0.0
+        {
+            return Err("事件循环阻塞阈值必须大于 0".to_string());
+        }
+        if self.segments.flush_interval > 0 {
+            // 写入间隔内的样本只在内存中，间隔超过原始样本的保留时间会丢数据
+            let raw_retention = Resolution::Raw.retention() / 1000;
+            if self.segments.flush_interval >= raw_retention {
+                return Err(format!(
+                    "segment 写入间隔必须小于原始样本的保留时间 {}s",
+                    raw_retention
+                ));
+            }
+            if self.segments.retention == 0 {
+                return Err("segment 保留时间不能为 0".to_string());
+            }
+            if self.segments.max_bytes == 0 {
+                return Err("segment 总大小上限不能为 0".to_string());
+            }
+        }
+        if self.memory_budget == 0 {
+            return Err("内存预算不能为 0".to_string());
+        }
+        if !self.anomaly_z_threshold.is_finite() || self.anomaly_z_threshold <= 0.0 {
+            return Err("异常检测 z-score 阈值必须大于 0".to_string());
+        }
+        if self.rules_interval == 0 {
+            return Err("告警规则执行间隔不能为 0".to_string());
+        }
+        if self.leak_window == 0 || self.leak_window > MAX_LEAK_WINDOW {
+            return Err(format!(
+                "句柄泄漏检测窗口必须在 1-{}s 之间",
+                MAX_LEAK_WINDOW
+            ));
+        }
+        if let Some(otlp) = &self.otlp {
+            HttpUrl::parse(&otlp.endpoint).map_err(|e| format!("OTLP 地址无效: {}", e))?;
+            if otlp.interval == 0 {
+                return Err("OTLP 导出间隔不能为 0".to_string());
+            }
+            if otlp.batch_size == 0 {
+                return Err("OTLP 批量大小不能为 0".to_string());
+            }
+        }
+        if let Some(influx) = &self.influx {
+            InfluxTarget::parse(&influx.target)
+                .map_err(|e| format!("InfluxDB 写入目标无效: {}", e))?;
+            if influx.interval == 0 {
+                return Err("InfluxDB 导出间隔不能为 0".to_string());
+            }
+            if influx.batch_size == 0 {
+                return Err("InfluxDB 批量大小不能为 0".to_string());
+            }
+        }
 
         Ok(())
     }
@@ -74,5 +397,90 @@ impl AppConfig {
     pub fn print_config(&self) {
         log_print!("📋 应用程序配置:");
         log_print!("    地址: {}:{}", self.tcp.host, self.tcp.port);
+        log_print!("    数据目录: {}", self.agent_dir);
+        log_print!(
+            "    心跳超时: stale {}s, evict {}s (每 {}s 扫描)",
+            self.reaper.stale_timeout,
+            self.reaper.evict_timeout,
+            self.reaper.interval
+        );
+        log_print!("    内存预算: {}MB", self.memory_budget / 1024 / 1024);
+        if self.segments.flush_interval > 0 {
+            log_print!(
+                "    指标落盘: 每 {}s 写入, 保留 {}s, 上限 {}MB",
+                self.segments.flush_interval,
+                self.segments.retention,
+                self.segments.max_bytes / 1024 / 1024
+            );
+        }
+        log_print!("    告警规则执行间隔: {}s", self.rules_interval);
+        if self.os_sample_interval > 0 {
+            log_print!("    /proc 采样间隔: {}s", self.os_sample_interval);
+        }
+        if let Some(otlp) = &self.otlp {
+            log_print!(
+                "    OTLP 导出: {} ({}, 每 {}s)",
+                otlp.endpoint,
+                otlp.encoding,
+                otlp.interval
+            );
+        }
+        if let Some(influx) = &self.influx {
+            log_print!(
+                "    InfluxDB 导出: {} (每 {}s)",
+                influx.target,
+                influx.interval
+            );
+        }
+    }
+}
+
+/// 读取并解析环境变量，解析失败时忽略
+fn parse_env<T: std::str::FromStr + std::fmt::Display>(key: &str) -> Option<T> {
+    let value = std::env::var(key).ok()?.parse::<T>().ok()?;
+    debug_print!("ENV {}: {}", key, value);
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_valid() {
+        assert!(AppConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn leak_window_must_fit_tracked_history() {
+        let validate = |leak_window| {
+            AppConfig {
+                leak_window,
+                ..Default::default()
+            }
+            .validate()
+        };
+        assert!(validate(0).is_err());
+        assert!(validate(MAX_LEAK_WINDOW).is_ok());
+        assert!(validate(MAX_LEAK_WINDOW + 1).is_err());
+    }
+
+    #[test]
+    fn segment_flush_interval_must_be_below_raw_retention() {
+        let validate = |flush_interval| {
+            AppConfig {
+                segments: SegmentConfig {
+                    flush_interval,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+            .validate()
+        };
+        // 为 0 时不落盘
+        assert!(validate(0).is_ok());
+        assert!(validate(299).is_ok());
+        assert!(validate(300).is_err());
+        assert!(validate(3_600).is_err());
     }
 }
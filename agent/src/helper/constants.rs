@@ -1,6 +1,8 @@
 pub const AGENT_DIR: &str = "_mito_node_";
 pub const UDS_SOCKET_NAME: &str = "_mito_node_.sock";
 pub const AGENT_TCP_PORT: u16 = 16666;
+// Linux 64 位系统 pid_max 的上限（PID_MAX_LIMIT），线程 id 与 pid 共用同一空间
+pub const PID_MAX_LIMIT: u32 = 4_194_304;
 
 #[derive(Debug, strum::EnumString, strum::Display)]
 pub enum ListenerResultType {
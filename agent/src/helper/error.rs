@@ -1,9 +1,6 @@
 use std::fmt;
 use std::io;
 
-// 导入宏
-use crate::error_print;
-
 /// 应用程序错误类型
 #[derive(Debug)]
 pub enum AppError {
@@ -11,8 +8,6 @@ pub enum AppError {
     Io(io::Error),
     /// 配置错误
     Config(String),
-    /// 网络错误
-    Network(String),
     /// 数据处理错误
     DataProcessing(String),
     /// 序列化错误
@@ -26,7 +21,6 @@ impl fmt::Display for AppError {
         match self {
             AppError::Io(err) => write!(f, "IO 错误: {}", err),
             AppError::Config(msg) => write!(f, "配置错误: {}", msg),
-            AppError::Network(msg) => write!(f, "网络错误: {}", msg),
             AppError::DataProcessing(msg) => write!(f, "数据处理错误: {}", msg),
             AppError::Serialization(msg) => write!(f, "序列化错误: {}", msg),
             AppError::Unknown(msg) => write!(f, "未知错误: {}", msg),
@@ -62,25 +56,3 @@ impl From<&str> for AppError {
 
 /// 结果类型别名
 pub type AppResult<T> = Result<T, AppError>;
-
-/// 错误处理工具函数
-pub mod utils {
-    use super::*;
-
-    /// 将错误转换为用户友好的消息
-    pub fn to_user_friendly(error: &AppError) -> String {
-        match error {
-            AppError::Io(_) => "系统 IO 操作失败".to_string(),
-            AppError::Config(_) => "配置参数错误".to_string(),
-            AppError::Network(_) => "网络连接失败".to_string(),
-            AppError::DataProcessing(_) => "数据处理失败".to_string(),
-            AppError::Serialization(_) => "数据格式错误".to_string(),
-            AppError::Unknown(_) => "未知错误".to_string(),
-        }
-    }
-
-    /// 记录错误日志
-    pub fn log_error(error: &AppError) {
-        error_print!("错误: {}", error);
-    }
-}
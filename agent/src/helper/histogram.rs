@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+// 每个 2 的幂区间划分的子桶数量，桶代表值的相对误差不超过 1/(2*SUB_BUCKETS)，约 3%
+pub const SUB_BUCKETS: i32 = 16;
+// 支持的指数范围 2^-32 ~ 2^64，超出范围的值归入边界桶
+const MIN_EXPONENT: i32 = -32;
+const MAX_EXPONENT: i32 = 64;
+// 单次上报中每个桶（以及 zero_count）的数量上限，超出时拒绝整条数据
+pub const MAX_REPORTED_COUNT: u64 = u32::MAX as u64;
+// BTreeMap 每个桶的内存估算，包含节点的额外开销
+const BUCKET_ENTRY_BYTES: usize = 2 * std::mem::size_of::<(i32, u64)>();
+
+/// log-linear 分桶：先按 2 的幂划分，再在每个区间内均分为 SUB_BUCKETS 份
+///
+/// 桶序号 = 指数 * SUB_BUCKETS + 子桶序号，Node 侧可按同样规则预先分桶后上报
+pub fn bucket_index(value: f64) -> i32 {
+    let exponent = value.log2().floor() as i32;
+    let exponent = exponent.clamp(MIN_EXPONENT, MAX_EXPONENT - 1);
+    let base = 2f64.powi(exponent);
+    let sub = (((value / base) - 1.0) * SUB_BUCKETS as f64).floor() as i32;
+    exponent * SUB_BUCKETS + sub.clamp(0, SUB_BUCKETS - 1)
+}
+
+/// 桶的下界与上界
+pub fn bucket_bounds(index: i32) -> (f64, f64) {
+    let exponent = index.div_euclid(SUB_BUCKETS);
+    let sub = index.rem_euclid(SUB_BUCKETS);
+    let base = 2f64.powi(exponent);
+    let width = base / SUB_BUCKETS as f64;
+    let lower = base + width * sub as f64;
+    (lower, lower + width)
+}
+
+pub fn valid_bucket_index(index: i32) -> bool {
+    (MIN_EXPONENT * SUB_BUCKETS..MAX_EXPONENT * SUB_BUCKETS).contains(&index)
+}
+
+/// 可合并的直方图，只接受非负值，0 单独计数
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    // 桶序号 -> 数量，只保存非空的桶
+    pub buckets: BTreeMap<i32, u64>,
+    pub zero_count: u64,
+    pub count: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Histogram {
+    pub fn record(&mut self, value: f64) {
+        if value <= 0.0 {
+            self.zero_count = self.zero_count.saturating_add(1);
+        } else {
+            let bucket = self.buckets.entry(bucket_index(value)).or_default();
+            *bucket = bucket.saturating_add(1);
+        }
+        self.observe(1, value, value, value);
+    }
+
+    /// 直接累加已分好的桶，sum/min/max 未知时用桶的代表值估算
+    ///
+    /// 数量来自客户端上报，累加时饱和而不是溢出
+    pub fn record_bucket(&mut self, index: i32, count: u64) {
+        if count == 0 {
+            return;
+        }
+        let bucket = self.buckets.entry(index).or_default();
+        *bucket = bucket.saturating_add(count);
+        let (lower, upper) = bucket_bounds(index);
+        self.observe(count, (lower + upper) / 2.0 * count as f64, lower, upper);
+    }
+
+    pub fn record_zeros(&mut self, count: u64) {
+        if count == 0 {
+            return;
+        }
+        self.zero_count = self.zero_count.saturating_add(count);
+        self.observe(count, 0.0, 0.0, 0.0);
+    }
+
+    fn observe(&mut self, count: u64, sum: f64, min: f64, max: f64) {
+        if self.count == 0 {
+            self.min = min;
+            self.max = max;
+        } else {
+            self.min = self.min.min(min);
+            self.max = self.max.max(max);
+        }
+        self.count = self.count.saturating_add(count);
+        self.sum += sum;
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        if other.count == 0 {
+            return;
+        }
+        for (index, count) in &other.buckets {
+            let bucket = self.buckets.entry(*index).or_default();
+            *bucket = bucket.saturating_add(*count);
+        }
+        self.zero_count = self.zero_count.saturating_add(other.zero_count);
+        self.observe(other.count, other.sum, other.min, other.max);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// 占用的内存估算，单位 byte
+    pub fn estimated_bytes(&self) -> usize {
+        std::mem::size_of::<Self>() + self.buckets.len() * BUCKET_ENTRY_BYTES
+    }
+
+    pub fn avg(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+
+    /// 分位数，q 的范围为 0~1，取所在桶的中点并限制在 [min, max] 内
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        // 第 rank 个值（从 1 开始）所在的桶
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        if rank <= self.zero_count {
+            return Some(0.0);
+        }
+        let mut seen = self.zero_count;
+        for (index, count) in &self.buckets {
+            seen = seen.saturating_add(*count);
+            if seen >= rank {
+                let (lower, upper) = bucket_bounds(*index);
+                return Some(((lower + upper) / 2.0).clamp(self.min, self.max));
+            }
+        }
+        Some(self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_index_stays_within_relative_error() {
+        for value in [1e-6, 0.3, 1.0, 1.5, 3.0, 100.0, 12_345.678, 1e12] {
+            let index = bucket_index(value);
+            assert!(valid_bucket_index(index), "{}", value);
+            let (lower, upper) = bucket_bounds(index);
+            assert!(
+                lower <= value && value < upper,
+                "{} not in [{}, {})",
+                value,
+                lower,
+                upper
+            );
+            let middle = (lower + upper) / 2.0;
+            assert!((middle - value).abs() / value <= 1.0 / (2 * SUB_BUCKETS) as f64);
+        }
+        // 2 的幂落在区间的第一个子桶
+        assert_eq!(bucket_index(1.0), 0);
+        assert_eq!(bucket_index(2.0), SUB_BUCKETS);
+        // 超出范围的值归入边界桶
+        assert_eq!(bucket_index(1e-30), MIN_EXPONENT * SUB_BUCKETS);
+        assert_eq!(bucket_index(1e30), MAX_EXPONENT * SUB_BUCKETS - 1);
+    }
+
+    #[test]
+    fn quantile_returns_bucket_middle_clamped_to_min_max() {
+        let mut histogram = Histogram::default();
+        for value in 1..=100 {
+            histogram.record(value as f64);
+        }
+        histogram.record(0.0);
+        assert_eq!(histogram.count, 101);
+        assert_eq!(histogram.quantile(0.0), Some(0.0));
+        let p50 = histogram.quantile(0.5).unwrap();
+        assert!((p50 - 50.0).abs() / 50.0 < 0.04, "{}", p50);
+        let p99 = histogram.quantile(0.99).unwrap();
+        assert!((p99 - 99.0).abs() / 99.0 < 0.04, "{}", p99);
+        assert_eq!(histogram.quantile(1.0), Some(100.0));
+        assert_eq!(Histogram::default().quantile(0.5), None);
+    }
+
+    #[test]
+    fn merge_matches_recording_into_one_histogram() {
+        let mut all = Histogram::default();
+        let mut left = Histogram::default();
+        let mut right = Histogram::default();
+        for value in [0.0, 1.0, 2.5, 7.0] {
+            all.record(value);
+            left.record(value);
+        }
+        for value in [3.0, 250.0] {
+            all.record(value);
+            right.record(value);
+        }
+        left.merge(&right);
+        left.merge(&Histogram::default());
+        assert_eq!(left, all);
+        assert_eq!((left.min, left.max, left.zero_count), (0.0, 250.0, 1));
+    }
+
+    #[test]
+    fn record_bucket_estimates_sum_and_saturates_counts() {
+        let mut histogram = Histogram::default();
+        let index = bucket_index(10.0);
+        histogram.record_bucket(index, 2);
+        let (lower, upper) = bucket_bounds(index);
+        assert_eq!((histogram.min, histogram.max), (lower, upper));
+        assert_eq!(histogram.sum, lower + upper);
+
+        histogram.record_bucket(index, u64::MAX);
+        histogram.record_zeros(u64::MAX);
+        assert_eq!(histogram.buckets[&index], u64::MAX);
+        assert_eq!(histogram.count, u64::MAX);
+        let mut merged = histogram.clone();
+        merged.merge(&histogram);
+        assert_eq!(merged.zero_count, u64::MAX);
+        assert!(merged.quantile(1.0).is_some());
+    }
+}
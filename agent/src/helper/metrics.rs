@@ -1,9 +1,956 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock, RwLock,
+    },
+    time::Duration,
+};
 
-// map: <pid, metrics>
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use tokio::{task, time::interval};
 
-struct Metrics {}
+use crate::{
+    data_processor::{
+        anomaly::ANOMALY_DETECTOR, histograms::HISTOGRAM_STORE, leaks::HANDLE_TRACKER,
+        sampler::THREAD_SNAPSHOTS, sharded::ShardedMap, store::PROCESS_DATA,
+    },
+    helper::time::now_millis,
+    log_print,
+};
 
-fn create_metrics_map() -> HashMap<u32, Metrics> {
-    let metrics_map = HashMap::new();
+/// 固定容量的环形缓冲区，写满后覆盖最旧的数据
+///
+/// 内存按写入的数据量增长，不预先分配整个容量
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RingBuffer<T> {
+    capacity: usize,
+    items: VecDeque<T>,
+}
+
+impl<T> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            items: VecDeque::new(),
+        }
+    }
+
+    /// 写入数据，缓冲区已满时返回被挤出的最旧数据
+    pub fn push(&mut self, item: T) -> Option<T> {
+        let evicted = if self.items.len() >= self.capacity {
+            self.items.pop_front()
+        } else {
+            None
+        };
+        self.items.push_back(item);
+        evicted
+    }
+
+    pub fn latest(&self) -> Option<&T> {
+        self.items.back()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> {
+        self.items.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut T> {
+        self.items.iter_mut()
+    }
+
+    /// 从最旧的一端依次移除满足条件的数据，遇到第一个不满足的即停止
+    pub fn evict_while(&mut self, evict: impl Fn(&T) -> bool) {
+        while self.items.front().is_some_and(&evict) {
+            self.items.pop_front();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// 实际分配的内存，单位 byte
+    pub fn allocated_bytes(&self) -> usize {
+        self.items.capacity() * std::mem::size_of::<T>()
+    }
+
+    /// 移除数据后释放多余的内存
+    pub fn shrink_to_fit(&mut self) {
+        self.items.shrink_to_fit();
+    }
+}
+
+/// 带时间戳的单个样本
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    // timestamp millisecond
+    pub timestamp: u64,
+    pub value: f64,
+}
+
+// 原始样本的写入序号，全局递增，从 1 开始
+static INGEST_SEQ: AtomicU64 = AtomicU64::new(0);
+
+fn next_seq() -> u64 {
+    INGEST_SEQ.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// 之后写入的样本序号要大于 seq，用于从落盘数据恢复游标
+pub fn reserve_seq_through(seq: u64) {
+    INGEST_SEQ.fetch_max(seq, Ordering::Relaxed);
+}
+
+/// 增量读取原始样本的消费者（导出器、落盘）在各进程已处理到的写入序号
+#[derive(Debug, Default)]
+pub struct RawCursors {
+    next_id: AtomicU64,
+    // 消费者 -> pid -> 写入序号
+    cursors: RwLock<HashMap<u64, HashMap<u32, u64>>>,
+}
+
+impl RawCursors {
+    pub fn register(&'static self) -> RawCursor {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.cursors.write().unwrap().insert(id, HashMap::new());
+        RawCursor { id, registry: self }
+    }
+
+    /// 所有消费者都已处理的写入序号，序号更大的样本按内存预算清理时需要保留
+    ///
+    /// 没有消费者时为 None，消费者还没有处理过该进程时为 0
+    pub fn pinned(&self, pid: u32) -> Option<u64> {
+        self.cursors
+            .read()
+            .unwrap()
+            .values()
+            .map(|cursors| cursors.get(&pid).copied().unwrap_or(0))
+            .min()
+    }
+}
+
+pub static RAW_CURSORS: LazyLock<RawCursors> = LazyLock::new(RawCursors::default);
+
+/// 单个消费者的游标，drop 时注销
+#[derive(Debug)]
+pub struct RawCursor {
+    id: u64,
+    registry: &'static RawCursors,
+}
+
+impl RawCursor {
+    pub fn get(&self, pid: u32) -> u64 {
+        self.registry
+            .cursors
+            .read()
+            .unwrap()
+            .get(&self.id)
+            .and_then(|cursors| cursors.get(&pid).copied())
+            .unwrap_or(0)
+    }
+
+    pub fn advance(&self, pid: u32, seq: u64) {
+        if let Some(cursors) = self.registry.cursors.write().unwrap().get_mut(&self.id) {
+            cursors.insert(pid, seq);
+        }
+    }
+
+    /// 只保留满足条件的进程的游标
+    pub fn retain(&self, f: impl Fn(u32) -> bool) {
+        if let Some(cursors) = self.registry.cursors.write().unwrap().get_mut(&self.id) {
+            cursors.retain(|pid, _| f(*pid));
+        }
+    }
+}
+
+impl Drop for RawCursor {
+    fn drop(&mut self) {
+        self.registry.cursors.write().unwrap().remove(&self.id);
+    }
+}
+
+/// 原始样本及其写入序号
+///
+/// 增量导出和落盘按写入序号推进游标，迟到的样本与同一时间戳的其他序列都不会被跳过
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct RawSample {
+    // 旧版本快照中没有序号，恢复后视为已导出
+    #[serde(default)]
+    seq: u64,
+    #[serde(flatten)]
+    sample: Sample,
+}
+
+/// 一段时间内样本的聚合结果
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bucket {
+    // 桶的起始时间 timestamp millisecond
+    pub timestamp: u64,
+    pub count: u32,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Bucket {
+    fn new(timestamp: u64, value: f64) -> Self {
+        Self {
+            timestamp,
+            count: 1,
+            sum: value,
+            min: value,
+            max: value,
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn avg(&self) -> f64 {
+        self.sum / self.count as f64
+    }
+
+    /// 合并同一时间段的另一个桶，timestamp 保持不变
+    pub fn merge(&mut self, other: &Bucket) {
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+}
+
+impl From<&Sample> for Bucket {
+    fn from(sample: &Sample) -> Self {
+        Bucket::new(sample.timestamp, sample.value)
+    }
+}
+
+/// 查询结果的精度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Resolution {
+    // 原始样本，保留最近 RAW_RETENTION_MS
+    Raw,
+    // 10s 聚合，保留最近 1 小时
+    TenSeconds,
+    // 1m 聚合（min/avg/max），保留最近 1 天
+    OneMinute,
+}
+
+impl Resolution {
+    /// 聚合桶的宽度，原始样本为 0
+    pub fn width(&self) -> u64 {
+        match self {
+            Resolution::Raw => 0,
+            Resolution::TenSeconds => 10_000,
+            Resolution::OneMinute => 60_000,
+        }
+    }
+
+    /// 该精度能覆盖的时间范围
+    pub fn retention(&self) -> u64 {
+        match self {
+            Resolution::Raw => RAW_RETENTION_MS,
+            Resolution::TenSeconds => 3_600_000,
+            Resolution::OneMinute => 86_400_000,
+        }
+    }
+
+    /// 选择仍能覆盖 from 的最高精度
+    pub fn for_range(from: u64, now: u64) -> Self {
+        let age = now.saturating_sub(from);
+        [Resolution::Raw, Resolution::TenSeconds]
+            .into_iter()
+            .find(|resolution| age <= resolution.retention())
+            .unwrap_or(Resolution::OneMinute)
+    }
+
+    fn capacity(&self) -> usize {
+        match self {
+            Resolution::Raw => RAW_CAPACITY,
+            _ => (self.retention() / self.width()) as usize,
+        }
+    }
+}
+
+// 原始样本保留时间，5 分钟
+const RAW_RETENTION_MS: u64 = 300_000;
+// 原始样本数量上限，防止高频上报时超出内存预算
+const RAW_CAPACITY: usize = 600;
+
+/// 单层聚合，最后一个桶是正在累积的桶
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollupTier {
+    width: u64,
+    buckets: RingBuffer<Bucket>,
+}
+
+impl RollupTier {
+    fn new(resolution: Resolution) -> Self {
+        Self {
+            width: resolution.width(),
+            buckets: RingBuffer::new(resolution.capacity()),
+        }
+    }
+
+    fn add(&mut self, sample: &Sample) {
+        let start = sample.timestamp - sample.timestamp % self.width;
+        // 乱序到达的样本合并到对应的桶，已被挤出的时间段直接丢弃
+        if let Some(bucket) = self
+            .buckets
+            .iter_mut()
+            .rev()
+            .take_while(|bucket| bucket.timestamp >= start)
+            .find(|bucket| bucket.timestamp == start)
+        {
+            bucket.add(sample.value);
+            return;
+        }
+        if self
+            .buckets
+            .latest()
+            .is_none_or(|latest| latest.timestamp < start)
+        {
+            self.buckets.push(Bucket::new(start, sample.value));
+        }
+    }
+}
+
+/// 一条指标序列：原始样本 + 10s、1m 两层聚合
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Series {
+    raw: RingBuffer<RawSample>,
+    ten_seconds: RollupTier,
+    one_minute: RollupTier,
+}
+
+impl Default for Series {
+    fn default() -> Self {
+        Self {
+            raw: RingBuffer::new(Resolution::Raw.capacity()),
+            ten_seconds: RollupTier::new(Resolution::TenSeconds),
+            one_minute: RollupTier::new(Resolution::OneMinute),
+        }
+    }
+}
+
+impl Series {
+    pub fn push(&mut self, sample: Sample) {
+        self.ten_seconds.add(&sample);
+        self.one_minute.add(&sample);
+        self.raw.push(RawSample {
+            seq: next_seq(),
+            sample,
+        });
+        // 超出保留时间的原始样本由聚合层代替
+        let expire_before = sample.timestamp.saturating_sub(RAW_RETENTION_MS);
+        self.raw
+            .evict_while(|item| item.sample.timestamp < expire_before);
+    }
+
+    pub fn latest(&self) -> Option<&Sample> {
+        self.raw.latest().map(|item| &item.sample)
+    }
+
+    /// 各层实际占用的内存，单位 byte
+    pub fn estimated_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.raw.allocated_bytes()
+            + self.ten_seconds.buckets.allocated_bytes()
+            + self.one_minute.buckets.allocated_bytes()
+    }
+
+    /// 移除早于 cutoff 的样本与聚合桶并释放内存
+    ///
+    /// 写入序号大于 pinned 的原始样本还没有被导出或落盘，即使早于 cutoff 也保留
+    pub fn evict_before(&mut self, cutoff: u64, pinned: Option<u64>) {
+        self.raw.evict_while(|item| {
+            item.sample.timestamp < cutoff && pinned.is_none_or(|pinned| item.seq <= pinned)
+        });
+        self.raw.shrink_to_fit();
+        for tier in [&mut self.ten_seconds, &mut self.one_minute] {
+            tier.buckets.evict_while(|bucket| bucket.timestamp < cutoff);
+            tier.buckets.shrink_to_fit();
+        }
+    }
+
+    /// 写入序号大于 after 的原始样本及其序号
+    pub fn raw_since(&self, after: u64) -> impl Iterator<Item = (u64, &Sample)> {
+        self.raw
+            .iter()
+            .filter(move |item| item.seq > after)
+            .map(|item| (item.seq, &item.sample))
+    }
+
+    fn max_seq(&self) -> u64 {
+        self.raw.iter().map(|item| item.seq).max().unwrap_or(0)
+    }
+
+    /// 查询 [from, to] 范围内指定精度的数据，原始样本转换为 count 为 1 的桶
+    pub fn query(&self, resolution: Resolution, from: u64, to: u64) -> Vec<Bucket> {
+        let in_range = |timestamp: u64| timestamp >= from && timestamp <= to;
+        match resolution {
+            Resolution::Raw => self
+                .raw
+                .iter()
+                .filter(|item| in_range(item.sample.timestamp))
+                .map(|item| Bucket::from(&item.sample))
+                .collect(),
+            Resolution::TenSeconds => self.ten_seconds.query(from, to),
+            Resolution::OneMinute => self.one_minute.query(from, to),
+        }
+    }
+}
+
+impl RollupTier {
+    fn query(&self, from: u64, to: u64) -> Vec<Bucket> {
+        // 与查询范围有交集的桶都返回
+        self.buckets
+            .iter()
+            .filter(|bucket| bucket.timestamp + self.width > from && bucket.timestamp <= to)
+            .copied()
+            .collect()
+    }
+}
+
+/// 序列标识，name 形如 `cpu.load`、`memory.rss`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SeriesKey {
+    pub name: String,
+    pub thread_id: Option<u32>,
+}
+
+// 序列名中包含动态部分的前缀，以及动态部分对应的标签名
+const LABELED_SERIES: [(&str, &str); 3] = [
+    ("memory.space.", "space"),
+    ("os.thread_cpu.", "kind"),
+    ("gc.count.", "kind"),
+];
+
+/// 拆分序列名中的动态部分作为标签，例如 `memory.space.old_space.used`
+/// 拆分为 `memory.space.used` 与 `space=old_space`，`os.thread_cpu.main`
+/// 拆分为 `os.thread_cpu` 与 `kind=main`
+pub fn split_series_name(name: &str) -> (String, Option<(&'static str, &str)>) {
+    for (prefix, label) in LABELED_SERIES {
+        if let Some(rest) = name.strip_prefix(prefix) {
+            return match rest.rsplit_once('.') {
+                Some((value, field)) => (format!("{}{}", prefix, field), Some((label, value))),
+                None => (
+                    prefix.trim_end_matches('.').to_string(),
+                    Some((label, rest)),
+                ),
+            };
+        }
+    }
+    (name.to_string(), None)
+}
+
+/// 单个进程的所有指标序列
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProcessMetrics {
+    // JSON 的 key 只能是字符串，序列化为数组
+    #[serde(with = "series_entries")]
+    pub series: BTreeMap<SeriesKey, Series>,
+}
+
+mod series_entries {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Series, SeriesKey};
+
+    #[derive(Serialize, Deserialize)]
+    struct Entry {
+        key: SeriesKey,
+        series: Series,
+    }
+
+    pub fn serialize<S: Serializer>(
+        series: &BTreeMap<SeriesKey, Series>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(series.iter().map(|(key, series)| Entry {
+            key: key.clone(),
+            series: series.clone(),
+        }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<SeriesKey, Series>, D::Error> {
+        let entries = Vec::<Entry>::deserialize(deserializer)?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| (entry.key, entry.series))
+            .collect())
+    }
+}
+
+/// 增量读取的原始样本
+#[derive(Debug, Clone, Default)]
+pub struct RawSince {
+    // (写入序号, 序列, 样本)，按写入序号排序，任意前缀的最大序号都可以作为游标
+    pub points: Vec<(u64, SeriesKey, Sample)>,
+    // 返回样本中最大的写入序号，作为下一次读取的游标
+    pub seq: u64,
+}
+
+/// 查询结果，resolution 为实际使用的精度
+#[derive(Debug, Clone, Serialize)]
+pub struct SeriesQuery {
+    pub resolution: Resolution,
+    pub points: Vec<Bucket>,
+}
+
+/// 以 pid 为 key 的时序指标存储
+pub struct Metrics {
+    data: ShardedMap<ProcessMetrics>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            data: ShardedMap::new(),
+        }
+    }
+
+    /// 写入同一时刻的一组样本，例如 CPU 的 load 与 user_load
+    pub fn record(&self, pid: u32, thread_id: Option<u32>, timestamp: u64, values: &[(&str, f64)]) {
+        self.data.upsert(pid, ProcessMetrics::default, |metrics| {
+            for (name, value) in values {
+                let key = SeriesKey {
+                    name: name.to_string(),
+                    thread_id,
+                };
+                metrics.series.entry(key).or_default().push(Sample {
+                    timestamp,
+                    value: *value,
+                });
+            }
+        });
+    }
+
+    /// 每条序列的最新样本
+    pub fn latest(&self, pid: u32) -> Vec<(SeriesKey, Sample)> {
+        self.data
+            .read(pid, |metrics| {
+                metrics
+                    .series
+                    .iter()
+                    .filter_map(|(key, series)| {
+                        series.latest().map(|sample| (key.clone(), *sample))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// 单条序列的最新样本
+    pub fn latest_sample(&self, pid: u32, key: &SeriesKey) -> Option<Sample> {
+        self.data
+            .read(pid, |metrics| metrics.series.get(key)?.latest().copied())
+            .flatten()
+    }
+
+    /// 写入序号大于 after 的原始样本，用于增量导出与落盘
+    pub fn raw_since(&self, pid: u32, after: u64) -> RawSince {
+        self.data
+            .read(pid, |metrics| {
+                let mut result = RawSince::default();
+                for (key, series) in &metrics.series {
+                    for (seq, sample) in series.raw_since(after) {
+                        result.seq = result.seq.max(seq);
+                        result.points.push((seq, key.clone(), *sample));
+                    }
+                }
+                result.points.sort_unstable_by_key(|(seq, _, _)| *seq);
+                result
+            })
+            .unwrap_or_default()
+    }
+
+    /// 查询 [from, to] 时间范围内的数据，根据 from 距今的时间自动选择精度
+    pub fn query(&self, pid: u32, key: &SeriesKey, from: u64, to: u64) -> SeriesQuery {
+        let resolution = Resolution::for_range(from, now_millis());
+        self.query_with_resolution(pid, key, resolution, from, to)
+    }
+
+    pub fn query_with_resolution(
+        &self,
+        pid: u32,
+        key: &SeriesKey,
+        resolution: Resolution,
+        from: u64,
+        to: u64,
+    ) -> SeriesQuery {
+        let points = self
+            .data
+            .read(pid, |metrics| {
+                metrics
+                    .series
+                    .get(key)
+                    .map(|series| series.query(resolution, from, to))
+                    .unwrap_or_default()
+            })
+            .unwrap_or_default();
+        SeriesQuery { resolution, points }
+    }
+
+    /// 查询进程下所有满足条件的序列
+    pub fn query_matching(
+        &self,
+        pid: u32,
+        filter: impl Fn(&SeriesKey) -> bool,
+        resolution: Resolution,
+        from: u64,
+        to: u64,
+    ) -> Vec<(SeriesKey, Vec<Bucket>)> {
+        self.data
+            .read(pid, |metrics| {
+                metrics
+                    .series
+                    .iter()
+                    .filter(|(key, _)| filter(key))
+                    .map(|(key, series)| (key.clone(), series.query(resolution, from, to)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn series_keys(&self, pid: u32) -> Vec<SeriesKey> {
+        self.data
+            .read(pid, |metrics| metrics.series.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn remove(&self, pid: u32) -> Option<ProcessMetrics> {
+        self.data.remove(pid)
+    }
+
+    /// 只保留满足条件的进程的指标
+    pub fn retain_pids(&self, f: impl Fn(u32) -> bool) {
+        self.data.retain(|pid, _| f(*pid));
+    }
+
+    /// 所有序列占用的内存估算，单位 byte
+    pub fn estimated_bytes(&self) -> usize {
+        let mut bytes = 0;
+        self.data.for_each(|_, metrics| {
+            bytes += metrics
+                .series
+                .iter()
+                .map(|(key, series)| {
+                    std::mem::size_of::<SeriesKey>() + key.name.len() + series.estimated_bytes()
+                })
+                .sum::<usize>();
+        });
+        bytes
+    }
+
+    /// 移除所有序列中早于 cutoff 的数据，尚未被导出或落盘的原始样本除外
+    pub fn evict_before(&self, cutoff: u64) {
+        self.data.retain(|pid, metrics| {
+            let pinned = RAW_CURSORS.pinned(*pid);
+            for series in metrics.series.values_mut() {
+                series.evict_before(cutoff, pinned);
+            }
+            true
+        });
+    }
+
+    /// 所有进程指标的快照，用于持久化
+    pub fn snapshot(&self) -> HashMap<u32, ProcessMetrics> {
+        let mut snapshot = HashMap::new();
+        self.data.for_each(|pid, metrics| {
+            snapshot.insert(*pid, metrics.clone());
+        });
+        snapshot
+    }
+
+    pub fn restore(&self, pid: u32, metrics: ProcessMetrics) {
+        // 之后写入的样本序号要大于快照中的序号
+        let max_seq = metrics.series.values().map(Series::max_seq).max();
+        INGEST_SEQ.fetch_max(max_seq.unwrap_or(0), Ordering::Relaxed);
+        self.data.insert(pid, metrics);
+    }
+}
+
+pub static METRICS_STORE: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+// 指标数据与进程列表对账的间隔
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 清理进程的所有指标数据，进程从 store 中移除时直接调用
+pub fn remove_process_data(pid: u32) {
+    if METRICS_STORE.remove(pid).is_some() {
+        log_print!("🧹 已清理进程 {} 的指标数据", pid);
+    }
+    HANDLE_TRACKER.remove(pid);
+    ANOMALY_DETECTOR.remove(pid);
+    HISTOGRAM_STORE.remove(pid);
+    THREAD_SNAPSHOTS.remove(pid);
+}
+
+/// 清理已不在 store 中的进程的指标数据
+///
+/// 移除进程时已直接清理，这里兜底处理与移除并发写入的数据
+pub fn reconcile_process_data() {
+    let registered = |pid: u32| PROCESS_DATA.contains_key(pid);
+    METRICS_STORE.retain_pids(registered);
+    HANDLE_TRACKER.retain(|pid, _| registered(*pid));
+    ANOMALY_DETECTOR.retain(|pid, _| registered(*pid));
+    HISTOGRAM_STORE.retain(|pid, _| registered(*pid));
+    THREAD_SNAPSHOTS.retain(|pid, _| registered(*pid));
+}
+
+/// 在后台定时对账，清理残留的指标数据
+pub fn start_metrics_cleanup() {
+    task::spawn(async {
+        let mut ticker = interval(CLEANUP_INTERVAL);
+        loop {
+            ticker.tick().await;
+            reconcile_process_data();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str, thread_id: Option<u32>) -> SeriesKey {
+        SeriesKey {
+            name: name.to_string(),
+            thread_id,
+        }
+    }
+
+    #[test]
+    fn ring_buffer_overwrites_oldest_when_full() {
+        let mut buffer = RingBuffer::new(2);
+        assert_eq!(buffer.push(1), None);
+        assert_eq!(buffer.push(2), None);
+        assert_eq!(buffer.push(3), Some(1));
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(buffer.latest(), Some(&3));
+
+        buffer.evict_while(|item| *item < 3);
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), [3]);
+    }
+
+    #[test]
+    fn record_keeps_series_per_pid_and_thread() {
+        let metrics = Metrics::new();
+        metrics.record(1, None, 1_000, &[("cpu.load", 0.5), ("cpu.user_load", 0.2)]);
+        metrics.record(1, None, 2_000, &[("cpu.load", 0.7)]);
+        metrics.record(1, Some(7), 2_000, &[("cpu.load", 0.9)]);
+        metrics.record(2, None, 2_000, &[("cpu.load", 0.1)]);
+
+        let latest = metrics.latest(1);
+        assert_eq!(latest.len(), 3);
+        assert_eq!(
+            metrics.latest_sample(1, &key("cpu.load", None)),
+            Some(Sample {
+                timestamp: 2_000,
+                value: 0.7
+            })
+        );
+        assert_eq!(
+            metrics
+                .latest_sample(1, &key("cpu.load", Some(7)))
+                .unwrap()
+                .value,
+            0.9
+        );
+        assert_eq!(metrics.series_keys(2), [key("cpu.load", None)]);
+        assert!(metrics.latest(3).is_empty());
+
+        metrics.remove(1);
+        assert!(metrics.latest(1).is_empty());
+        assert_eq!(metrics.latest(2).len(), 1);
+    }
+
+    #[test]
+    fn raw_since_returns_samples_written_after_cursor() {
+        let metrics = Metrics::new();
+        metrics.record(1, None, 1_000, &[("cpu.load", 1.0)]);
+        let first = metrics.raw_since(1, 0);
+        assert_eq!(first.points.len(), 1);
+
+        metrics.record(1, None, 3_000, &[("cpu.load", 3.0)]);
+        // 迟到的样本与同一时间戳的其他序列都按写入顺序读到
+        metrics.record(1, None, 2_000, &[("cpu.load", 2.0)]);
+        metrics.record(1, Some(7), 1_000, &[("cpu.load", 0.5)]);
+        let next = metrics.raw_since(1, first.seq);
+        // 按写入顺序返回
+        let timestamps: Vec<u64> = next
+            .points
+            .iter()
+            .map(|(_, _, sample)| sample.timestamp)
+            .collect();
+        assert_eq!(timestamps, [3_000, 2_000, 1_000]);
+        assert!(next.points.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(next.points.last().unwrap().0, next.seq);
+        assert!(next.seq > first.seq);
+        assert!(metrics.raw_since(1, next.seq).points.is_empty());
+    }
+
+    #[test]
+    fn restored_samples_keep_their_sequence() {
+        let metrics = Metrics::new();
+        metrics.record(1, None, 1_000, &[("cpu.load", 1.0)]);
+        let restored: ProcessMetrics =
+            serde_json::from_value(serde_json::to_value(metrics.remove(1).unwrap()).unwrap())
+                .unwrap();
+        metrics.restore(1, restored);
+        let cursor = metrics.raw_since(1, 0).seq;
+        assert!(cursor > 0);
+        metrics.record(1, None, 2_000, &[("cpu.load", 2.0)]);
+        assert_eq!(metrics.raw_since(1, cursor).points.len(), 1);
+    }
+
+    #[test]
+    fn raw_samples_expire_after_retention() {
+        let mut series = Series::default();
+        series.push(Sample {
+            timestamp: 1_000,
+            value: 1.0,
+        });
+        series.push(Sample {
+            timestamp: 1_000 + RAW_RETENTION_MS + 1,
+            value: 2.0,
+        });
+        assert_eq!(series.raw.iter().count(), 1);
+        assert_eq!(series.latest().unwrap().value, 2.0);
+    }
+
+    fn sample(timestamp: u64, value: f64) -> Sample {
+        Sample { timestamp, value }
+    }
+
+    #[test]
+    fn rollup_tier_merges_out_of_order_samples_into_their_bucket() {
+        let mut tier = RollupTier::new(Resolution::TenSeconds);
+        tier.add(&sample(12_000, 1.0));
+        tier.add(&sample(25_000, 4.0));
+        // 迟到的样本仍落入 10s 桶
+        tier.add(&sample(18_000, 3.0));
+
+        let buckets: Vec<Bucket> = tier.buckets.iter().copied().collect();
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[0].timestamp, 10_000);
+        assert_eq!(buckets[0].count, 2);
+        assert_eq!(buckets[0].sum, 4.0);
+        assert_eq!((buckets[0].min, buckets[0].max), (1.0, 3.0));
+        assert_eq!(buckets[1].timestamp, 20_000);
+        assert_eq!(buckets[1].avg(), 4.0);
+    }
+
+    #[test]
+    fn rollup_tier_drops_samples_older_than_its_buckets() {
+        let mut tier = RollupTier::new(Resolution::TenSeconds);
+        let capacity = Resolution::TenSeconds.capacity() as u64;
+        for index in 0..=capacity {
+            tier.add(&sample(index * 10_000, 1.0));
+        }
+        // 第一个桶已被挤出，对应时间段的样本直接丢弃，不会插到最前面
+        tier.add(&sample(5_000, 1.0));
+        assert_eq!(tier.buckets.iter().next().unwrap().timestamp, 10_000);
+        assert!(tier.buckets.iter().all(|bucket| bucket.count == 1));
+    }
+
+    #[test]
+    fn resolution_for_range_picks_finest_covering_tier() {
+        let now = 100_000_000;
+        assert_eq!(Resolution::for_range(now - 60_000, now), Resolution::Raw);
+        assert_eq!(
+            Resolution::for_range(now - RAW_RETENTION_MS - 1, now),
+            Resolution::TenSeconds
+        );
+        assert_eq!(
+            Resolution::for_range(now - 3_600_001, now),
+            Resolution::OneMinute
+        );
+        // from 晚于 now 时按原始样本处理
+        assert_eq!(Resolution::for_range(now + 1_000, now), Resolution::Raw);
+    }
+
+    #[test]
+    fn query_with_resolution_reads_matching_tier() {
+        let metrics = Metrics::new();
+        for second in 0..120u64 {
+            metrics.record(1, None, second * 1_000, &[("cpu.load", second as f64)]);
+        }
+        let key = key("cpu.load", None);
+
+        let raw = metrics.query_with_resolution(1, &key, Resolution::Raw, 10_000, 19_999);
+        assert_eq!(raw.points.len(), 10);
+        assert!(raw.points.iter().all(|point| point.count == 1));
+
+        let ten = metrics.query_with_resolution(1, &key, Resolution::TenSeconds, 15_000, 30_000);
+        // 与范围有交集的桶都返回：10s、20s、30s
+        let starts: Vec<u64> = ten.points.iter().map(|point| point.timestamp).collect();
+        assert_eq!(starts, [10_000, 20_000, 30_000]);
+        assert_eq!(ten.points[0].count, 10);
+        assert_eq!(ten.points[0].avg(), 14.5);
+
+        let minute = metrics.query_with_resolution(1, &key, Resolution::OneMinute, 0, 119_999);
+        assert_eq!(minute.resolution, Resolution::OneMinute);
+        assert_eq!(minute.points.len(), 2);
+        assert_eq!((minute.points[1].min, minute.points[1].max), (60.0, 119.0));
+    }
+
+    #[test]
+    fn evict_before_drops_old_samples_and_buckets() {
+        let mut series = Series::default();
+        for second in 0..180u64 {
+            series.push(sample(second * 1_000, 1.0));
+        }
+        series.evict_before(120_000, None);
+        assert!(series
+            .query(Resolution::Raw, 0, u64::MAX)
+            .iter()
+            .all(|point| point.timestamp >= 120_000));
+        assert_eq!(series.query(Resolution::OneMinute, 0, u64::MAX).len(), 1);
+    }
+
+    #[test]
+    fn evict_before_keeps_samples_not_yet_consumed() {
+        let mut series = Series::default();
+        for second in 0..10u64 {
+            series.push(sample(second * 1_000, 1.0));
+        }
+        let seqs: Vec<u64> = series.raw_since(0).map(|(seq, _)| seq).collect();
+        // 只消费了前 3 个样本
+        series.evict_before(u64::MAX, Some(seqs[2]));
+        let left: Vec<u64> = series.raw_since(0).map(|(seq, _)| seq).collect();
+        assert_eq!(left, seqs[3..]);
+        series.evict_before(u64::MAX, None);
+        assert_eq!(series.raw_since(0).count(), 0);
+    }
+
+    #[test]
+    fn raw_cursors_pin_the_slowest_consumer() {
+        let registry: &'static RawCursors = Box::leak(Box::default());
+        assert_eq!(registry.pinned(1), None);
+        let exporter = registry.register();
+        let segments = registry.register();
+        exporter.advance(1, 9);
+        // 落盘还没有处理过该进程
+        assert_eq!(registry.pinned(1), Some(0));
+        segments.advance(1, 5);
+        assert_eq!(registry.pinned(1), Some(5));
+        assert_eq!(segments.get(1), 5);
+        drop(segments);
+        assert_eq!(registry.pinned(1), Some(9));
+        exporter.retain(|pid| pid != 1);
+        assert_eq!((exporter.get(1), registry.pinned(1)), (0, Some(0)));
+    }
 }
@@ -1,5 +1,9 @@
 pub mod config;
 pub mod constants;
 pub mod error;
+pub mod histogram;
 pub mod metrics;
 pub mod path;
+pub mod procfs;
+pub mod stats;
+pub mod time;
@@ -0,0 +1,412 @@
+use std::{fs, io};
+
+use serde::{Deserialize, Serialize};
+
+// Linux 上 sysconf(_SC_CLK_TCK) 基本固定为 100
+pub const CLOCK_TICKS_PER_SEC: u64 = 100;
+// 脱敏后的占位值
+const REDACTED: &str = "***";
+
+/// /proc/<pid>/stat 中 agent 关心的字段
+#[derive(Debug, Clone)]
+pub struct ProcStat {
+    pub pid: u32,
+    pub comm: String,
+    // R/S/D/Z/T 等进程状态
+    pub state: char,
+    pub ppid: u32,
+    // 用户态 CPU 时间，单位 clock tick
+    pub utime: u64,
+    // 内核态 CPU 时间，单位 clock tick
+    pub stime: u64,
+    // 进程启动时间，系统启动后的 clock tick 数
+    pub start_time: u64,
+}
+
+/// /proc/<pid>/status 中 agent 采样的字段，内存单位为字节
+#[derive(Debug, Clone, Default)]
+pub struct ProcStatus {
+    pub vm_rss: u64,
+    // 常驻内存峰值
+    pub vm_hwm: u64,
+    pub threads: u64,
+    pub voluntary_ctxt_switches: u64,
+    pub nonvoluntary_ctxt_switches: u64,
+}
+
+/// 进程存活检测结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Liveness {
+    Alive,
+    // 进程已退出或成为僵尸进程
+    Exited,
+    // pid 已被新进程复用
+    PidReused,
+    // 非 Linux 平台无法检测
+    Unknown,
+}
+
+/// 解析 /proc/<pid>/stat 的内容
+///
+/// comm 字段被括号包裹且可能包含空格，因此以最后一个 `)` 作为分隔
+pub fn parse_proc_stat(content: &str) -> Option<ProcStat> {
+    let open = content.find('(')?;
+    let close = content.rfind(')')?;
+    let pid = content[..open].trim().parse().ok()?;
+    let comm = content[open + 1..close].to_string();
+    // fields[0] 对应 man proc 中的第 3 个字段 state
+    let fields: Vec<&str> = content[close + 1..].split_whitespace().collect();
+    let field = |index: usize| fields.get(index - 3).copied();
+
+    Some(ProcStat {
+        pid,
+        comm,
+        state: field(3)?.chars().next()?,
+        ppid: field(4)?.parse().ok()?,
+        utime: field(14)?.parse().ok()?,
+        stime: field(15)?.parse().ok()?,
+        start_time: field(22)?.parse().ok()?,
+    })
+}
+
+pub fn read_proc_stat(pid: u32) -> io::Result<ProcStat> {
+    let content = fs::read_to_string(format!("/proc/{}/stat", pid))?;
+    parse_proc_stat(&content).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("无法解析 /proc/{}/stat", pid),
+        )
+    })
+}
+
+/// 解析 /proc/<pid>/status 的内容，缺失的字段为 0（例如内核线程没有 VmRSS）
+pub fn parse_proc_status(content: &str) -> ProcStatus {
+    let mut status = ProcStatus::default();
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        // 内存字段形如 `VmRSS:     1234 kB`
+        let number = value
+            .split_whitespace()
+            .next()
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(0);
+        match key {
+            "VmRSS" => status.vm_rss = number * 1024,
+            "VmHWM" => status.vm_hwm = number * 1024,
+            "Threads" => status.threads = number,
+            "voluntary_ctxt_switches" => status.voluntary_ctxt_switches = number,
+            "nonvoluntary_ctxt_switches" => status.nonvoluntary_ctxt_switches = number,
+            _ => {}
+        }
+    }
+    status
+}
+
+pub fn read_proc_status(pid: u32) -> io::Result<ProcStatus> {
+    let content = fs::read_to_string(format!("/proc/{}/status", pid))?;
+    Ok(parse_proc_status(&content))
+}
+
+/// 检测进程是否仍然存活，传入注册时记录的启动时间可识别 pid 复用
+#[cfg(target_os = "linux")]
+pub fn check_liveness(pid: u32, expected_start_time: Option<u64>) -> Liveness {
+    match read_proc_stat(pid) {
+        Ok(stat) if stat.state == 'Z' || stat.state == 'X' => Liveness::Exited,
+        Ok(stat) => match expected_start_time {
+            Some(start_time) if start_time != stat.start_time => Liveness::PidReused,
+            _ => Liveness::Alive,
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Liveness::Exited,
+        // 权限不足等情况无法判断，交给心跳超时处理
+        Err(_) => Liveness::Unknown,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn check_liveness(_pid: u32, _expected_start_time: Option<u64>) -> Liveness {
+    Liveness::Unknown
+}
+
+/// 读取进程在内核中的启动时间，非 Linux 平台返回 None
+#[cfg(target_os = "linux")]
+pub fn read_start_time(pid: u32) -> Option<u64> {
+    read_proc_stat(pid).ok().map(|stat| stat.start_time)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_start_time(_pid: u32) -> Option<u64> {
+    None
+}
+
+/// 注册时从 /proc/<pid> 采集的进程元信息，读取失败的字段为空
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProcessMetadata {
+    pub cmdline: Vec<String>,
+    pub cwd: Option<String>,
+    pub exe: Option<String>,
+    pub ppid: Option<u32>,
+    pub uid: Option<u32>,
+    // 进程启动时间 timestamp second
+    pub start_time: Option<u64>,
+    pub node_options: Option<String>,
+    pub node_env: Option<String>,
+}
+
+/// 读取系统启动时间 timestamp second（/proc/stat 中的 btime）
+fn read_boot_time() -> Option<u64> {
+    let content = fs::read_to_string("/proc/stat").ok()?;
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/// 读取 /proc/<pid>/status 中的真实 uid
+fn read_uid(pid: u32) -> Option<u32> {
+    let content = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))
+        .and_then(|value| value.split_whitespace().next())
+        .and_then(|uid| uid.parse().ok())
+}
+
+/// 读取 /proc/<pid>/environ 中指定的环境变量
+fn read_environ(pid: u32, keys: &[&str]) -> Vec<Option<String>> {
+    let content = fs::read(format!("/proc/{}/environ", pid)).unwrap_or_default();
+    let entries: Vec<String> = content
+        .split(|byte| *byte == 0)
+        .map(|entry| String::from_utf8_lossy(entry).to_string())
+        .collect();
+
+    keys.iter()
+        .map(|key| {
+            let prefix = format!("{}=", key);
+            entries
+                .iter()
+                .find_map(|entry| entry.strip_prefix(&prefix).map(|value| value.to_string()))
+        })
+        .collect()
+}
+
+fn read_link(pid: u32, name: &str) -> Option<String> {
+    fs::read_link(format!("/proc/{}/{}", pid, name))
+        .ok()
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+/// 对 `--name=value`、`--name value` 形式的参数脱敏，参数名命中任一关键字时隐藏其值
+pub fn redact_value(value: &str, patterns: &[String]) -> String {
+    redact_args(value.split_whitespace(), patterns).join(" ")
+}
+
+/// 逐个参数脱敏，用于 cmdline 与按空白拆分后的环境变量值
+pub fn redact_args<'a>(
+    args: impl IntoIterator<Item = &'a str>,
+    patterns: &[String],
+) -> Vec<String> {
+    let is_sensitive = |name: &str| {
+        let name = name.to_lowercase();
+        patterns
+            .iter()
+            .any(|pattern| name.contains(pattern.as_str()))
+    };
+
+    let mut redact_next = false;
+    args.into_iter()
+        .map(|token| {
+            if redact_next {
+                redact_next = false;
+                return REDACTED.to_string();
+            }
+            match token.split_once('=') {
+                Some((name, _)) if is_sensitive(name) => format!("{}={}", name, REDACTED),
+                Some(_) => token.to_string(),
+                None => {
+                    redact_next = token.starts_with('-') && is_sensitive(token);
+                    token.to_string()
+                }
+            }
+        })
+        .collect()
+}
+
+/// 采集进程元信息，cmdline 与环境变量的值按 patterns 脱敏
+#[cfg(target_os = "linux")]
+pub fn read_process_metadata(pid: u32, patterns: &[String]) -> Option<ProcessMetadata> {
+    let stat = read_proc_stat(pid).ok()?;
+    let args: Vec<String> = fs::read(format!("/proc/{}/cmdline", pid))
+        .map(|content| {
+            content
+                .split(|byte| *byte == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    // 启动参数中同样可能带有 --api-token=... 之类的敏感信息
+    let cmdline = redact_args(args.iter().map(String::as_str), patterns);
+    let start_time =
+        read_boot_time().map(|boot_time| boot_time + stat.start_time / CLOCK_TICKS_PER_SEC);
+    let mut environ = read_environ(pid, &["NODE_OPTIONS", "NODE_ENV"]).into_iter();
+
+    Some(ProcessMetadata {
+        cmdline,
+        cwd: read_link(pid, "cwd"),
+        exe: read_link(pid, "exe"),
+        ppid: Some(stat.ppid),
+        uid: read_uid(pid),
+        start_time,
+        node_options: environ
+            .next()
+            .flatten()
+            .map(|value| redact_value(&value, patterns)),
+        node_env: environ
+            .next()
+            .flatten()
+            .map(|value| redact_value(&value, patterns)),
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_process_metadata(_pid: u32, _patterns: &[String]) -> Option<ProcessMetadata> {
+    None
+}
+
+/// 读取主机名，优先使用 /proc/sys/kernel/hostname，其次是 HOSTNAME 环境变量
+pub fn read_hostname() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .map(|hostname| hostname.trim().to_string())
+        .filter(|hostname| !hostname.is_empty())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// 读取进程下所有线程的 /proc/<pid>/task/<tid>/stat，已退出的线程直接跳过
+pub fn read_thread_stats(pid: u32) -> io::Result<Vec<ProcStat>> {
+    let mut threads = Vec::new();
+    for entry in fs::read_dir(format!("/proc/{}/task", pid))? {
+        let path = entry?.path().join("stat");
+        if let Some(stat) = fs::read_to_string(path)
+            .ok()
+            .and_then(|content| parse_proc_stat(&content))
+        {
+            threads.push(stat);
+        }
+    }
+    threads.sort_by_key(|stat| stat.pid);
+    Ok(threads)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // comm 中包含空格与括号
+    const STAT: &str = "1234 (node (worker) 1) S 1 1234 1234 0 -1 4194560 5000 0 0 0 250 75 0 0 20 0 11 0 98765 1000000 2000 18446744073709551615 1 1 0 0 0 0 0 4096 0 0 0 0 17 3 0 0 0 0 0";
+
+    #[test]
+    fn parse_proc_stat_handles_comm_with_spaces() {
+        let stat = parse_proc_stat(STAT).unwrap();
+        assert_eq!(stat.pid, 1234);
+        assert_eq!(stat.comm, "node (worker) 1");
+        assert_eq!(stat.state, 'S');
+        assert_eq!(stat.ppid, 1);
+        assert_eq!(stat.utime, 250);
+        assert_eq!(stat.stime, 75);
+        assert_eq!(stat.start_time, 98765);
+    }
+
+    #[test]
+    fn parse_proc_stat_rejects_truncated_content() {
+        assert!(parse_proc_stat("1234 (node) S 1 1234").is_none());
+        assert!(parse_proc_stat("garbage").is_none());
+    }
+
+    #[test]
+    fn parse_proc_status_reads_memory_in_bytes() {
+        let status = parse_proc_status(
+            "Name:\tnode\nVmHWM:\t  204800 kB\nVmRSS:\t  102400 kB\nThreads:\t11\n\
+             voluntary_ctxt_switches:\t42\nnonvoluntary_ctxt_switches:\t7\n",
+        );
+        assert_eq!(status.vm_rss, 100 * 1024 * 1024);
+        assert_eq!(status.vm_hwm, 200 * 1024 * 1024);
+        assert_eq!(status.threads, 11);
+        assert_eq!(status.voluntary_ctxt_switches, 42);
+        assert_eq!(status.nonvoluntary_ctxt_switches, 7);
+    }
+
+    #[test]
+    fn parse_proc_status_defaults_missing_fields() {
+        // 内核线程没有 VmRSS
+        let status = parse_proc_status("Name:\tkthreadd\nThreads:\t1\nbroken line\n");
+        assert_eq!(status.vm_rss, 0);
+        assert_eq!(status.threads, 1);
+    }
+
+    fn patterns() -> Vec<String> {
+        vec!["token".to_string(), "secret".to_string()]
+    }
+
+    #[test]
+    fn redact_value_hides_sensitive_arguments() {
+        assert_eq!(
+            redact_value(
+                "--max-old-space-size=100 --api-token=abc --SECRET xyz --inspect",
+                &patterns()
+            ),
+            "--max-old-space-size=100 --api-token=*** --SECRET *** --inspect"
+        );
+        assert_eq!(redact_value("--token=abc", &[]), "--token=abc");
+    }
+
+    #[test]
+    fn redact_args_hides_sensitive_argv_entries() {
+        let args = [
+            "node",
+            "app.js",
+            "--api-token=abc",
+            "--secret",
+            "xyz",
+            "tokenless",
+        ];
+        assert_eq!(
+            redact_args(args, &patterns()),
+            [
+                "node",
+                "app.js",
+                "--api-token=***",
+                "--secret",
+                "***",
+                "tokenless"
+            ]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn read_process_metadata_reads_own_process() {
+        let metadata = read_process_metadata(std::process::id(), &patterns()).unwrap();
+        assert!(!metadata.cmdline.is_empty());
+        assert!(metadata.exe.is_some());
+        assert!(metadata.start_time.is_some());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn check_liveness_detects_pid_reuse_and_exit() {
+        let pid = std::process::id();
+        let start_time = read_start_time(pid).unwrap();
+        assert_eq!(check_liveness(pid, None), Liveness::Alive);
+        assert_eq!(check_liveness(pid, Some(start_time)), Liveness::Alive);
+        assert_eq!(
+            check_liveness(pid, Some(start_time + 1)),
+            Liveness::PidReused
+        );
+        // 超出 pid_max 的 pid 不可能存在
+        assert_eq!(check_liveness(u32::MAX, None), Liveness::Exited);
+    }
+}
@@ -0,0 +1,40 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// agent 自身的运行统计，通过 GET /metrics 暴露
+#[derive(Default)]
+pub struct AgentStats {
+    // 累计接入的 UDS 连接数
+    pub connections_total: AtomicU64,
+    // 当前保持的 UDS 连接数
+    pub connections_active: AtomicU64,
+    // 成功处理的消息数
+    pub messages_ingested: AtomicU64,
+    // 无法解析的消息数
+    pub parse_failures: AtomicU64,
+}
+
+impl AgentStats {
+    pub fn connection_opened(&self) {
+        self.connections_total.fetch_add(1, Ordering::Relaxed);
+        self.connections_active.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_closed(&self) {
+        self.connections_active.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn message_ingested(&self) {
+        self.messages_ingested.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn parse_failed(&self) {
+        self.parse_failures.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub static AGENT_STATS: AgentStats = AgentStats {
+    connections_total: AtomicU64::new(0),
+    connections_active: AtomicU64::new(0),
+    messages_ingested: AtomicU64::new(0),
+    parse_failures: AtomicU64::new(0),
+};
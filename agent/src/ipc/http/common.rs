@@ -1,28 +1,86 @@
-use axum::routing::MethodRouter;
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::StatusCode,
+    response::Json as ResponseJson,
+    routing::MethodRouter,
+};
 use serde::{Deserialize, Serialize};
 
+use crate::data_processor::{
+    budget::MemoryUsage,
+    segments::SegmentStats,
+    store::{deserialize_pid, ProcessStore},
+};
+
 pub trait BaseRouter {
     fn get_path(&self) -> &'static str;
     fn get_handler(&self) -> fn() -> MethodRouter;
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize)]
 pub struct BaseResponse {
     pub success: bool,
     pub message: String,
 }
 
+/// 接口失败时返回的状态码与消息体
+pub type ErrorResponse = (StatusCode, ResponseJson<BaseResponse>);
+
+pub fn error_response(status: StatusCode, message: impl Into<String>) -> ErrorResponse {
+    (
+        status,
+        ResponseJson(BaseResponse {
+            success: false,
+            message: message.into(),
+        }),
+    )
+}
+
+/// 将请求体解析失败转换为带具体原因的 400 响应，避免只返回 422 状态码
+pub fn json_rejection_response(rejection: JsonRejection) -> ErrorResponse {
+    error_response(
+        StatusCode::BAD_REQUEST,
+        format!("请求参数错误: {}", rejection.body_text()),
+    )
+}
+
+/// 路径参数解析失败时返回 400
+pub fn path_rejection_response(rejection: PathRejection) -> ErrorResponse {
+    error_response(
+        StatusCode::BAD_REQUEST,
+        format!("路径参数错误: {}", rejection.body_text()),
+    )
+}
+
+/// 查询参数解析失败时返回 400
+pub fn query_rejection_response(rejection: QueryRejection) -> ErrorResponse {
+    error_response(
+        StatusCode::BAD_REQUEST,
+        format!("查询参数错误: {}", rejection.body_text()),
+    )
+}
+
 #[derive(Serialize)]
 pub struct InfoResponse {
     pub name: String,
     pub version: String,
     pub status: String,
+    // 已注册进程及其元信息
+    pub processes: Vec<ProcessStore>,
+    // 各模块占用的内存估算
+    pub memory: MemoryUsage,
+    // 指标落盘的统计，未启用时为 null
+    pub segments: Option<SegmentStats>,
 }
 
 #[derive(Deserialize)]
 pub struct UpdateProcessRequest {
-    pub process_id: u16,
+    // process_id 与 data 只做格式校验，/update_process 目前只根据 action 返回结果
+    #[allow(dead_code)]
+    #[serde(deserialize_with = "deserialize_pid")]
+    pub process_id: u32,
     pub action: String,
+    #[allow(dead_code)]
     pub data: Option<serde_json::Value>,
 }
 
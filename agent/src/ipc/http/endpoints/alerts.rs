@@ -0,0 +1,49 @@
+use axum::{
+    response::Json as ResponseJson,
+    routing::{get, MethodRouter},
+};
+use serde::Serialize;
+
+use crate::data_processor::rules::{
+    firing_alerts, loaded_rules, resolved_alerts, Alert, LoadedRules,
+};
+
+use super::super::common::BaseRouter;
+
+pub struct AlertsRouter {
+    pub path: &'static str,
+    pub handler: fn() -> MethodRouter,
+}
+
+#[derive(Serialize)]
+pub struct AlertsResponse {
+    pub firing: Vec<Alert>,
+    // 最近恢复的告警
+    pub resolved: Vec<Alert>,
+    #[serde(flatten)]
+    pub rules: LoadedRules,
+}
+
+impl BaseRouter for AlertsRouter {
+    fn get_path(&self) -> &'static str {
+        self.path
+    }
+
+    fn get_handler(&self) -> fn() -> MethodRouter {
+        self.handler
+    }
+}
+
+pub const ALERTS_ROUTER: AlertsRouter = AlertsRouter {
+    path: "/alerts",
+    handler: || get(get_alerts),
+};
+
+// GET /alerts 接口处理函数，返回触发中的告警、最近恢复的告警以及当前规则
+async fn get_alerts() -> ResponseJson<AlertsResponse> {
+    ResponseJson(AlertsResponse {
+        firing: firing_alerts(),
+        resolved: resolved_alerts(),
+        rules: loaded_rules(),
+    })
+}
@@ -0,0 +1,82 @@
+use axum::{
+    extract::{rejection::PathRejection, Path},
+    http::StatusCode,
+    response::Json as ResponseJson,
+    routing::{get, MethodRouter},
+};
+use serde::Serialize;
+
+use crate::{
+    data_processor::{
+        anomaly::{Anomaly, Baseline, ANOMALY_DETECTOR},
+        store::PROCESS_MAP_STORE,
+    },
+    helper::config::AppConfig,
+};
+
+use super::super::common::{error_response, path_rejection_response, BaseRouter, ErrorResponse};
+
+pub struct AnomaliesRouter {
+    pub path: &'static str,
+    pub handler: fn() -> MethodRouter,
+}
+
+#[derive(Serialize)]
+pub struct BaselineEntry {
+    pub name: String,
+    pub thread_id: Option<u32>,
+    #[serde(flatten)]
+    pub baseline: Baseline,
+}
+
+#[derive(Serialize)]
+pub struct AnomaliesResponse {
+    pub process_id: u32,
+    pub z_threshold: f64,
+    pub baselines: Vec<BaselineEntry>,
+    pub anomalies: Vec<Anomaly>,
+}
+
+impl BaseRouter for AnomaliesRouter {
+    fn get_path(&self) -> &'static str {
+        self.path
+    }
+
+    fn get_handler(&self) -> fn() -> MethodRouter {
+        self.handler
+    }
+}
+
+pub const ANOMALIES_ROUTER: AnomaliesRouter = AnomaliesRouter {
+    path: "/processes/:pid/anomalies",
+    handler: || get(get_anomalies),
+};
+
+// GET /processes/:pid/anomalies 接口处理函数，返回各序列的基线以及最近的异常窗口
+async fn get_anomalies(
+    pid: Result<Path<u32>, PathRejection>,
+) -> Result<ResponseJson<AnomaliesResponse>, ErrorResponse> {
+    let Path(pid) = pid.map_err(path_rejection_response)?;
+    if !PROCESS_MAP_STORE.contains(&pid) {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            format!("进程 {} 未注册", pid),
+        ));
+    }
+    let (baselines, anomalies) = ANOMALY_DETECTOR
+        .read(pid, |detector| (detector.baselines(), detector.anomalies()))
+        .unwrap_or_default();
+    Ok(ResponseJson(AnomaliesResponse {
+        process_id: pid,
+        z_threshold: AppConfig::global().anomaly_z_threshold,
+        baselines: baselines
+            .into_iter()
+            .map(|(key, baseline)| BaselineEntry {
+                name: key.name,
+                thread_id: key.thread_id,
+                baseline,
+            })
+            .collect(),
+        anomalies,
+    }))
+}
@@ -1,18 +1,20 @@
 use axum::{
-    extract::Json,
+    extract::{rejection::JsonRejection, Json},
     http::StatusCode,
     response::Json as ResponseJson,
     routing::{post, MethodRouter},
 };
 use serde::Deserialize;
-use std::time::{self, SystemTime};
 
 use crate::{
-    data_processor::store::{PartialProcessStore, PROCESS_MAP_STORE},
+    data_processor::store::{deserialize_pid, PartialProcessStore, PROCESS_MAP_STORE},
+    helper::time::now_secs,
     log_print,
 };
 
-use super::super::common::{BaseResponse, BaseRouter};
+use super::super::common::{
+    error_response, json_rejection_response, BaseResponse, BaseRouter, ErrorResponse,
+};
 
 pub struct HeartbeatRouter {
     pub path: &'static str,
@@ -21,12 +23,13 @@ pub struct HeartbeatRouter {
 
 #[derive(Deserialize)]
 pub struct HeartbeatRequest {
-    process_id: u16,
+    #[serde(deserialize_with = "deserialize_pid")]
+    process_id: u32,
 }
 
 impl BaseRouter for HeartbeatRouter {
     fn get_path(&self) -> &'static str {
-        &self.path
+        self.path
     }
 
     fn get_handler(&self) -> fn() -> MethodRouter {
@@ -40,24 +43,61 @@ pub const HEARTBEAT_ROUTER: HeartbeatRouter = HeartbeatRouter {
 };
 
 pub async fn heartbeat(
-    Json(payload): Json<HeartbeatRequest>,
-) -> Result<ResponseJson<BaseResponse>, StatusCode> {
+    payload: Result<Json<HeartbeatRequest>, JsonRejection>,
+) -> Result<ResponseJson<BaseResponse>, ErrorResponse> {
+    let Json(payload) = payload.map_err(json_rejection_response)?;
     log_print!("/heartbeat {:?}", payload.process_id);
     // 更新进程最后的心跳时间
-    let secs = SystemTime::now()
-        .duration_since(time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-    PROCESS_MAP_STORE.update(
+    let updated = PROCESS_MAP_STORE.update(
         &payload.process_id,
         PartialProcessStore {
-            uds_port: None,
-            latest_heartbeat_time: Some(secs),
+            latest_heartbeat_time: Some(now_secs()),
+            ..Default::default()
         },
     );
+    if !updated {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            format!(
+                "进程 {} 未注册，请先调用 POST /register 注册",
+                payload.process_id
+            ),
+        ));
+    }
     let response = BaseResponse {
         success: true,
         message: "ok".to_string(),
     };
     Ok(ResponseJson(response))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_processor::store::ProcessStore;
+
+    #[tokio::test]
+    async fn heartbeat_for_unregistered_process_returns_404() {
+        let request = HeartbeatRequest {
+            process_id: 4_100_101,
+        };
+        let (status, _) = heartbeat(Ok(Json(request))).await.err().unwrap();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn heartbeat_updates_registered_process() {
+        let pid = 4_100_102;
+        let mut process = ProcessStore::for_test(pid, "demo");
+        // 不超过 stale 阈值，避免并行执行的 reaper 测试改动该进程
+        let before = now_secs() - 5;
+        process.latest_heartbeat_time = before;
+        PROCESS_MAP_STORE.register(process);
+
+        let request = HeartbeatRequest { process_id: pid };
+        let response = heartbeat(Ok(Json(request))).await.unwrap();
+        assert!(response.success);
+        let process = PROCESS_MAP_STORE.get(&pid).unwrap();
+        assert!(process.latest_heartbeat_time > before);
+    }
+}
@@ -0,0 +1,40 @@
+use axum::{
+    extract::{rejection::QueryRejection, Query},
+    http::StatusCode,
+    response::Json as ResponseJson,
+    routing::{get, MethodRouter},
+};
+
+use crate::data_processor::histograms::{query_histograms, HistogramQuery, HistogramQueryResult};
+
+use super::super::common::{error_response, query_rejection_response, BaseRouter, ErrorResponse};
+
+pub struct HistogramsRouter {
+    pub path: &'static str,
+    pub handler: fn() -> MethodRouter,
+}
+
+impl BaseRouter for HistogramsRouter {
+    fn get_path(&self) -> &'static str {
+        self.path
+    }
+
+    fn get_handler(&self) -> fn() -> MethodRouter {
+        self.handler
+    }
+}
+
+pub const HISTOGRAMS_ROUTER: HistogramsRouter = HistogramsRouter {
+    path: "/histograms/query",
+    handler: || get(get_histograms_query),
+};
+
+// GET /histograms/query?name=http.server.duration&group_by=all 接口处理函数，合并直方图后返回分位数
+async fn get_histograms_query(
+    query: Result<Query<HistogramQuery>, QueryRejection>,
+) -> Result<ResponseJson<HistogramQueryResult>, ErrorResponse> {
+    let Query(query) = query.map_err(query_rejection_response)?;
+    query_histograms(&query)
+        .map(ResponseJson)
+        .map_err(|e| error_response(StatusCode::BAD_REQUEST, e))
+}
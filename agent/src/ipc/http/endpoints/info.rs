@@ -2,8 +2,13 @@ use axum::{
     response::Json as ResponseJson,
     routing::{get, MethodRouter},
 };
+use tokio::task;
 
-use super::super::common::{BaseResponse, BaseRouter, InfoResponse};
+use crate::data_processor::{
+    budget::memory_usage, segments::segment_stats, store::PROCESS_MAP_STORE,
+};
+
+use super::super::common::{BaseRouter, InfoResponse};
 
 pub struct InfoRouter {
     pub path: &'static str,
@@ -12,7 +17,7 @@ pub struct InfoRouter {
 
 impl BaseRouter for InfoRouter {
     fn get_path(&self) -> &'static str {
-        &self.path
+        self.path
     }
 
     fn get_handler(&self) -> fn() -> MethodRouter {
@@ -27,10 +32,16 @@ pub const INFO_ROUTER: InfoRouter = InfoRouter {
 
 // GET /info 接口处理函数
 async fn get_info() -> ResponseJson<InfoResponse> {
+    let mut processes = PROCESS_MAP_STORE.list();
+    processes.sort_by_key(|process| process.process_id);
     let info = InfoResponse {
         name: "mitojs-agent".to_string(),
         version: "0.1.0".to_string(),
         status: "running".to_string(),
+        processes,
+        memory: memory_usage(),
+        // 落盘写入期间会持有 segment 存储的锁
+        segments: task::spawn_blocking(segment_stats).await.ok().flatten(),
     };
     ResponseJson(info)
 }
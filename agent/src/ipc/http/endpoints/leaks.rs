@@ -0,0 +1,64 @@
+use axum::{
+    extract::{rejection::PathRejection, Path},
+    http::StatusCode,
+    response::Json as ResponseJson,
+    routing::{get, MethodRouter},
+};
+use serde::Serialize;
+
+use crate::{
+    data_processor::{
+        leaks::{top_suspects, LeakSuspect},
+        store::PROCESS_MAP_STORE,
+    },
+    helper::{config::AppConfig, time::now_millis},
+};
+
+use super::super::common::{error_response, path_rejection_response, BaseRouter, ErrorResponse};
+
+pub struct LeaksRouter {
+    pub path: &'static str,
+    pub handler: fn() -> MethodRouter,
+}
+
+#[derive(Serialize)]
+pub struct LeaksResponse {
+    pub process_id: u32,
+    // 检测窗口，单位秒
+    pub window: u64,
+    pub suspects: Vec<LeakSuspect>,
+}
+
+impl BaseRouter for LeaksRouter {
+    fn get_path(&self) -> &'static str {
+        self.path
+    }
+
+    fn get_handler(&self) -> fn() -> MethodRouter {
+        self.handler
+    }
+}
+
+pub const LEAKS_ROUTER: LeaksRouter = LeaksRouter {
+    path: "/processes/:pid/leaks",
+    handler: || get(get_leaks),
+};
+
+// GET /processes/:pid/leaks 接口处理函数，返回数量持续增长的句柄创建栈
+async fn get_leaks(
+    pid: Result<Path<u32>, PathRejection>,
+) -> Result<ResponseJson<LeaksResponse>, ErrorResponse> {
+    let Path(pid) = pid.map_err(path_rejection_response)?;
+    if !PROCESS_MAP_STORE.contains(&pid) {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            format!("进程 {} 未注册", pid),
+        ));
+    }
+    let window = AppConfig::global().leak_window;
+    Ok(ResponseJson(LeaksResponse {
+        process_id: pid,
+        window,
+        suspects: top_suspects(pid, now_millis(), window * 1000),
+    }))
+}
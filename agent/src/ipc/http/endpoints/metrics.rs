@@ -0,0 +1,229 @@
+use std::{collections::BTreeMap, fmt::Write, sync::atomic::Ordering};
+
+use axum::{
+    http::header::CONTENT_TYPE,
+    response::IntoResponse,
+    routing::{get, MethodRouter},
+};
+
+use crate::{
+    data_processor::store::PROCESS_MAP_STORE,
+    helper::{
+        metrics::{split_series_name, METRICS_STORE},
+        procfs::read_hostname,
+        stats::AGENT_STATS,
+    },
+};
+
+use super::super::common::BaseRouter;
+
+// 指标名前缀
+const METRIC_PREFIX: &str = "mitojs";
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+pub struct MetricsRouter {
+    pub path: &'static str,
+    pub handler: fn() -> MethodRouter,
+}
+
+impl BaseRouter for MetricsRouter {
+    fn get_path(&self) -> &'static str {
+        self.path
+    }
+
+    fn get_handler(&self) -> fn() -> MethodRouter {
+        self.handler
+    }
+}
+
+pub const METRICS_ROUTER: MetricsRouter = MetricsRouter {
+    path: "/metrics",
+    handler: || get(get_metrics),
+};
+
+/// 同名指标的所有样本，输出时共用一组 HELP/TYPE
+struct MetricFamily {
+    help: String,
+    kind: &'static str,
+    lines: Vec<String>,
+}
+
+#[derive(Default)]
+struct Exposition {
+    families: BTreeMap<String, MetricFamily>,
+}
+
+impl Exposition {
+    fn add(
+        &mut self,
+        name: &str,
+        help: &str,
+        kind: &'static str,
+        labels: &[(&str, &str)],
+        value: f64,
+    ) {
+        let family = self
+            .families
+            .entry(name.to_string())
+            .or_insert_with(|| MetricFamily {
+                help: help.to_string(),
+                kind,
+                lines: Vec::new(),
+            });
+        let labels = labels
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape_label_value(value)))
+            .collect::<Vec<_>>()
+            .join(",");
+        family
+            .lines
+            .push(format!("{}{{{}}} {}", name, labels, format_value(value)));
+    }
+
+    fn render(&self) -> String {
+        let mut output = String::new();
+        for (name, family) in &self.families {
+            let _ = writeln!(output, "# HELP {} {}", name, family.help);
+            let _ = writeln!(output, "# TYPE {} {}", name, family.kind);
+            for line in &family.lines {
+                let _ = writeln!(output, "{}", line);
+            }
+        }
+        output
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// 序列名转换为指标名，例如 `memory.heap_used` 转换为 `mitojs_memory_heap_used`
+fn metric_name(series_name: &str) -> String {
+    let name: String = series_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("{}_{}", METRIC_PREFIX, name)
+}
+
+// GET /metrics 接口处理函数，输出 Prometheus 文本格式的指标
+async fn get_metrics() -> impl IntoResponse {
+    let host = read_hostname();
+    let mut exposition = Exposition::default();
+
+    let processes = PROCESS_MAP_STORE.list();
+    for process in &processes {
+        let pid = process.process_id.to_string();
+        for (key, sample) in METRICS_STORE.latest(process.process_id) {
+            let (series_name, extra_label) = split_series_name(&key.name);
+            let name = metric_name(&series_name);
+            let thread_id = key.thread_id.map(|thread_id| thread_id.to_string());
+            let mut labels = vec![
+                ("pid", pid.as_str()),
+                ("app", process.app_name.as_str()),
+                ("host", host.as_str()),
+            ];
+            if let Some(thread_id) = &thread_id {
+                labels.push(("thread_id", thread_id.as_str()));
+            }
+            labels.extend(extra_label);
+            let help = format!("Latest value of series {}", key.name);
+            exposition.add(&name, &help, "gauge", &labels, sample.value);
+        }
+    }
+
+    let host_label = [("host", host.as_str())];
+    let agent_metrics = [
+        (
+            "agent_uds_connections_total",
+            "Total UDS connections accepted",
+            "counter",
+            AGENT_STATS.connections_total.load(Ordering::Relaxed),
+        ),
+        (
+            "agent_uds_connections_active",
+            "Currently open UDS connections",
+            "gauge",
+            AGENT_STATS.connections_active.load(Ordering::Relaxed),
+        ),
+        (
+            "agent_messages_ingested_total",
+            "Messages ingested successfully",
+            "counter",
+            AGENT_STATS.messages_ingested.load(Ordering::Relaxed),
+        ),
+        (
+            "agent_parse_failures_total",
+            "Messages that failed to parse",
+            "counter",
+            AGENT_STATS.parse_failures.load(Ordering::Relaxed),
+        ),
+        (
+            "agent_processes",
+            "Registered processes",
+            "gauge",
+            processes.len() as u64,
+        ),
+    ];
+    for (name, help, kind, value) in agent_metrics {
+        let name = format!("{}_{}", METRIC_PREFIX, name);
+        exposition.add(&name, help, kind, &host_label, value as f64);
+    }
+
+    (
+        [(CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)],
+        exposition.render(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exposition_groups_samples_under_one_help_and_type() {
+        let mut exposition = Exposition::default();
+        exposition.add("mitojs_cpu_load", "cpu", "gauge", &[("pid", "1")], 0.5);
+        exposition.add("mitojs_cpu_load", "cpu", "gauge", &[("pid", "2")], 1.0);
+        exposition.add("mitojs_agent_processes", "processes", "gauge", &[], 2.0);
+
+        assert_eq!(
+            exposition.render(),
+            "# HELP mitojs_agent_processes processes\n\
+             # TYPE mitojs_agent_processes gauge\n\
+             mitojs_agent_processes{} 2\n\
+             # HELP mitojs_cpu_load cpu\n\
+             # TYPE mitojs_cpu_load gauge\n\
+             mitojs_cpu_load{pid=\"1\"} 0.5\n\
+             mitojs_cpu_load{pid=\"2\"} 1\n"
+        );
+    }
+
+    #[test]
+    fn label_values_and_special_floats_are_escaped() {
+        assert_eq!(escape_label_value("a\\b\"c\nd"), "a\\\\b\\\"c\\nd");
+        assert_eq!(format_value(f64::NAN), "NaN");
+        assert_eq!(format_value(f64::INFINITY), "+Inf");
+        assert_eq!(format_value(f64::NEG_INFINITY), "-Inf");
+        assert_eq!(format_value(1.25), "1.25");
+    }
+
+    #[test]
+    fn series_names_become_prefixed_metric_names() {
+        assert_eq!(metric_name("memory.heap_used"), "mitojs_memory_heap_used");
+        assert_eq!(metric_name("gc.pause-ms"), "mitojs_gc_pause_ms");
+    }
+}
@@ -0,0 +1,44 @@
+use axum::{
+    extract::{rejection::QueryRejection, Query},
+    http::StatusCode,
+    response::Json as ResponseJson,
+    routing::{get, MethodRouter},
+};
+use tokio::task;
+
+use crate::data_processor::query::{query_all_metrics, MetricsQuery, MetricsQueryResult};
+
+use super::super::common::{error_response, query_rejection_response, BaseRouter, ErrorResponse};
+
+pub struct MetricsQueryRouter {
+    pub path: &'static str,
+    pub handler: fn() -> MethodRouter,
+}
+
+impl BaseRouter for MetricsQueryRouter {
+    fn get_path(&self) -> &'static str {
+        self.path
+    }
+
+    fn get_handler(&self) -> fn() -> MethodRouter {
+        self.handler
+    }
+}
+
+pub const METRICS_QUERY_ROUTER: MetricsQueryRouter = MetricsQueryRouter {
+    path: "/metrics/query",
+    handler: || get(get_metrics_query),
+};
+
+// GET /metrics/query?type=cpu&group_by=app 接口处理函数，跨进程查询历史序列
+async fn get_metrics_query(
+    query: Result<Query<MetricsQuery>, QueryRejection>,
+) -> Result<ResponseJson<MetricsQueryResult>, ErrorResponse> {
+    let Query(query) = query.map_err(query_rejection_response)?;
+    // 查询落盘数据时会读取 segment 文件
+    task::spawn_blocking(move || query_all_metrics(&query))
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map(ResponseJson)
+        .map_err(|e| error_response(StatusCode::BAD_REQUEST, e))
+}
@@ -1,3 +1,13 @@
+pub mod alerts;
+pub mod anomalies;
 pub mod heartbeat;
+pub mod histograms;
 pub mod info;
-pub mod update_process;
\ No newline at end of file
+pub mod leaks;
+pub mod metrics;
+pub mod metrics_query;
+pub mod process_metrics;
+pub mod processes;
+pub mod register;
+pub mod threads;
+pub mod update_process;
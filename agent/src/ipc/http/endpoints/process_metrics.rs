@@ -0,0 +1,57 @@
+use axum::{
+    extract::{
+        rejection::{PathRejection, QueryRejection},
+        Path, Query,
+    },
+    http::StatusCode,
+    response::Json as ResponseJson,
+    routing::{get, MethodRouter},
+};
+use tokio::task;
+
+use crate::data_processor::{
+    query::{query_process_metrics, MetricsQuery, MetricsQueryResult},
+    store::PROCESS_MAP_STORE,
+};
+
+use super::super::common::{
+    error_response, path_rejection_response, query_rejection_response, BaseRouter, ErrorResponse,
+};
+
+pub struct ProcessMetricsRouter {
+    pub path: &'static str,
+    pub handler: fn() -> MethodRouter,
+}
+
+impl BaseRouter for ProcessMetricsRouter {
+    fn get_path(&self) -> &'static str {
+        self.path
+    }
+
+    fn get_handler(&self) -> fn() -> MethodRouter {
+        self.handler
+    }
+}
+
+pub const PROCESS_METRICS_ROUTER: ProcessMetricsRouter = ProcessMetricsRouter {
+    path: "/processes/:pid/metrics",
+    handler: || get(get_process_metrics),
+};
+
+// GET /processes/:pid/metrics?type=cpu&from=&to=&step=&agg= 接口处理函数，返回按 step 对齐的历史序列
+async fn get_process_metrics(
+    pid: Result<Path<u32>, PathRejection>,
+    query: Result<Query<MetricsQuery>, QueryRejection>,
+) -> Result<ResponseJson<MetricsQueryResult>, ErrorResponse> {
+    let Path(pid) = pid.map_err(path_rejection_response)?;
+    let Query(query) = query.map_err(query_rejection_response)?;
+    let process = PROCESS_MAP_STORE
+        .get(&pid)
+        .ok_or_else(|| error_response(StatusCode::NOT_FOUND, format!("进程 {} 未注册", pid)))?;
+    // 查询落盘数据时会读取 segment 文件
+    task::spawn_blocking(move || query_process_metrics(&process, &query))
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map(ResponseJson)
+        .map_err(|e| error_response(StatusCode::BAD_REQUEST, e))
+}
@@ -0,0 +1,49 @@
+use axum::{
+    response::Json as ResponseJson,
+    routing::{get, MethodRouter},
+};
+use serde::Serialize;
+
+use crate::data_processor::{
+    reaper::{recent_evictions, EvictedProcess},
+    store::{ProcessStore, PROCESS_MAP_STORE},
+};
+
+use super::super::common::BaseRouter;
+
+pub struct ProcessesRouter {
+    pub path: &'static str,
+    pub handler: fn() -> MethodRouter,
+}
+
+#[derive(Serialize)]
+pub struct ProcessesResponse {
+    pub processes: Vec<ProcessStore>,
+    // 最近因心跳超时被移除的进程
+    pub evicted: Vec<EvictedProcess>,
+}
+
+impl BaseRouter for ProcessesRouter {
+    fn get_path(&self) -> &'static str {
+        self.path
+    }
+
+    fn get_handler(&self) -> fn() -> MethodRouter {
+        self.handler
+    }
+}
+
+pub const PROCESSES_ROUTER: ProcessesRouter = ProcessesRouter {
+    path: "/processes",
+    handler: || get(get_processes),
+};
+
+// GET /processes 接口处理函数，返回已注册进程及其心跳状态
+async fn get_processes() -> ResponseJson<ProcessesResponse> {
+    let mut processes = PROCESS_MAP_STORE.list();
+    processes.sort_by_key(|process| process.process_id);
+    ResponseJson(ProcessesResponse {
+        processes,
+        evicted: recent_evictions(),
+    })
+}
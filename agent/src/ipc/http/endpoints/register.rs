@@ -0,0 +1,148 @@
+use axum::{
+    extract::{rejection::JsonRejection, Json},
+    http::StatusCode,
+    response::Json as ResponseJson,
+    routing::{post, MethodRouter},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data_processor::store::{
+        deserialize_pid, generate_session_id, ProcessStatus, ProcessStore, PROCESS_MAP_STORE,
+    },
+    helper::{
+        config::AppConfig,
+        procfs::{read_process_metadata, read_start_time},
+        time::now_secs,
+    },
+    log_print,
+};
+
+use super::super::common::{error_response, json_rejection_response, BaseRouter, ErrorResponse};
+
+pub struct RegisterRouter {
+    pub path: &'static str,
+    pub handler: fn() -> MethodRouter,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterRequest {
+    #[serde(deserialize_with = "deserialize_pid")]
+    process_id: u32,
+    uds_path: String,
+    app_name: String,
+    // 进程启动时间 timestamp second
+    start_time: u64,
+    node_version: String,
+    // 内存预算不足时优先级低的进程先被清理，默认为 0
+    #[serde(default)]
+    priority: i32,
+}
+
+#[derive(Serialize)]
+pub struct RegisterResponse {
+    pub success: bool,
+    pub message: String,
+    pub session_id: String,
+}
+
+impl BaseRouter for RegisterRouter {
+    fn get_path(&self) -> &'static str {
+        self.path
+    }
+
+    fn get_handler(&self) -> fn() -> MethodRouter {
+        self.handler
+    }
+}
+
+pub const REGISTER_ROUTER: RegisterRouter = RegisterRouter {
+    path: "/register",
+    handler: || post(register),
+};
+
+// POST /register 接口处理函数，重复注册会覆盖旧的进程信息并分配新的会话 id
+pub async fn register(
+    payload: Result<Json<RegisterRequest>, JsonRejection>,
+) -> Result<ResponseJson<RegisterResponse>, ErrorResponse> {
+    let Json(payload) = payload.map_err(json_rejection_response)?;
+    log_print!("/register {:?}", payload);
+    if payload.uds_path.is_empty() {
+        return Err(error_response(StatusCode::BAD_REQUEST, "uds_path 不能为空"));
+    }
+
+    let now = now_secs();
+    let session_id = generate_session_id();
+    PROCESS_MAP_STORE.register(ProcessStore {
+        process_id: payload.process_id,
+        session_id: session_id.clone(),
+        uds_path: payload.uds_path,
+        app_name: payload.app_name,
+        start_time: payload.start_time,
+        proc_start_time: read_start_time(payload.process_id),
+        node_version: payload.node_version,
+        register_time: now,
+        latest_heartbeat_time: now,
+        status: ProcessStatus::Active,
+        status_update_time: now,
+        exited_time: None,
+        metadata: read_process_metadata(payload.process_id, &AppConfig::global().redact.patterns),
+        priority: payload.priority,
+    });
+
+    let response = RegisterResponse {
+        success: true,
+        message: "ok".to_string(),
+        session_id,
+    };
+    Ok(ResponseJson(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(process_id: u32, uds_path: &str) -> RegisterRequest {
+        RegisterRequest {
+            process_id,
+            uds_path: uds_path.to_string(),
+            app_name: "demo".to_string(),
+            start_time: 1,
+            node_version: "v20.0.0".to_string(),
+            priority: 3,
+        }
+    }
+
+    #[tokio::test]
+    async fn register_stores_process_with_new_session() {
+        let pid = 4_100_001;
+        let first = register(Ok(Json(request(pid, "/tmp/a.sock"))))
+            .await
+            .unwrap();
+        let second = register(Ok(Json(request(pid, "/tmp/b.sock"))))
+            .await
+            .unwrap();
+        assert_ne!(first.session_id, second.session_id);
+
+        // 重复注册覆盖旧的进程信息
+        let process = PROCESS_MAP_STORE.get(&pid).unwrap();
+        assert_eq!(process.session_id, second.session_id);
+        assert_eq!(process.uds_path, "/tmp/b.sock");
+        assert_eq!(process.priority, 3);
+        assert_eq!(process.status, ProcessStatus::Active);
+    }
+
+    #[tokio::test]
+    async fn register_rejects_empty_uds_path() {
+        let pid = 4_100_002;
+        let (status, _) = register(Ok(Json(request(pid, "")))).await.err().unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!PROCESS_MAP_STORE.contains(&pid));
+    }
+
+    #[test]
+    fn register_request_rejects_out_of_range_pid() {
+        let body = r#"{"process_id":0,"uds_path":"/tmp/a.sock","app_name":"demo","start_time":1,"node_version":"v20"}"#;
+        assert!(serde_json::from_str::<RegisterRequest>(body).is_err());
+    }
+}
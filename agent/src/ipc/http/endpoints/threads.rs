@@ -0,0 +1,64 @@
+use axum::{
+    extract::{rejection::PathRejection, Path},
+    http::StatusCode,
+    response::Json as ResponseJson,
+    routing::{get, MethodRouter},
+};
+use serde::Serialize;
+
+use crate::data_processor::{
+    sampler::{ThreadsSnapshot, THREAD_SNAPSHOTS},
+    store::PROCESS_MAP_STORE,
+};
+
+use super::super::common::{error_response, path_rejection_response, BaseRouter, ErrorResponse};
+
+pub struct ThreadsRouter {
+    pub path: &'static str,
+    pub handler: fn() -> MethodRouter,
+}
+
+#[derive(Serialize)]
+pub struct ThreadsResponse {
+    pub process_id: u32,
+    #[serde(flatten)]
+    pub snapshot: ThreadsSnapshot,
+}
+
+impl BaseRouter for ThreadsRouter {
+    fn get_path(&self) -> &'static str {
+        self.path
+    }
+
+    fn get_handler(&self) -> fn() -> MethodRouter {
+        self.handler
+    }
+}
+
+pub const THREADS_ROUTER: ThreadsRouter = ThreadsRouter {
+    path: "/processes/:pid/threads",
+    handler: || get(get_threads),
+};
+
+// GET /processes/:pid/threads 接口处理函数，返回最近一次采样的线程 CPU 使用率
+async fn get_threads(
+    pid: Result<Path<u32>, PathRejection>,
+) -> Result<ResponseJson<ThreadsResponse>, ErrorResponse> {
+    let Path(pid) = pid.map_err(path_rejection_response)?;
+    if !PROCESS_MAP_STORE.contains(&pid) {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            format!("进程 {} 未注册", pid),
+        ));
+    }
+    let snapshot = THREAD_SNAPSHOTS.get(pid).ok_or_else(|| {
+        error_response(
+            StatusCode::NOT_FOUND,
+            format!("进程 {} 暂无线程采样数据", pid),
+        )
+    })?;
+    Ok(ResponseJson(ThreadsResponse {
+        process_id: pid,
+        snapshot,
+    }))
+}
@@ -1,13 +1,14 @@
 use axum::{
-    extract::Json,
-    http::StatusCode,
+    extract::{rejection::JsonRejection, Json},
     response::Json as ResponseJson,
     routing::{post, MethodRouter},
 };
 
 use crate::log_print;
 
-use super::super::common::{BaseRouter, UpdateProcessRequest, UpdateProcessResponse};
+use super::super::common::{
+    json_rejection_response, BaseRouter, ErrorResponse, UpdateProcessRequest, UpdateProcessResponse,
+};
 
 pub struct UpdateProcessRouter {
     pub path: &'static str,
@@ -16,7 +17,7 @@ pub struct UpdateProcessRouter {
 
 impl BaseRouter for UpdateProcessRouter {
     fn get_path(&self) -> &'static str {
-        &self.path
+        self.path
     }
 
     fn get_handler(&self) -> fn() -> MethodRouter {
@@ -31,8 +32,9 @@ pub const UPDATE_PROCESS_ROUTER: UpdateProcessRouter = UpdateProcessRouter {
 
 // POST /update_process 接口处理函数
 async fn update_process(
-    Json(payload): Json<UpdateProcessRequest>,
-) -> Result<ResponseJson<UpdateProcessResponse>, StatusCode> {
+    payload: Result<Json<UpdateProcessRequest>, JsonRejection>,
+) -> Result<ResponseJson<UpdateProcessResponse>, ErrorResponse> {
+    let Json(payload) = payload.map_err(json_rejection_response)?;
     // 这里可以根据实际需求处理进程更新逻辑
     log_print!("Received update_process request: {:?}", payload.action);
 
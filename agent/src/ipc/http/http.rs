@@ -15,7 +15,11 @@ use crate::{error_print, log_print};
 use super::{
     common::BaseRouter,
     endpoints::{
-        heartbeat::HEARTBEAT_ROUTER, info::INFO_ROUTER, update_process::UPDATE_PROCESS_ROUTER,
+        alerts::ALERTS_ROUTER, anomalies::ANOMALIES_ROUTER, heartbeat::HEARTBEAT_ROUTER,
+        histograms::HISTOGRAMS_ROUTER, info::INFO_ROUTER, leaks::LEAKS_ROUTER,
+        metrics::METRICS_ROUTER, metrics_query::METRICS_QUERY_ROUTER,
+        process_metrics::PROCESS_METRICS_ROUTER, processes::PROCESSES_ROUTER,
+        register::REGISTER_ROUTER, threads::THREADS_ROUTER, update_process::UPDATE_PROCESS_ROUTER,
     },
 };
 
@@ -33,7 +37,21 @@ pub async fn setup_http_server(
         .route("/", get(get_agent_name))
         .layer(CorsLayer::permissive()); // CORS 支持
 
-    const ROUTERS: [&dyn BaseRouter; 3] = [&INFO_ROUTER, &UPDATE_PROCESS_ROUTER, &HEARTBEAT_ROUTER];
+    const ROUTERS: [&dyn BaseRouter; 13] = [
+        &INFO_ROUTER,
+        &UPDATE_PROCESS_ROUTER,
+        &REGISTER_ROUTER,
+        &HEARTBEAT_ROUTER,
+        &PROCESSES_ROUTER,
+        &METRICS_ROUTER,
+        &THREADS_ROUTER,
+        &LEAKS_ROUTER,
+        &PROCESS_METRICS_ROUTER,
+        &METRICS_QUERY_ROUTER,
+        &ALERTS_ROUTER,
+        &ANOMALIES_ROUTER,
+        &HISTOGRAMS_ROUTER,
+    ];
     for router in ROUTERS {
         app = app.route(router.get_path(), (router.get_handler())());
     }
@@ -61,7 +79,9 @@ pub async fn setup_http_server(
 }
 
 /// 分类服务器启动错误
-fn classify_server_error(error: &Box<dyn std::error::Error + Send + Sync>) -> ListenerResultType {
+fn classify_server_error(
+    error: &(dyn std::error::Error + Send + Sync + 'static),
+) -> ListenerResultType {
     // 检查是否是 IO 错误且为端口占用
     if let Some(io_error) = error.downcast_ref::<std::io::Error>() {
         return match io_error.kind() {
@@ -93,7 +113,7 @@ pub async fn start_http_server(config: AppConfig) {
                 error_print!("HTTP 服务器启动失败: {}", e);
                 let message = IpcMessage {
                     code: IpcMessageCode::Err,
-                    message: classify_server_error(&e).to_string(),
+                    message: classify_server_error(e.as_ref()).to_string(),
                 };
                 send_ipc_message(message);
             }
@@ -1,3 +1,4 @@
 pub mod common;
 pub mod endpoints;
+#[allow(clippy::module_inception)]
 pub mod http;
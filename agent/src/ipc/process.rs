@@ -31,7 +31,7 @@ pub fn write_message_for_ipc(message: IpcMessage) -> io::Result<()> {
 }
 
 pub fn send_ipc_message(message: IpcMessage) {
-    if let Err(_) = write_message_for_ipc(message) {
+    if write_message_for_ipc(message).is_err() {
         error_print!("write message for ipc failed");
         // std::process::exit(1);
     }
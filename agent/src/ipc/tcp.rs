@@ -6,7 +6,7 @@ use tokio::task;
 use tokio::time::interval;
 
 // 导入宏
-use crate::{log_print, debug_print, error_print};
+use crate::{error_print, log_print};
 
 // 定义回调函数类型
 pub type DataCallback = Arc<dyn Fn(&str) + Send + Sync>;
@@ -60,11 +60,7 @@ impl TcpSocket {
                 match self.listener.accept().await {
                     Ok((stream, addr)) => {
                         connection_count += 1;
-                        log_print!(
-                            "🔗 接受新连接: {} (当前连接数: {})",
-                            addr,
-                            connection_count
-                        );
+                        log_print!("🔗 接受新连接: {} (当前连接数: {})", addr, connection_count);
 
                         // 为每个连接创建一个处理任务
                         let callback_for_task = callback.clone();
@@ -95,7 +91,7 @@ impl TcpSocket {
 async fn handle_client_with_heartbeat(
     stream: TcpStream,
     callback: DataCallback,
-    config: TcpConfig,
+    _config: TcpConfig,
 ) {
     let (reader, writer) = stream.into_split();
 
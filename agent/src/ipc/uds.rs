@@ -7,6 +7,7 @@ use tokio::net::UnixStream;
 use tokio::task;
 
 use crate::data_processor::subscribe::data_subscription;
+use crate::helper::stats::AGENT_STATS;
 // 导入宏
 use crate::{error_print, log_print};
 
@@ -49,27 +50,26 @@ impl UdsSocket {
         );
         // 使用 tokio::spawn 在后台处理连接
         task::spawn(async move {
+            // 由 tokio 监听连接，避免非阻塞 accept 轮询
+            let listener = match tokio::net::UnixListener::from_std(listener_clone) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    error_print!("UDS socket 注册到 tokio 失败: {}", e);
+                    return;
+                }
+            };
             loop {
-                match listener_clone.accept() {
+                match listener.accept().await {
                     Ok((stream, _addr)) => {
                         // 新 client 链接，创建新的任务处理
                         log_print!("Accepted connection from: {:?}, {:?}", stream, _addr);
 
-                        // 将 std::os::unix::net::UnixStream 转换为 tokio::net::UnixStream
-                        stream.set_nonblocking(true).unwrap();
-                        let tokio_stream = UnixStream::from_std(stream).unwrap();
-
                         // 为每个连接创建一个处理任务，每个链接可能是在独立的线程中处理
                         let callback_for_independent_task = callback.clone();
                         task::spawn(async move {
-                            handle_client(tokio_stream, callback_for_independent_task).await;
+                            handle_client(stream, callback_for_independent_task).await;
                         });
                     }
-                    Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                        // 非阻塞模式下没有连接时会返回 WouldBlock，等待一下再继续
-                        log_print!("WouldBlock, sleep 10ms");
-                        tokio::time::sleep(tokio::time::Duration::from_millis(2000)).await;
-                    }
                     Err(e) => {
                         error_print!("Error accepting connection: {}", e);
                         break;
@@ -82,6 +82,7 @@ impl UdsSocket {
 
 // 处理客户端连接的异步函数
 async fn handle_client(mut stream: UnixStream, callback: DataCallback) {
+    AGENT_STATS.connection_opened();
     let mut buf_reader = BufReader::new(&mut stream);
     let mut buffer = String::new();
 
@@ -103,6 +104,7 @@ async fn handle_client(mut stream: UnixStream, callback: DataCallback) {
             }
         }
     }
+    AGENT_STATS.connection_closed();
 }
 
 impl Drop for UdsSocket {
@@ -1,13 +1,18 @@
 mod data_processor;
+mod exporter;
 mod helper;
 mod ipc;
 #[macro_use]
 mod marco;
 
-use crate::data_processor::store::{ProcessStore, PROCESS_MAP_STORE};
-use crate::helper::config::AppConfig;
-use crate::ipc::http;
-use tokio::signal;
+use crate::data_processor::{budget, persist, reaper, rules, sampler, segments};
+use crate::exporter::{influx::InfluxExporter, otlp::OtlpExporter};
+use crate::helper::{config::AppConfig, metrics, path::get_socket_path};
+use crate::ipc::{http, uds};
+use tokio::signal::{
+    self,
+    unix::{signal as unix_signal, SignalKind},
+};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -20,16 +25,46 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .map_err(|e| format!("配置验证失败: {}", e))?;
 
     config.print_config();
+    AppConfig::init_global(config.clone());
+
+    persist::restore_on_startup(&config.agent_dir);
+    persist::start_persist(config.agent_dir.clone(), config.persist_interval);
+    reaper::start_reaper(config.reaper.clone());
+    metrics::start_metrics_cleanup();
+    sampler::start_os_sampler(config.os_sample_interval);
+    rules::start_rules(&config.agent_dir, config.rules_interval);
+    budget::start_budget_enforcer();
+    segments::start_segments(&config.agent_dir, config.segments.clone());
+    if let Some(otlp) = &config.otlp {
+        let exporter = OtlpExporter::new(otlp)?;
+        exporter::start_exporter(Box::new(exporter), otlp.interval, otlp.batch_size);
+    }
+    if let Some(influx) = &config.influx {
+        let exporter = InfluxExporter::new(influx)?;
+        exporter::start_exporter(Box::new(exporter), influx.interval, influx.batch_size);
+    }
+
+    // Node 进程通过 UDS 上报指标，socket 文件在 drop 时删除，需要持有到退出
+    let _uds_socket = uds::setup_uds_server(get_socket_path()).await?;
 
     let config_clone = config.clone();
 
     http::http::start_http_server(config_clone).await;
 
+    // Node 侧重启 agent 时发送 SIGTERM
+    let mut terminate = unix_signal(SignalKind::terminate())?;
     tokio::select! {
         _ = signal::ctrl_c() => {
             log_print!("\n🛑 收到 Ctrl+C 信号，正在关闭...");
         }
+        _ = terminate.recv() => {
+            log_print!("🛑 收到 SIGTERM 信号，正在关闭...");
+        }
+    }
+    if let Err(e) = persist::save_snapshot(&config.agent_dir) {
+        error_print!("保存 store 快照失败: {}", e);
     }
+    segments::flush_segments().await;
     log_print!("✅ Agent 已优雅关闭");
     Ok(())
 }